@@ -65,7 +65,13 @@ fn main() {
         }
     }
 
-    let api = NetboxApi::new(&config.sync.netbox);
+    let api = match NetboxApi::new(&config.sync.netbox) {
+        Ok(api) => api,
+        Err(e) => {
+            error!("{}", e);
+            std::process::exit(exitcode::CONFIG);
+        }
+    };
     for ip in api.get_reservations().unwrap() {
         let addr: IpAddr = std::net::IpAddr::V4(ip.address());
         let last = last_lease.get(&addr);
@@ -1,10 +1,8 @@
 use log::error;
-use netbox_windhcp::Config;
-#[cfg(target_os = "windows")]
-use netbox_windhcp::{cli, Sync};
+use netbox_windhcp::{cli, Config, Sync};
 
 fn main() {
-    let config = match Config::load_from_file() {
+    let mut config = match Config::load_from_file() {
         Ok(config) => config,
         Err(e) => {
             println!("Error reading config: {}", e);
@@ -14,21 +12,36 @@ fn main() {
 
     config.log.setup("sync");
 
-    #[cfg(target_os = "windows")]
     let cli_args = cli::Sync::init();
+    if cli_args.explain_filter {
+        config.sync.netbox.set_explain_filter(true);
+    }
+    config.sync.incremental.set_force_full(cli_args.full);
+
+    let result = match cli_args.command {
+        Some(cli::Command::Diff { export }) => Sync::offline(config.sync, cli_args.scope, &export)
+            .and_then(|mut sync| sync.set_targets(&cli_args.changed, &cli_args.interface, &cli_args.vminterface).map(|_| sync))
+            .and_then(|sync| sync.diff().map(|_| ())),
+        #[cfg(target_os = "windows")]
+        command => Sync::new(config.sync, cli_args.noop, cli_args.scope)
+            .and_then(|mut sync| sync.set_targets(&cli_args.changed, &cli_args.interface, &cli_args.vminterface).map(|_| sync))
+            .and_then(|mut sync| match command {
+                Some(cli::Command::Rollback { snapshot }) => sync.rollback(&snapshot),
+                Some(cli::Command::Export { output }) => sync.export(&output),
+                _ => {
+                    sync.set_plan_file(cli_args.plan_file);
+                    sync.run()
+                },
+            }),
+        #[cfg(not(target_os = "windows"))]
+        _ => Err("Only works on Windows, except for diff".into()),
+    };
 
-    #[cfg(target_os = "windows")]
-    match Sync::new(config.sync, cli_args.noop, cli_args.scope).run() {
+    match result {
         Ok(_) => std::process::exit(exitcode::OK),
         Err(e) => {
             error!("{}", e);
             std::process::exit(exitcode::DATAERR);
         }
     }
-
-    #[cfg(not(target_os = "windows"))]
-    {
-        error!("Only works on Windows");
-        std::process::exit(exitcode::DATAERR);
-    }
 }
@@ -1,6 +1,8 @@
-use std::net::Ipv4Addr;
+use std::net::IpAddr;
+use std::path::PathBuf;
 
-use clap::Parser;
+use clap::{Parser, Subcommand};
+use ipnet::IpNet;
 
 /// Netbxo to Windows DHCP Syncer
 #[derive(Parser, Debug)]
@@ -10,7 +12,43 @@ pub struct Sync {
     #[arg(short, long, default_value_t = false)]
     pub noop: bool,
     #[arg(short, long)]
-    pub scope: Option<Ipv4Addr>,
+    pub scope: Option<IpAddr>,
+    /// Sync only the scopes containing this network or address, can be repeated
+    #[arg(long)]
+    pub changed: Vec<IpNet>,
+    /// Sync only the scopes containing an address of this device interface, can be repeated
+    #[arg(long)]
+    pub interface: Vec<u64>,
+    /// Sync only the scopes containing an address of this VM interface, can be repeated
+    #[arg(long)]
+    pub vminterface: Vec<u64>,
+    /// Log the Netbox objects excluded by the filter expressions and the clause excluding them
+    #[arg(long, default_value_t = false)]
+    pub explain_filter: bool,
+    /// Sync all scopes even if an incremental sync is configured
+    #[arg(long, default_value_t = false)]
+    pub full: bool,
+    /// Write the planned changes as JSON list to this file
+    #[arg(long)]
+    pub plan_file: Option<PathBuf>,
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Restore the scopes of a snapshot taken before a sync
+    Rollback {
+        snapshot: PathBuf,
+    },
+    /// Export all scopes of the DHCP server, as JSON for *.json files, YAML otherwise
+    Export {
+        output: PathBuf,
+    },
+    /// Show the changes a sync would make to an export file (YAML, JSON or Export-DhcpServer XML)
+    Diff {
+        export: PathBuf,
+    },
 }
 
 impl Sync {
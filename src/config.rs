@@ -1,18 +1,17 @@
 use std::error::Error;
-use std::fs::File;
+use std::fs::{self, File};
 
 use serde::Deserialize;
+use serde_yaml_ng::Value;
 
 use crate::logging::LogConfig;
 
 use super::server::config::WebhookConfig;
-#[cfg(target_os = "windows")]
 use super::sync::config::SyncConfig;
 
 #[derive(Debug, Deserialize)]
 pub struct Config {
     pub webhook: WebhookConfig,
-    #[cfg(target_os = "windows")]
     pub sync: SyncConfig,
     #[serde(default)]
     pub log: LogConfig,
@@ -28,9 +27,97 @@ impl Config {
     );
     const CONFIG_FILE_LOCAL: &'static str = concat!("./", env!("CARGO_PKG_NAME"), ".cfg");
 
+    /// Keys whose value may reference a secret as `env:NAME` or `file:PATH`.
+    const SECRET_KEYS: [&'static str; 5] = ["token", "secret", "api_token", "key_passphrase", "shared_secret"];
+
     pub fn load_from_file() -> Result<Self, Box<dyn Error>> {
         let file = File::open(Self::CONFIG_FILE).or(File::open(Self::CONFIG_FILE_LOCAL))?;
 
-        Ok(serde_yaml_ng::from_reader::<File, Config>(file)?)
+        let mut value = serde_yaml_ng::from_reader::<File, Value>(file)?;
+        Self::resolve_secrets(&mut value)?;
+
+        Ok(serde_yaml_ng::from_value::<Config>(value)?)
+    }
+
+    fn resolve_secrets(value: &mut Value) -> Result<(), String> {
+        match value {
+            Value::Mapping(map) => {
+                for (key, value) in map.iter_mut() {
+                    match (key.as_str(), value) {
+                        (Some(key), Value::String(s)) if Self::SECRET_KEYS.contains(&key) => {
+                            if let Some(secret) = resolve_secret(s)? {
+                                *s = secret;
+                            }
+                        },
+                        (_, value) => Self::resolve_secrets(value)?,
+                    }
+                }
+            },
+            Value::Sequence(seq) => {
+                for value in seq.iter_mut() {
+                    Self::resolve_secrets(value)?;
+                }
+            },
+            _ => {},
+        }
+        Ok(())
+    }
+}
+
+/// Resolves a secret reference, plain values are returned as `None`.
+fn resolve_secret(value: &str) -> Result<Option<String>, String> {
+    if let Some(name) = value.strip_prefix("env:") {
+        return std::env::var(name)
+            .map(Some)
+            .map_err(|e| format!("Secret reference {:?}: {}", value, e));
+    }
+
+    if let Some(path) = value.strip_prefix("file:") {
+        return fs::read_to_string(path)
+            .map(|secret| Some(secret.trim_end_matches(['\r', '\n']).to_string()))
+            .map_err(|e| format!("Secret reference {:?}: {}", value, e));
+    }
+
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_resolves_secret_references() {
+        let path = std::env::temp_dir().join(format!("netbox-windhcp-secret-{}", std::process::id()));
+        fs::write(&path, "FROMFILE\n").unwrap();
+        std::env::set_var("NETBOX_WINDHCP_TEST_TOKEN", "FROMENV");
+
+        let mut value = serde_yaml_ng::from_str::<Value>(&format!(r#"---
+        webhook:
+            secret: file:{}
+        sync:
+            netbox:
+                token: env:NETBOX_WINDHCP_TEST_TOKEN
+                apiurl: env:NOT_A_SECRET
+            dhcp:
+                failover_relations:
+                    - name: DHCPFailover
+                      shared_secret: PLAIN
+        "#, path.display())).unwrap();
+        Config::resolve_secrets(&mut value).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(value["webhook"]["secret"].as_str(), Some("FROMFILE"));
+        assert_eq!(value["sync"]["netbox"]["token"].as_str(), Some("FROMENV"));
+        assert_eq!(value["sync"]["netbox"]["apiurl"].as_str(), Some("env:NOT_A_SECRET"));
+        assert_eq!(value["sync"]["dhcp"]["failover_relations"][0]["shared_secret"].as_str(), Some("PLAIN"));
+    }
+
+    #[test]
+    fn it_names_missing_secret_references() {
+        let err = resolve_secret("env:NETBOX_WINDHCP_TEST_MISSING").unwrap_err();
+        assert!(err.starts_with(r#"Secret reference "env:NETBOX_WINDHCP_TEST_MISSING": "#), "{}", err);
+        let err = resolve_secret("file:/nonexistent/netbox_token").unwrap_err();
+        assert!(err.starts_with(r#"Secret reference "file:/nonexistent/netbox_token": "#), "{}", err);
+        assert_eq!(resolve_secret("SECRET"), Ok(None));
     }
 }
@@ -0,0 +1,35 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use serde::Serialize;
+
+/// Writes the value as JSON to a temporary file first and renames it, so an interrupted write
+/// keeps the previous file.
+pub fn write_atomic<T: Serialize>(path: &Path, value: &T) -> io::Result<()> {
+    if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+        fs::create_dir_all(dir)?;
+    }
+
+    let tmp = path.with_extension("tmp");
+    fs::write(&tmp, serde_json::to_vec_pretty(value)?)?;
+    fs::rename(&tmp, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_replaces_the_file() {
+        let dir = std::env::temp_dir().join(format!("netbox-windhcp-json-{}", std::process::id()));
+        let path = dir.join("state.json");
+
+        write_atomic(&path, &vec![1]).unwrap();
+        write_atomic(&path, &vec![2]).unwrap();
+        assert_eq!(serde_json::from_slice::<Vec<u32>>(&fs::read(&path).unwrap()).unwrap(), vec![2]);
+        assert!(!path.with_extension("tmp").exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
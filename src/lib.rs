@@ -1,8 +1,8 @@
 pub mod config;
 pub use config::Config;
+pub mod json;
 pub mod logging;
 pub mod server;
 pub mod sync;
-#[cfg(target_os = "windows")]
 pub use sync::Sync;
 pub mod cli;
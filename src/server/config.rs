@@ -1,7 +1,11 @@
 use std::{net::SocketAddr, time::Duration};
 
+use ipnet::IpNet;
 use serde::Deserialize;
 
+use super::history::HistoryConfig;
+use super::webhook::{NetboxWebHook, NetboxWebHookEvent};
+
 #[derive(Debug, Deserialize, Clone, PartialEq, Eq)]
 pub struct WebhookConfig {
     pub listen: SocketAddr,
@@ -9,8 +13,14 @@ pub struct WebhookConfig {
     sync_standoff_time: Option<u64>,
     sync_timeout: Option<u64>,
     secret: Option<String>,
+    api_token: Option<String>,
     cert: Option<String>,
     key: Option<String>,
+    key_passphrase: Option<String>,
+    #[serde(default)]
+    rules: Vec<WebhookRule>,
+    #[serde(default)]
+    history: HistoryConfig,
 }
 
 impl WebhookConfig {
@@ -30,8 +40,9 @@ impl WebhookConfig {
         self.secret.as_ref()
     }
 
-    pub fn enable_tls(&self) -> bool {
-        self.cert.is_some() && self.key.is_some()
+    /// Bearer token of the sync job API, the API is disabled without it.
+    pub fn api_token(&self) -> Option<&String> {
+        self.api_token.as_ref()
     }
 
     pub fn cert(&self) -> Option<&String> {
@@ -41,6 +52,62 @@ impl WebhookConfig {
     pub fn key(&self) -> Option<&String> {
         self.key.as_ref()
     }
+
+    /// Passphrase of an encrypted PKCS#8 TLS key.
+    pub fn key_passphrase(&self) -> Option<&String> {
+        self.key_passphrase.as_ref()
+    }
+
+    pub fn history(&self) -> &HistoryConfig {
+        &self.history
+    }
+
+    /// Index of the first rule matching the webhook, without rules every webhook matches.
+    pub fn matching_rule(&self, hook: &NetboxWebHook) -> Option<usize> {
+        match self.rules.is_empty() {
+            true => Some(0),
+            false => self.rules.iter().position(|rule| rule.matches(hook)),
+        }
+    }
+}
+
+/// Webhooks triggering a sync, empty lists match everything.
+#[derive(Debug, Default, Deserialize, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct WebhookRule {
+    models: Vec<String>,
+    events: Vec<NetboxWebHookEvent>,
+    tags: Vec<String>,
+    family: Option<u8>,
+    within: Vec<IpNet>,
+}
+
+impl WebhookRule {
+    /// Tags and networks are checked on the object before and after the change,
+    /// so removing a tag or moving an address out of a network still matches.
+    pub fn matches(&self, hook: &NetboxWebHook) -> bool {
+        if !self.models.is_empty() && !self.models.contains(&hook.model) {
+            return false;
+        }
+        if !self.events.is_empty() && !self.events.contains(&hook.event) {
+            return false;
+        }
+        if !self.tags.is_empty() && !hook.tags().iter().any(|tag| self.tags.iter().any(|t| t == tag)) {
+            return false;
+        }
+
+        if self.family.is_none() && self.within.is_empty() {
+            return true;
+        }
+        hook.networks().iter().any(|net| {
+            let family = match net {
+                IpNet::V4(_) => 4,
+                IpNet::V6(_) => 6,
+            };
+            self.family.is_none_or(|f| f == family)
+                && (self.within.is_empty() || self.within.iter().any(|w| w.contains(net)))
+        })
+    }
 }
 
 impl Default for WebhookConfig {
@@ -51,8 +118,12 @@ impl Default for WebhookConfig {
             sync_standoff_time: Default::default(),
             sync_timeout: Default::default(),
             secret: Default::default(),
+            api_token: Default::default(),
             cert: Default::default(),
             key: Default::default(),
+            key_passphrase: Default::default(),
+            rules: Default::default(),
+            history: Default::default(),
         }
     }
 }
@@ -80,8 +151,10 @@ mod tests {
         sync_standoff_time: 42
         sync_timeout: 42
         secret: SECRET
+        api_token: TOKEN
         cert: cert.pem
         key: key.pem
+        key_passphrase: PASSPHRASE
         "#);
         assert_eq!(cfg.unwrap(), WebhookConfig {
             listen: "127.0.0.1:12345".parse().unwrap(),
@@ -89,11 +162,63 @@ mod tests {
             sync_standoff_time: Some(42),
             sync_timeout: Some(42),
             secret: Some(String::from("SECRET")),
+            api_token: Some(String::from("TOKEN")),
             cert: Some(String::from("cert.pem")),
             key: Some(String::from("key.pem")),
+            key_passphrase: Some(String::from("PASSPHRASE")),
+            rules: Vec::new(),
+            history: HistoryConfig::default(),
         });
     }
 
+    fn hook(json: &str) -> NetboxWebHook {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn it_matches_webhook_rules() {
+        let cfg = serde_yaml_ng::from_str::<WebhookConfig>(r#"---
+        listen: 127.0.0.1:12345
+        rules:
+            - models: [prefix, ipaddress]
+              tags: [dhcp]
+              family: 4
+              within: [10.0.0.0/8]
+            - models: [interface]
+              events: [updated, deleted]
+        "#).unwrap();
+
+        let prefix = hook(r#"{
+            "event": "updated", "timestamp": "2024-05-01 10:00:00+00:00", "model": "prefix",
+            "username": "admin", "request_id": "1",
+            "data": { "prefix": "10.1.0.0/24", "tags": [{ "name": "DHCP", "slug": "dhcp" }] }
+        }"#);
+        assert_eq!(cfg.matching_rule(&prefix), Some(0));
+
+        let untagged = hook(r#"{
+            "event": "updated", "timestamp": "2024-05-01 10:00:00+00:00", "model": "prefix",
+            "username": "admin", "request_id": "2",
+            "data": { "prefix": "10.1.0.0/24", "tags": [] },
+            "snapshots": { "prechange": { "prefix": "10.1.0.0/24", "tags": ["dhcp"] }, "postchange": {} }
+        }"#);
+        assert_eq!(cfg.matching_rule(&untagged), Some(0));
+
+        let outside = hook(r#"{
+            "event": "created", "timestamp": "2024-05-01 10:00:00+00:00", "model": "ipaddress",
+            "username": "admin", "request_id": "3",
+            "data": { "address": "192.168.1.10/24", "tags": [{ "name": "dhcp", "slug": "dhcp" }] }
+        }"#);
+        assert_eq!(cfg.matching_rule(&outside), None);
+
+        let interface = hook(r#"{
+            "event": "created", "timestamp": "2024-05-01 10:00:00+00:00", "model": "interface",
+            "username": "admin", "request_id": "4", "data": {}
+        }"#);
+        assert_eq!(cfg.matching_rule(&interface), None);
+
+        assert_eq!(WebhookConfig::default().matching_rule(&interface), Some(0));
+    }
+
     #[test]
     fn it_returns_sync_interval_as_durations() {
         let cfg = WebhookConfig {
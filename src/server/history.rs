@@ -0,0 +1,219 @@
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{self, BufReader};
+use std::path::PathBuf;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize, Serializer};
+
+use crate::json;
+
+#[derive(Debug, Deserialize, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct HistoryConfig {
+    file: Option<PathBuf>,
+    keep: usize,
+    output_lines: usize,
+}
+
+impl Default for HistoryConfig {
+    fn default() -> Self {
+        Self { file: None, keep: 20, output_lines: 100 }
+    }
+}
+
+impl HistoryConfig {
+    /// File keeping the history across restarts, the history is only kept in memory without it.
+    pub fn file(&self) -> Option<&PathBuf> {
+        self.file.as_ref()
+    }
+
+    pub fn output_lines(&self) -> usize {
+        self.output_lines
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum SyncTrigger {
+    Interval,
+    Webhook,
+    Api,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct SyncRun {
+    pub id: u64,
+    pub start: DateTime<Utc>,
+    pub end: Option<DateTime<Utc>>,
+    pub duration: Option<f64>,
+    pub triggers: Vec<SyncTrigger>,
+    pub args: Vec<String>,
+    pub exit_code: Option<i32>,
+    pub error: Option<String>,
+    pub output: Vec<String>,
+}
+
+/// Run without the captured output, as listed on `/status`.
+#[derive(Debug, Serialize)]
+struct SyncRunSummary<'a> {
+    id: u64,
+    start: DateTime<Utc>,
+    end: Option<DateTime<Utc>>,
+    duration: Option<f64>,
+    triggers: &'a [SyncTrigger],
+    args: &'a [String],
+    exit_code: Option<i32>,
+    error: Option<&'a str>,
+}
+
+impl<'a> From<&'a SyncRun> for SyncRunSummary<'a> {
+    fn from(run: &'a SyncRun) -> Self {
+        Self {
+            id: run.id,
+            start: run.start,
+            end: run.end,
+            duration: run.duration,
+            triggers: &run.triggers,
+            args: &run.args,
+            exit_code: run.exit_code,
+            error: run.error.as_deref(),
+        }
+    }
+}
+
+/// The last sync runs, newest first.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct RunHistory {
+    runs: VecDeque<SyncRun>,
+    #[serde(skip)]
+    config: HistoryConfig,
+}
+
+impl RunHistory {
+    pub fn load(config: &HistoryConfig) -> Result<Self, Box<dyn std::error::Error + Send + std::marker::Sync>> {
+        let mut history = match config.file() {
+            Some(path) => match File::open(path) {
+                Ok(file) => serde_json::from_reader(BufReader::new(file))
+                    .map_err(|e| format!("Unable to read run history {}: {}", path.display(), e))?,
+                Err(e) if e.kind() == io::ErrorKind::NotFound => Self::default(),
+                Err(e) => return Err(format!("Unable to open run history {}: {}", path.display(), e).into()),
+            },
+            None => Self::default(),
+        };
+        history.config = config.clone();
+        history.runs.truncate(config.keep);
+        Ok(history)
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        match self.config.file() {
+            Some(path) => json::write_atomic(path, self),
+            None => Ok(()),
+        }
+    }
+
+    /// Records the start of a run and returns its id.
+    pub fn start(&mut self, triggers: Vec<SyncTrigger>, args: Vec<String>) -> u64 {
+        let id = self.runs.front().map_or(1, |run| run.id + 1);
+        self.runs.push_front(SyncRun {
+            id,
+            start: Utc::now(),
+            end: None,
+            duration: None,
+            triggers,
+            args,
+            exit_code: None,
+            error: None,
+            output: Vec::new(),
+        });
+        self.runs.truncate(self.config.keep.max(1));
+        id
+    }
+
+    pub fn finish(&mut self, id: u64, exit_code: Option<i32>, error: Option<String>, output: Vec<String>) {
+        if let Some(run) = self.runs.iter_mut().find(|run| run.id == id) {
+            let end = Utc::now();
+            run.duration = Some((end - run.start).num_milliseconds() as f64 / 1000.0);
+            run.end = Some(end);
+            run.exit_code = exit_code;
+            run.error = error;
+            run.output = output;
+        }
+    }
+
+    pub fn get(&self, id: u64) -> Option<&SyncRun> {
+        self.runs.iter().find(|run| run.id == id)
+    }
+}
+
+/// Serializes the history without the captured output.
+pub fn serialize_summaries<S: Serializer>(history: &RunHistory, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(history.runs.iter().map(SyncRunSummary::from))
+}
+
+/// The last lines of the output of a run.
+#[derive(Debug)]
+pub struct OutputTail {
+    lines: VecDeque<String>,
+    limit: usize,
+}
+
+impl OutputTail {
+    pub fn new(limit: usize) -> Self {
+        Self { lines: VecDeque::new(), limit }
+    }
+
+    pub fn push(&mut self, line: String) {
+        self.lines.push_back(line);
+        while self.lines.len() > self.limit {
+            self.lines.pop_front();
+        }
+    }
+
+    pub fn take(&mut self) -> Vec<String> {
+        self.lines.drain(..).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn it_keeps_and_persists_the_last_runs() {
+        let path = std::env::temp_dir()
+            .join(format!("netbox-windhcp-history-{}", std::process::id()))
+            .join("runs.json");
+        let config = HistoryConfig { file: Some(path.clone()), keep: 2, output_lines: 10 };
+
+        let mut history = RunHistory::load(&config).unwrap();
+        for _ in 0..3 {
+            let id = history.start(vec![SyncTrigger::Webhook], vec![String::from("--changed"), String::from("10.0.0.10/32")]);
+            history.finish(id, Some(1), Some(String::from("command_status")), vec![String::from("ERROR failed")]);
+        }
+        history.save().unwrap();
+
+        let history = RunHistory::load(&config).unwrap();
+        assert_eq!(history.runs.iter().map(|run| run.id).collect::<Vec<_>>(), vec![3, 2]);
+        assert_eq!(history.get(3).unwrap().output, vec![String::from("ERROR failed")]);
+        assert_eq!(history.get(1), None);
+
+        let summaries = serde_json::to_value(history.runs.iter().map(SyncRunSummary::from).collect::<Vec<_>>()).unwrap();
+        assert_eq!(summaries[0]["exit_code"], 1);
+        assert!(summaries[0].get("output").is_none());
+
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn it_keeps_the_tail_of_the_output() {
+        let mut output = OutputTail::new(2);
+        for line in ["a", "b", "c"] {
+            output.push(line.to_string());
+        }
+        assert_eq!(output.take(), vec!["b", "c"]);
+    }
+}
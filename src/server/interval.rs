@@ -4,7 +4,8 @@ use tokio::{sync::broadcast, task::JoinHandle, time::sleep};
 
 use super::{
     config::WebhookConfig,
-    shared::{Message, SharedServerStatus},
+    history::SyncTrigger,
+    shared::{Message, SharedServerStatus, SyncRequest},
 };
 
 pub fn spawn(
@@ -19,7 +20,7 @@ pub fn spawn(
     tokio::spawn(async move {
         loop {
             let last_sync = {
-                status.lock().await.last_sync
+                status.lock().await.last_full_sync
             }.unwrap_or(Utc::now() - interval);
 
             let next_sync = last_sync + interval;
@@ -27,7 +28,7 @@ pub fn spawn(
             if next_sync <= Utc::now() {
                 {
                     let mut status = status.lock().await;
-                    status.needs_sync = true;
+                    status.request_sync(SyncRequest::Full, SyncTrigger::Interval);
                 }
                 match sync_tx.send(Message::TriggerSync) {
                     Ok(_) => info!("Intervall Sync triggerd"),
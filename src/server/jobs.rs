@@ -0,0 +1,141 @@
+use std::collections::VecDeque;
+use std::net::IpAddr;
+use std::path::Path;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Finished jobs kept for polling, queued and running jobs are always kept.
+const KEEP_FINISHED: usize = 100;
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum JobState {
+    Queued,
+    Running,
+    Succeeded,
+    Failed,
+}
+
+/// Parameters of `POST /sync`.
+#[derive(Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct JobParams {
+    pub scope: Option<IpAddr>,
+    pub noop: bool,
+}
+
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct Job {
+    pub id: u64,
+    pub state: JobState,
+    pub scope: Option<IpAddr>,
+    pub noop: bool,
+    pub created: DateTime<Utc>,
+    pub run_id: Option<u64>,
+    pub plan: Option<Vec<String>>,
+    pub error: Option<String>,
+}
+
+impl Job {
+    /// Arguments of the sync command, the plan is written to `plan_file`.
+    pub fn args(&self, plan_file: &Path) -> Vec<String> {
+        let mut args = vec![String::from("--plan-file"), plan_file.display().to_string()];
+        if let Some(scope) = self.scope {
+            args.extend([String::from("--scope"), scope.to_string()]);
+        }
+        if self.noop {
+            args.push(String::from("--noop"));
+        }
+        args
+    }
+}
+
+/// Sync jobs requested through the API, oldest first.
+#[derive(Debug, Default)]
+pub struct JobQueue {
+    jobs: VecDeque<Job>,
+    last_id: u64,
+}
+
+impl JobQueue {
+    pub fn enqueue(&mut self, params: JobParams) -> u64 {
+        self.last_id += 1;
+        self.jobs.push_back(Job {
+            id: self.last_id,
+            state: JobState::Queued,
+            scope: params.scope,
+            noop: params.noop,
+            created: Utc::now(),
+            run_id: None,
+            plan: None,
+            error: None,
+        });
+
+        let finished = self.jobs.iter().filter(|job| matches!(job.state, JobState::Succeeded | JobState::Failed)).count();
+        if finished > KEEP_FINISHED {
+            if let Some(idx) = self.jobs.iter().position(|job| matches!(job.state, JobState::Succeeded | JobState::Failed)) {
+                self.jobs.remove(idx);
+            }
+        }
+        self.last_id
+    }
+
+    /// Marks the oldest queued job as running and returns it.
+    pub fn start_next(&mut self) -> Option<Job> {
+        let job = self.jobs.iter_mut().find(|job| job.state == JobState::Queued)?;
+        job.state = JobState::Running;
+        Some(job.clone())
+    }
+
+    /// Links the job to its entry in the run history.
+    pub fn set_run_id(&mut self, id: u64, run_id: u64) {
+        if let Some(job) = self.jobs.iter_mut().find(|job| job.id == id) {
+            job.run_id = Some(run_id);
+        }
+    }
+
+    pub fn finish(&mut self, id: u64, plan: Option<Vec<String>>, error: Option<String>) {
+        if let Some(job) = self.jobs.iter_mut().find(|job| job.id == id) {
+            job.state = match error {
+                None => JobState::Succeeded,
+                Some(_) => JobState::Failed,
+            };
+            job.plan = plan;
+            job.error = error;
+        }
+    }
+
+    pub fn get(&self, id: u64) -> Option<&Job> {
+        self.jobs.iter().find(|job| job.id == id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_runs_jobs_in_order() {
+        let mut queue = JobQueue::default();
+        let first = queue.enqueue(JobParams { scope: Some("10.0.0.1".parse().unwrap()), noop: true });
+        let second = queue.enqueue(JobParams::default());
+
+        let job = queue.start_next().unwrap();
+        queue.set_run_id(job.id, 7);
+        assert_eq!(job.id, first);
+        assert_eq!(job.args(Path::new("plan.json")), vec!["--plan-file", "plan.json", "--scope", "10.0.0.1", "--noop"]);
+        assert_eq!(queue.get(first).unwrap().state, JobState::Running);
+        assert_eq!(queue.get(first).unwrap().run_id, Some(7));
+
+        queue.finish(first, Some(vec![String::from("Create scope 10.0.0.0")]), None);
+        assert_eq!(queue.get(first).unwrap().state, JobState::Succeeded);
+
+        let job = queue.start_next().unwrap();
+        assert_eq!(job.id, second);
+        assert_eq!(job.args(Path::new("plan.json")), vec!["--plan-file", "plan.json"]);
+        queue.finish(second, None, Some(String::from("Process exited with status code 65")));
+        assert_eq!(queue.get(second).unwrap().state, JobState::Failed);
+        assert_eq!(queue.start_next(), None);
+    }
+}
@@ -0,0 +1,119 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::time::Duration;
+
+use super::shared::ServerStatus;
+
+const PREFIX: &str = "netbox_windhcp";
+
+/// Counters of the server since its start, rendered in the Prometheus text format.
+#[derive(Debug, Default)]
+pub struct Metrics {
+    pub sync_runs: u64,
+    pub sync_successes: u64,
+    pub sync_failures: u64,
+    pub sync_timeouts: u64,
+    pub last_sync_duration: Option<Duration>,
+    webhooks: BTreeMap<(String, String), u64>,
+    ignored: BTreeMap<(String, String), u64>,
+    rejected: BTreeMap<String, u64>,
+}
+
+impl Metrics {
+    pub fn webhook_received(&mut self, model: &str, event: &str) {
+        *self.webhooks.entry((model.to_string(), event.to_string())).or_default() += 1;
+    }
+
+    /// Webhooks acknowledged without a sync as no rule matched.
+    pub fn webhook_ignored(&mut self, model: &str, event: &str) {
+        *self.ignored.entry((model.to_string(), event.to_string())).or_default() += 1;
+    }
+
+    pub fn webhook_rejected(&mut self, reason: &str) {
+        *self.rejected.entry(reason.to_string()).or_default() += 1;
+    }
+}
+
+fn escape(value: &str) -> String {
+    value.replace('\\', r"\\").replace('"', r#"\""#).replace('\n', r"\n")
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {}_{} {}", PREFIX, name, help);
+    let _ = writeln!(out, "# TYPE {}_{} {}", PREFIX, name, kind);
+}
+
+fn metric(out: &mut String, name: &str, kind: &str, help: &str, value: impl std::fmt::Display) {
+    header(out, name, kind, help);
+    let _ = writeln!(out, "{}_{} {}", PREFIX, name, value);
+}
+
+pub fn render(status: &ServerStatus) -> String {
+    let metrics = &status.metrics;
+    let mut out = String::new();
+
+    metric(&mut out, "sync_runs_total", "counter", "Sync runs started.", metrics.sync_runs);
+    metric(&mut out, "sync_successes_total", "counter", "Sync runs which succeeded.", metrics.sync_successes);
+    metric(&mut out, "sync_failures_total", "counter", "Sync runs which failed, including timeouts.", metrics.sync_failures);
+    metric(&mut out, "sync_timeouts_total", "counter", "Sync runs which were killed after the timeout.", metrics.sync_timeouts);
+    if let Some(duration) = metrics.last_sync_duration {
+        metric(&mut out, "sync_last_duration_seconds", "gauge", "Duration of the last sync run.", duration.as_secs_f64());
+    }
+    if let Some(last_sync) = status.last_sync {
+        metric(&mut out, "sync_last_timestamp_seconds", "gauge", "End of the last sync run.", last_sync.timestamp());
+    }
+    metric(&mut out, "needs_sync", "gauge", "A sync is pending.", status.needs_sync as u8);
+    metric(&mut out, "syncing", "gauge", "A sync is running.", status.syncing as u8);
+
+    header(&mut out, "webhooks_received_total", "counter", "Webhooks received by model and event.");
+    for ((model, event), count) in metrics.webhooks.iter() {
+        let _ = writeln!(out, "{}_webhooks_received_total{{model=\"{}\",event=\"{}\"}} {}", PREFIX, escape(model), escape(event), count);
+    }
+
+    header(&mut out, "webhooks_ignored_total", "counter", "Webhooks ignored by the webhook rules by model and event.");
+    for ((model, event), count) in metrics.ignored.iter() {
+        let _ = writeln!(out, "{}_webhooks_ignored_total{{model=\"{}\",event=\"{}\"}} {}", PREFIX, escape(model), escape(event), count);
+    }
+
+    header(&mut out, "webhooks_rejected_total", "counter", "Webhooks rejected by reason.");
+    for (reason, count) in metrics.rejected.iter() {
+        let _ = writeln!(out, "{}_webhooks_rejected_total{{reason=\"{}\"}} {}", PREFIX, escape(reason), count);
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+
+    use super::*;
+
+    #[test]
+    fn it_renders_prometheus_metrics() {
+        let mut status = ServerStatus::new();
+        status.needs_sync = true;
+        status.last_sync = Some(Utc.with_ymd_and_hms(2024, 5, 1, 10, 0, 0).unwrap());
+        status.metrics.sync_runs = 2;
+        status.metrics.sync_successes = 1;
+        status.metrics.sync_failures = 1;
+        status.metrics.last_sync_duration = Some(Duration::from_millis(1500));
+        status.metrics.webhook_received("prefix", "updated");
+        status.metrics.webhook_received("prefix", "updated");
+        status.metrics.webhook_received("ip\"address", "created");
+        status.metrics.webhook_ignored("site", "created");
+        status.metrics.webhook_rejected("BadSignature");
+
+        let out = render(&status);
+        assert!(out.contains("# TYPE netbox_windhcp_sync_runs_total counter\nnetbox_windhcp_sync_runs_total 2\n"));
+        assert!(out.contains("netbox_windhcp_sync_timeouts_total 0\n"));
+        assert!(out.contains("netbox_windhcp_sync_last_duration_seconds 1.5\n"));
+        assert!(out.contains("netbox_windhcp_sync_last_timestamp_seconds 1714557600\n"));
+        assert!(out.contains("netbox_windhcp_needs_sync 1\n"));
+        assert!(out.contains("netbox_windhcp_syncing 0\n"));
+        assert!(out.contains("netbox_windhcp_webhooks_received_total{model=\"prefix\",event=\"updated\"} 2\n"));
+        assert!(out.contains("netbox_windhcp_webhooks_received_total{model=\"ip\\\"address\",event=\"created\"} 1\n"));
+        assert!(out.contains("netbox_windhcp_webhooks_ignored_total{model=\"site\",event=\"created\"} 1\n"));
+        assert!(out.contains("netbox_windhcp_webhooks_rejected_total{reason=\"BadSignature\"} 1\n"));
+    }
+}
@@ -1,5 +1,8 @@
 pub mod config;
+mod history;
 mod interval;
+mod jobs;
+mod metrics;
 #[cfg(target_os = "windows")]
 pub mod service;
 mod shared;
@@ -8,17 +11,18 @@ mod sync;
 mod web;
 mod webhook;
 
-use log::debug;
+use log::{debug, warn};
 use std::sync::{mpsc as std_mpsc, Arc};
 use tokio::sync::{broadcast, Mutex};
 
-use crate::{server::shared::ServerStatus, Config};
+use crate::{server::shared::ServerStatus, sync::owned::SyncOwned, Config};
 
+use self::history::RunHistory;
 use self::shared::{Message, SharedServerStatus};
 
 pub fn run(shutdown_rx: Option<std_mpsc::Receiver<Message>>) {
-    let config = match Config::load_from_file() {
-        Ok(config) => config.webhook,
+    let (config, owned) = match Config::load_from_file() {
+        Ok(config) => (config.webhook, SyncOwned::new(&config.sync.stats)),
         Err(e) => {
             println!("Error reading config: {}", e);
             return;
@@ -30,7 +34,12 @@ pub fn run(shutdown_rx: Option<std_mpsc::Receiver<Message>>) {
         .build()
         .unwrap()
         .block_on(async {
-            let status: SharedServerStatus = Arc::new(Mutex::new(ServerStatus::new()));
+            let mut server_status = ServerStatus::new();
+            match RunHistory::load(config.history()) {
+                Ok(history) => server_status.history = history,
+                Err(e) => warn!("{}, starting with an empty history", e),
+            }
+            let status: SharedServerStatus = Arc::new(Mutex::new(server_status));
             let (message_tx, message_rx) = broadcast::channel(16);
 
             let _interval_handle = self::interval::spawn(&config, &status, &message_tx);
@@ -49,7 +58,7 @@ pub fn run(shutdown_rx: Option<std_mpsc::Receiver<Message>>) {
             }
 
             tokio::join!(
-                self::web::server(&config, &status, &message_tx),
+                self::web::server(&config, &owned, &status, &message_tx),
                 self::sync::worker(&config, &status, &message_tx, message_rx)
             );
         })
@@ -1,9 +1,15 @@
+use std::collections::BTreeSet;
 use std::sync::Arc;
 
 use chrono::{DateTime, Utc};
+use ipnet::IpNet;
 use serde::Serialize;
 use tokio::sync::Mutex;
 
+use super::history::{serialize_summaries, RunHistory, SyncTrigger};
+use super::jobs::JobQueue;
+use super::metrics::Metrics;
+
 #[derive(Debug, Default, Serialize)]
 pub enum SyncStatus {
     #[default]
@@ -17,11 +23,78 @@ pub struct ServerStatus {
     pub needs_sync: bool,
     pub syncing: bool,
     pub last_sync: Option<DateTime<Utc>>,
+    pub last_full_sync: Option<DateTime<Utc>>,
     pub last_sync_status: SyncStatus,
+    #[serde(skip)]
+    pub pending: Option<SyncRequest>,
+    #[serde(skip)]
+    pub pending_triggers: BTreeSet<SyncTrigger>,
+    #[serde(rename = "runs", serialize_with = "serialize_summaries")]
+    pub history: RunHistory,
+    #[serde(skip)]
+    pub metrics: Metrics,
+    #[serde(skip)]
+    pub jobs: JobQueue,
 }
 
 impl ServerStatus {
     pub fn new() -> Self { Self { ..Default::default() } }
+
+    /// Adds a sync to the pending one, requests during the standoff time are run together.
+    pub fn request_sync(&mut self, request: SyncRequest, trigger: SyncTrigger) {
+        self.needs_sync = true;
+        self.pending_triggers.insert(trigger);
+        match self.pending.as_mut() {
+            Some(pending) => pending.merge(request),
+            None => self.pending = Some(request),
+        }
+    }
+}
+
+/// Netbox object whose scope has to be synced.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SyncTarget {
+    Network(IpNet),
+    Interface(u64),
+    VmInterface(u64),
+}
+
+impl SyncTarget {
+    fn args(&self) -> [String; 2] {
+        match self {
+            SyncTarget::Network(net) => [String::from("--changed"), net.to_string()],
+            SyncTarget::Interface(id) => [String::from("--interface"), id.to_string()],
+            SyncTarget::VmInterface(id) => [String::from("--vminterface"), id.to_string()],
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub enum SyncRequest {
+    #[default]
+    Full,
+    Targets(BTreeSet<SyncTarget>),
+}
+
+impl SyncRequest {
+    pub fn merge(&mut self, other: SyncRequest) {
+        match (self, other) {
+            (SyncRequest::Targets(targets), SyncRequest::Targets(mut other)) => targets.append(&mut other),
+            (request, _) => *request = SyncRequest::Full,
+        }
+    }
+
+    pub fn is_full(&self) -> bool {
+        *self == SyncRequest::Full
+    }
+
+    /// Arguments of the sync command restricting it to the targets.
+    pub fn args(&self) -> Vec<String> {
+        match self {
+            SyncRequest::Full => Vec::new(),
+            SyncRequest::Targets(targets) => targets.iter().flat_map(SyncTarget::args).collect(),
+        }
+    }
 }
 
 pub type SharedServerStatus = Arc<Mutex<ServerStatus>>;
@@ -31,3 +104,26 @@ pub enum Message {
     Shutdown,
     TriggerSync,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn targets(targets: &[SyncTarget]) -> SyncRequest {
+        SyncRequest::Targets(targets.iter().cloned().collect())
+    }
+
+    #[test]
+    fn it_merges_sync_requests() {
+        let mut status = ServerStatus::new();
+        status.request_sync(targets(&[SyncTarget::Network("10.0.0.10/32".parse().unwrap())]), SyncTrigger::Webhook);
+        status.request_sync(targets(&[SyncTarget::Interface(20), SyncTarget::Network("10.0.0.10/32".parse().unwrap())]), SyncTrigger::Webhook);
+        assert!(status.needs_sync);
+        assert_eq!(status.pending.as_ref().unwrap().args(), vec!["--changed", "10.0.0.10/32", "--interface", "20"]);
+
+        status.request_sync(SyncRequest::Full, SyncTrigger::Interval);
+        status.request_sync(targets(&[SyncTarget::VmInterface(21)]), SyncTrigger::Webhook);
+        assert!(status.pending.unwrap().is_full());
+        assert_eq!(status.pending_triggers.into_iter().collect::<Vec<_>>(), vec![SyncTrigger::Interval, SyncTrigger::Webhook]);
+    }
+}
@@ -3,14 +3,17 @@ use std::{
     error::Error,
     path::PathBuf,
     process::Stdio,
+    sync::{Arc, Mutex},
     time::Duration,
 };
 
 use chrono::Utc;
-use log::{debug, error, info};
+use log::{debug, error, info, warn};
 use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, BufReader},
     process::Command,
     sync::broadcast::{self, error::RecvError},
+    task::JoinHandle,
     time::{error::Elapsed, sleep, Instant},
 };
 
@@ -18,6 +21,8 @@ use crate::server::shared::SyncStatus;
 
 use super::{
     config::WebhookConfig,
+    history::{OutputTail, SyncTrigger},
+    jobs::Job,
     shared::{Message, SharedServerStatus},
 };
 
@@ -41,6 +46,7 @@ pub async fn worker(
     let status: SharedServerStatus = status.clone();
     let sync_standoff_time = config.sync_standoff_time();
     let sync_timeout = config.sync_timeout();
+    let output_lines = config.history().output_lines();
 
     let sync_command = match get_sync_binary() {
         Ok(bin) => bin,
@@ -56,6 +62,22 @@ pub async fn worker(
         match message_rx.recv().await {
             Ok(Message::Shutdown) | Err(RecvError::Closed) => { break; },
             Ok(Message::TriggerSync) => {
+                while let Some((job, run_id)) = start_job(&status).await {
+                    info!("Sync Job {} started", job.id);
+                    let plan_file = job_plan_file(&job);
+                    let sync_status = run_recorded(&status, run_id, &sync_command, &job.args(&plan_file), &sync_timeout, output_lines).await;
+
+                    let plan = std::fs::read(&plan_file).ok()
+                        .and_then(|plan| serde_json::from_slice::<Vec<String>>(&plan).ok());
+                    let _ = std::fs::remove_file(&plan_file);
+
+                    let mut status = status.lock().await;
+                    if sync_status.is_ok() && job.scope.is_none() && !job.noop {
+                        status.last_full_sync = status.last_sync;
+                    }
+                    status.jobs.finish(job.id, plan, sync_status.err().map(|e| e.to_string()));
+                }
+
                 if !status.lock().await.needs_sync {
                     debug!("Sync not required");
                     continue;
@@ -64,34 +86,21 @@ pub async fn worker(
 
                 sleep(sync_standoff_time).await;
 
-                {
+                let (request, run_id) = {
                     let mut status = status.lock().await;
                     status.needs_sync = false;
                     status.syncing = true;
-                }
+                    status.metrics.sync_runs += 1;
+                    let request = status.pending.take().unwrap_or_default();
+                    let triggers = std::mem::take(&mut status.pending_triggers).into_iter().collect();
+                    let run_id = status.history.start(triggers, request.args());
+                    (request, run_id)
+                };
 
-                let sync_start = Instant::now();
-                let sync_status = run_sync_command(&sync_command, &sync_timeout).await;
-
-                match sync_status {
-                    Ok(_) => {
-                        info!("Sync succeeded ({}s)", sync_start.elapsed().as_secs());
-                        {
-                            let mut status = status.lock().await;
-                            status.syncing = false;
-                            status.last_sync = Some(Utc::now());
-                            status.last_sync_status = SyncStatus::SyncOk;
-                        }
-                    }
-                    Err(e) => {
-                        error!("Sync failed: {}", e);
-                        {
-                            let mut status = status.lock().await;
-                            status.syncing = false;
-                            status.last_sync = Some(Utc::now());
-                            status.last_sync_status = SyncStatus::SyncFailed;
-                        }
-                    }
+                let sync_status = run_recorded(&status, run_id, &sync_command, &request.args(), &sync_timeout, output_lines).await;
+                if sync_status.is_ok() && request.is_full() {
+                    let mut status = status.lock().await;
+                    status.last_full_sync = status.last_sync;
                 }
             },
             Err(RecvError::Lagged(_)) => {},
@@ -100,6 +109,67 @@ pub async fn worker(
     info!("Sync Thread Ended");
 }
 
+/// File the sync writes the plan of a job to.
+fn job_plan_file(job: &Job) -> PathBuf {
+    env::temp_dir().join(format!("netbox-windhcp-job-{}-{}.json", std::process::id(), job.id))
+}
+
+/// Starts the oldest queued job and records its run.
+async fn start_job(status: &SharedServerStatus) -> Option<(Job, u64)> {
+    let mut status = status.lock().await;
+    let job = status.jobs.start_next()?;
+    status.syncing = true;
+    status.metrics.sync_runs += 1;
+    let run_id = status.history.start(vec![SyncTrigger::Api], job.args(&job_plan_file(&job)));
+    status.jobs.set_run_id(job.id, run_id);
+    Some((job, run_id))
+}
+
+/// Runs the sync command and records the result in the run history, metrics and status.
+async fn run_recorded(
+    status: &SharedServerStatus,
+    run_id: u64,
+    command: &PathBuf,
+    args: &[String],
+    timeout: &Duration,
+    output_lines: usize,
+) -> Result<(), SyncError> {
+    let sync_start = Instant::now();
+    let output = Arc::new(Mutex::new(OutputTail::new(output_lines)));
+    let sync_status = run_sync_command(command, args, timeout, &output).await;
+    let output = output.lock().unwrap().take();
+
+    let mut status = status.lock().await;
+    let (exit_code, error) = match &sync_status {
+        Ok(_) => (Some(0), None),
+        Err(e) => (e.exit_code(), Some(e.class().to_string())),
+    };
+    status.history.finish(run_id, exit_code, error, output);
+    if let Err(e) = status.history.save() {
+        warn!("Unable to write the run history: {}", e);
+    }
+
+    status.syncing = false;
+    status.last_sync = Some(Utc::now());
+    status.metrics.last_sync_duration = Some(sync_start.elapsed());
+    match &sync_status {
+        Ok(_) => {
+            info!("Sync succeeded ({}s)", sync_start.elapsed().as_secs());
+            status.last_sync_status = SyncStatus::SyncOk;
+            status.metrics.sync_successes += 1;
+        }
+        Err(e) => {
+            error!("Sync failed: {}", e);
+            status.last_sync_status = SyncStatus::SyncFailed;
+            status.metrics.sync_failures += 1;
+            if let SyncError::Timeout(_) = e {
+                status.metrics.sync_timeouts += 1;
+            }
+        }
+    }
+    sync_status
+}
+
 #[derive(Debug)]
 enum SyncError {
     CommandNotFound,
@@ -109,6 +179,26 @@ enum SyncError {
     CommandNoStatus,
 }
 
+impl SyncError {
+    /// Short name of the error for the run history.
+    fn class(&self) -> &'static str {
+        match self {
+            SyncError::CommandNotFound => "command_not_found",
+            SyncError::IoError(_) => "io_error",
+            SyncError::Timeout(_) => "timeout",
+            SyncError::CommandStatus(_) => "command_status",
+            SyncError::CommandNoStatus => "command_no_status",
+        }
+    }
+
+    fn exit_code(&self) -> Option<i32> {
+        match self {
+            SyncError::CommandStatus(code) => Some(*code),
+            _ => None,
+        }
+    }
+}
+
 impl Error for SyncError {}
 impl From<std::io::Error> for SyncError {
     fn from(e: std::io::Error) -> Self {
@@ -127,14 +217,36 @@ impl std::fmt::Display for SyncError {
     }
 }
 
-async fn run_sync_command(command: &PathBuf, timeout: &Duration) -> Result<(), SyncError> {
-    info!("Run Sync Command: {}", &command.display());
+/// Keeps the last lines of a stream of the sync command.
+fn capture<R: AsyncRead + Unpin + Send + 'static>(reader: R, output: &Arc<Mutex<OutputTail>>) -> JoinHandle<()> {
+    let output = output.clone();
+    tokio::spawn(async move {
+        let mut lines = BufReader::new(reader).lines();
+        while let Ok(Some(line)) = lines.next_line().await {
+            output.lock().unwrap().push(line);
+        }
+    })
+}
+
+async fn run_sync_command(
+    command: &PathBuf,
+    args: &[String],
+    timeout: &Duration,
+    output: &Arc<Mutex<OutputTail>>,
+) -> Result<(), SyncError> {
+    info!("Run Sync Command: {} {}", &command.display(), args.join(" "));
     let mut child = Command::new(command)
+        .args(args)
         .stdin(Stdio::null())
-        .stdout(Stdio::null())
-        .stderr(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
         .spawn()?;
 
+    let readers: Vec<JoinHandle<()>> = [
+        child.stdout.take().map(|stdout| capture(stdout, output)),
+        child.stderr.take().map(|stderr| capture(stderr, output)),
+    ].into_iter().flatten().collect();
+
     let status = match tokio::time::timeout(timeout.to_owned(), child.wait()).await {
         Ok(s) => s,
         Err(e) => {
@@ -144,6 +256,10 @@ async fn run_sync_command(command: &PathBuf, timeout: &Duration) -> Result<(), S
         }
     }?;
 
+    for reader in readers {
+        let _ = reader.await;
+    }
+
     match status.code() {
         Some(0) => Ok(()),
         Some(n) => Err(SyncError::CommandStatus(n)),
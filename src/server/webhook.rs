@@ -1,5 +1,11 @@
 use chrono::{DateTime, Utc};
+use ipnet::IpNet;
 use serde::Deserialize;
+use serde_json::{Map, Value};
+
+use crate::sync::owned::SyncOwned;
+
+use super::shared::{SyncRequest, SyncTarget};
 
 #[derive(Debug, Deserialize, PartialEq, Eq)]
 pub struct NetboxWebHook {
@@ -8,13 +14,137 @@ pub struct NetboxWebHook {
     pub model: String,
     pub username: String,
     pub request_id: String,
-    pub data: serde_json::Map<String, serde_json::Value>,
+    pub data: Map<String, Value>,
+    #[serde(default)]
+    pub snapshots: NetboxWebHookSnapshots,
 }
 
-#[derive(Debug, Deserialize, PartialEq, Eq)]
+impl NetboxWebHook {
+    /// The object after the change and, for updates and deletions, before it.
+    fn objects(&self) -> impl Iterator<Item = &Map<String, Value>> {
+        std::iter::once(&self.data).chain(self.snapshots.prechange.iter())
+    }
+
+    /// Tag names and slugs of the object, the data has nested tags and the snapshots tag names.
+    pub fn tags(&self) -> Vec<&str> {
+        self.objects()
+            .filter_map(|object| object.get("tags").and_then(Value::as_array))
+            .flatten()
+            .flat_map(|tag| match tag {
+                Value::String(name) => vec![name.as_str()],
+                tag => ["name", "slug"].iter().filter_map(|f| tag.get(*f).and_then(Value::as_str)).collect(),
+            })
+            .collect()
+    }
+
+    /// Networks of prefixes and addresses of IP ranges and IP addresses, before and after the change.
+    pub fn networks(&self) -> Vec<IpNet> {
+        self.objects()
+            .flat_map(|object| ["prefix", "start_address", "end_address", "address"].into_iter()
+                .filter_map(|f| Some((f, object.get(f)?.as_str()?.parse::<IpNet>().ok()?))))
+            .map(|(field, net)| match field {
+                "prefix" => net.trunc(),
+                _ => IpNet::from(net.addr()),
+            })
+            .collect()
+    }
+
+    /// Updates of only the status or statistics fields written by the sync itself.
+    pub fn only_owned_changed(&self, owned: &SyncOwned) -> bool {
+        owned.only_owned_changed(self.snapshots.prechange.as_ref(), self.snapshots.postchange.as_ref())
+    }
+
+    /// Scopes affected by the change, a full sync for models not mapping to scopes.
+    pub fn sync_request(&self) -> SyncRequest {
+        let targets: Vec<SyncTarget> = match self.model.as_str() {
+            "prefix" | "iprange" | "ipaddress" => self.networks().into_iter().map(SyncTarget::Network).collect(),
+            "interface" => self.data.get("id").and_then(Value::as_u64).map(SyncTarget::Interface).into_iter().collect(),
+            "vminterface" => self.data.get("id").and_then(Value::as_u64).map(SyncTarget::VmInterface).into_iter().collect(),
+            "macaddress" => self.objects()
+                .filter_map(|object| {
+                    let id = object.get("assigned_object_id").and_then(Value::as_u64)?;
+                    match object.get("assigned_object_type").and_then(Value::as_str)? {
+                        "dcim.interface" => Some(SyncTarget::Interface(id)),
+                        "virtualization.vminterface" => Some(SyncTarget::VmInterface(id)),
+                        _ => None,
+                    }
+                })
+                .collect(),
+            _ => Vec::new(),
+        };
+
+        match targets.is_empty() {
+            true => SyncRequest::Full,
+            false => SyncRequest::Targets(targets.into_iter().collect()),
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize, PartialEq, Eq)]
+pub struct NetboxWebHookSnapshots {
+    pub prechange: Option<Map<String, Value>>,
+    pub postchange: Option<Map<String, Value>>,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
 #[serde(rename_all = "lowercase")]
 pub enum NetboxWebHookEvent {
     Created,
     Updated,
     Deleted,
 }
+
+impl NetboxWebHookEvent {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NetboxWebHookEvent::Created => "created",
+            NetboxWebHookEvent::Updated => "updated",
+            NetboxWebHookEvent::Deleted => "deleted",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sync::stats::StatsConfig;
+
+    use super::*;
+
+    fn hook(model: &str, data: &str, prechange: &str) -> NetboxWebHook {
+        serde_json::from_str(&format!(r#"{{
+            "event": "updated", "timestamp": "2024-05-01 10:00:00+00:00", "model": "{}",
+            "username": "admin", "request_id": "1", "data": {},
+            "snapshots": {{ "prechange": {}, "postchange": {{}} }}
+        }}"#, model, data, prechange)).unwrap()
+    }
+
+    #[test]
+    fn it_derives_the_sync_targets() {
+        let address = hook("ipaddress", r#"{ "address": "10.0.0.10/24" }"#, r#"{ "address": "10.0.1.10/24" }"#);
+        assert_eq!(address.sync_request().args(), vec!["--changed", "10.0.0.10/32", "--changed", "10.0.1.10/32"]);
+
+        let prefix = hook("prefix", r#"{ "prefix": "10.0.0.0/23" }"#, "null");
+        assert_eq!(prefix.sync_request().args(), vec!["--changed", "10.0.0.0/23"]);
+
+        let mac = hook("macaddress", r#"{ "assigned_object_type": "virtualization.vminterface", "assigned_object_id": 21 }"#,
+            r#"{ "assigned_object_type": "dcim.interface", "assigned_object_id": 20 }"#);
+        assert_eq!(mac.sync_request().args(), vec!["--interface", "20", "--vminterface", "21"]);
+
+        let interface = hook("interface", r#"{ "id": 20 }"#, "null");
+        assert_eq!(interface.sync_request().args(), vec!["--interface", "20"]);
+
+        assert!(hook("vlan", r#"{ "id": 5 }"#, "null").sync_request().is_full());
+        assert!(hook("ipaddress", "{}", "null").sync_request().is_full());
+    }
+
+    #[test]
+    fn it_detects_updates_by_the_sync() {
+        let mut status = hook("prefix", r#"{ "prefix": "10.0.0.0/24" }"#,
+            r#"{ "prefix": "10.0.0.0/24", "custom_fields": { "dhcp_sync_status": null } }"#);
+        status.snapshots.postchange = serde_json::from_str(r#"{ "prefix": "10.0.0.0/24", "custom_fields": { "dhcp_sync_status": "ok" } }"#).unwrap();
+        assert!(status.only_owned_changed(&SyncOwned::new(&StatsConfig::default())));
+
+        let created = hook("prefix", r#"{ "prefix": "10.0.0.0/24" }"#, "null");
+        assert!(!created.only_owned_changed(&SyncOwned::new(&StatsConfig::default())));
+    }
+}
@@ -1,23 +1,77 @@
-use std::net::Ipv4Addr;
+use std::fmt::Display;
+use std::net::{Ipv4Addr, Ipv6Addr};
 use std::path::PathBuf;
 
-use serde::Deserialize;
+use serde::{de, Deserialize, Deserializer};
 
-use super::netbox::config::SyncNetboxConfig;
+use super::hostname::sanitize_label;
+use super::incremental::IncrementalConfig;
+use super::journal::JournalConfig;
+use super::snapshot::SnapshotConfig;
+use super::stats::StatsConfig;
+use super::netbox::address::IpAddress;
+use super::netbox::config::{field_keys, list_endpoint, RequiredKey, SyncNetboxConfig};
+use super::netbox::filter::Expr;
+use super::netbox::model::NetAddr;
+use super::netbox::prefix::Prefix;
+use super::state::{FailoverMode, FailoverRelation, FailoverServerType};
+use super::template::{placeholders, render, truncate};
 
-use super::windhcp::DnsFlags;
+use super::state::{DnsFlags, CLIENT_COMMENT_MAX_LENGTH, SUBNET_COMMENT_MAX_LENGTH, SUBNET_NAME_MAX_LENGTH};
 
 #[derive(Debug, Deserialize, Clone)]
 pub struct SyncConfig {
     pub netbox: SyncNetboxConfig,
     pub dhcp: SyncDhcpConfig,
     pub logs: SyncLogConfig,
+    #[serde(default)]
+    pub journal: JournalConfig,
+    #[serde(default)]
+    pub snapshot: SnapshotConfig,
+    #[serde(default)]
+    pub incremental: IncrementalConfig,
+    #[serde(default)]
+    pub stats: StatsConfig,
 }
 
 impl SyncConfig {
     pub fn netbox(&self) -> &SyncNetboxConfig {
         &self.netbox
     }
+
+    /// Netbox keys read by the templates, filter expressions and statistics.
+    pub fn netbox_keys(&self) -> Vec<RequiredKey> {
+        let templates = &self.dhcp.templates;
+        let mut keys: Vec<RequiredKey> = [&templates.scope_name, &templates.scope_comment].into_iter()
+            .flat_map(|template| placeholders(template))
+            .map(|field| ("prefixes", field_keys(&field)))
+            .collect();
+        keys.extend(placeholders(&templates.reservation_comment).iter()
+            .map(|field| match field.strip_prefix("prefix.") {
+                Some(field) => ("prefixes", field_keys(field)),
+                None => ("ip-addresses", field_keys(field)),
+            }));
+
+        for (list, expression) in self.netbox.filter_expression() {
+            let (Some(endpoint), Ok(expr)) = (list_endpoint(list), Expr::parse(expression)) else { continue };
+            keys.extend(expr.fields().into_iter().map(|field| (endpoint, field_keys(field))));
+        }
+
+        if self.stats.enabled() {
+            let endpoints = [(self.stats.prefix(), "prefixes"), (self.stats.range(), "ip-ranges")];
+            for (_, endpoint) in endpoints.into_iter().filter(|(target, _)| *target) {
+                keys.extend([(endpoint, field_keys("tags")), (endpoint, field_keys("cf"))]);
+            }
+        }
+
+        keys.retain(|(_, alternatives)| !alternatives.is_empty());
+        keys
+    }
+
+    /// Checks that the Netbox lists include the keys read by the sync.
+    pub fn validate(&self) -> Result<(), String> {
+        self.netbox.validate_fields(&self.netbox_keys())
+    }
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -30,6 +84,14 @@ pub struct SyncDhcpConfig {
     #[serde(default)]
     default_dns_servers: Vec<Ipv4Addr>,
     default_failover_relation: Option<String>,
+    #[serde(default)]
+    reservation_name: ReservationNameConfig,
+    #[serde(default)]
+    templates: TemplateConfig,
+    #[serde(default)]
+    v6: SyncDhcpV6Config,
+    #[serde(default)]
+    failover_relations: Vec<FailoverRelationConfig>,
 }
 
 impl SyncDhcpConfig {
@@ -56,6 +118,205 @@ impl SyncDhcpConfig {
     pub fn default_failover_relation(&self) -> Option<&String> {
         self.default_failover_relation.as_ref()
     }
+
+    pub fn reservation_name(&self) -> &ReservationNameConfig {
+        &self.reservation_name
+    }
+
+    pub fn templates(&self) -> &TemplateConfig {
+        &self.templates
+    }
+
+    pub fn v6(&self) -> &SyncDhcpV6Config {
+        &self.v6
+    }
+
+    pub fn failover_relations(&self) -> &[FailoverRelationConfig] {
+        self.failover_relations.as_ref()
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct FailoverRelationConfig {
+    name: String,
+    local_address: Option<Ipv4Addr>,
+    partner: Ipv4Addr,
+    #[serde(default)]
+    mode: FailoverMode,
+    #[serde(default)]
+    role: FailoverServerType,
+    #[serde(default, deserialize_with = "percentage")]
+    percentage: Option<u8>,
+    mclt: Option<u32>,
+    state_switchover_interval: Option<u32>,
+    shared_secret: Option<String>,
+}
+
+/// Share of the clients served by the local server or reserved for the standby server, 0 to 100.
+fn percentage<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<u8>, D::Error> {
+    match Option::<u8>::deserialize(deserializer)? {
+        Some(percentage) if percentage > 100 => Err(de::Error::custom(format!("invalid percentage {}, expected 0 to 100", percentage))),
+        percentage => Ok(percentage),
+    }
+}
+
+impl FailoverRelationConfig {
+    pub fn name(&self) -> &str {
+        self.name.as_ref()
+    }
+
+    pub fn shared_secret(&self) -> Option<&String> {
+        self.shared_secret.as_ref()
+    }
+
+    pub fn percentage(&self) -> u8 {
+        match self.mode {
+            FailoverMode::LoadBalance => self.percentage.unwrap_or(50),
+            FailoverMode::HotStandby => self.percentage.unwrap_or(5),
+        }
+    }
+
+    pub fn mclt(&self) -> u32 {
+        self.mclt.unwrap_or(60 * 60)
+    }
+
+    pub fn relation(&self, server: &str) -> Result<FailoverRelation, String> {
+        let local = match self.local_address.or_else(|| server.parse().ok()) {
+            Some(local) => local,
+            None => return Err(format!("Failover relation {} needs a local_address as the DHCP server {} is no IPv4 address", self.name, server)),
+        };
+
+        let (primary_server, secondary_server) = match self.role {
+            FailoverServerType::Primary => (local, self.partner),
+            FailoverServerType::Secondary => (self.partner, local),
+        };
+
+        Ok(FailoverRelation {
+            name: self.name.clone(),
+            primary_server,
+            secondary_server,
+            mode: self.mode,
+            server_type: self.role,
+            mclt: self.mclt(),
+            safe_period: self.state_switchover_interval.unwrap_or_default(),
+            percentage: self.percentage(),
+        })
+    }
+}
+
+#[derive(Debug, Default, Deserialize, Clone)]
+#[serde(default)]
+pub struct SyncDhcpV6Config {
+    enabled: bool,
+    preference: u16,
+    preferred_lifetime: Option<u32>,
+    valid_lifetime: Option<u32>,
+    default_dns_servers: Vec<Ipv6Addr>,
+    default_domain_search: Vec<String>,
+}
+
+impl SyncDhcpV6Config {
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn preference(&self) -> u16 {
+        self.preference
+    }
+
+    pub fn preferred_lifetime(&self) -> u32 {
+        self.preferred_lifetime.unwrap_or(8 * 24 * 60 * 60)
+    }
+
+    pub fn valid_lifetime(&self) -> u32 {
+        self.valid_lifetime.unwrap_or(12 * 24 * 60 * 60)
+    }
+
+    pub fn default_dns_servers(&self) -> &[Ipv6Addr] {
+        self.default_dns_servers.as_ref()
+    }
+
+    pub fn default_domain_search(&self) -> &[String] {
+        self.default_domain_search.as_ref()
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct TemplateConfig {
+    scope_name: String,
+    scope_comment: String,
+    reservation_comment: String,
+}
+
+impl Default for TemplateConfig {
+    fn default() -> Self {
+        Self {
+            scope_name: String::from("{description}"),
+            scope_comment: String::from("{description}"),
+            reservation_comment: String::from("{description}"),
+        }
+    }
+}
+
+impl TemplateConfig {
+    pub fn scope_name<N: NetAddr + Copy + Display>(&self, prefix: &Prefix<N>) -> String {
+        truncate(&render(&self.scope_name, |k| prefix.template_value(k)), SUBNET_NAME_MAX_LENGTH)
+    }
+
+    pub fn scope_comment<N: NetAddr + Copy + Display>(&self, prefix: &Prefix<N>) -> String {
+        truncate(&render(&self.scope_comment, |k| prefix.template_value(k)), SUBNET_COMMENT_MAX_LENGTH)
+    }
+
+    pub fn reservation_comment<N, A>(&self, prefix: &Prefix<N>, reservation: &IpAddress<A>) -> String
+    where N: NetAddr + Copy + Display, A: NetAddr, A::Addr: Display {
+        let comment = render(&self.reservation_comment, |k| match k.strip_prefix("prefix.") {
+            Some(k) => prefix.template_value(k),
+            None => reservation.template_value(k),
+        });
+        truncate(&comment, CLIENT_COMMENT_MAX_LENGTH)
+    }
+}
+
+#[derive(Debug, Default, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ReservationNameFallback {
+    #[default]
+    None,
+    Device,
+    DeviceInterface,
+}
+
+#[derive(Debug, Default, Deserialize, Clone)]
+#[serde(default)]
+pub struct ReservationNameConfig {
+    fallback: ReservationNameFallback,
+    append_domain: bool,
+}
+
+impl ReservationNameConfig {
+    pub fn client_name<A: NetAddr>(&self, reservation: &IpAddress<A>, dns_domain: Option<&String>) -> String
+    where A::Addr: Display {
+        let name = match (reservation.dns_name(), &self.fallback) {
+            (dns_name, _) if !dns_name.is_empty() => dns_name.to_string(),
+            (_, ReservationNameFallback::None) => String::new(),
+            (_, ReservationNameFallback::Device) => reservation.assigned_parent_name()
+                .map(|n| sanitize_label(n))
+                .unwrap_or_default(),
+            (_, ReservationNameFallback::DeviceInterface) => match (reservation.assigned_parent_name(), reservation.assigned_object_name()) {
+                (Some(parent), Some(interface)) => sanitize_label(&format!("{}-{}", parent, interface)),
+                (Some(parent), None) => sanitize_label(parent),
+                (None, _) => String::new(),
+            },
+        };
+
+        match dns_domain {
+            Some(domain) if self.append_domain && !name.is_empty() && !name.contains('.') => {
+                format!("{}.{}", name, domain)
+            },
+            _ => name,
+        }
+    }
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -93,5 +354,201 @@ mod tests {
         let cfg = cfg.unwrap();
         assert_eq!(cfg.server(), "dhcp.example.com");
         assert_eq!(cfg.lease_duration(), 604800);
+        assert!(!cfg.v6().enabled());
+    }
+
+    #[test]
+    fn it_parses_dhcp_v6_config() {
+        let cfg = serde_yaml_ng::from_str::<SyncDhcpConfig>(r#"---
+        server: dhcp.example.com
+        v6:
+            enabled: true
+            preference: 10
+            valid_lifetime: 86400
+            default_dns_servers:
+                - 2001:db8::53
+            default_domain_search:
+                - example.com
+        "#).unwrap();
+        assert!(cfg.v6().enabled());
+        assert_eq!(cfg.v6().preference(), 10);
+        assert_eq!(cfg.v6().preferred_lifetime(), 691200);
+        assert_eq!(cfg.v6().valid_lifetime(), 86400);
+        assert_eq!(cfg.v6().default_dns_servers(), &["2001:db8::53".parse::<Ipv6Addr>().unwrap()]);
+        assert_eq!(cfg.v6().default_domain_search(), &[String::from("example.com")]);
+    }
+
+    #[test]
+    fn it_parses_failover_relations() {
+        let cfg = serde_yaml_ng::from_str::<SyncDhcpConfig>(r#"---
+        server: 10.0.0.1
+        failover_relations:
+            - name: DHCP-Failover
+              partner: 10.0.0.2
+              mode: hot_standby
+              role: secondary
+              state_switchover_interval: 3600
+        "#).unwrap();
+        let relation = cfg.failover_relations()[0].relation(cfg.server()).unwrap();
+        assert_eq!(relation.name, "DHCP-Failover");
+        assert_eq!(relation.primary_server, Ipv4Addr::new(10, 0, 0, 2));
+        assert_eq!(relation.secondary_server, Ipv4Addr::new(10, 0, 0, 1));
+        assert_eq!(relation.mode, FailoverMode::HotStandby);
+        assert_eq!(relation.server_type, FailoverServerType::Secondary);
+        assert_eq!(relation.percentage, 5);
+        assert_eq!(relation.mclt, 3600);
+        assert_eq!(relation.safe_period, 3600);
+    }
+
+    #[test]
+    fn it_requires_a_local_address_for_failover_relations() {
+        let cfg = serde_yaml_ng::from_str::<SyncDhcpConfig>(r#"---
+        server: dhcp.example.com
+        failover_relations:
+            - name: DHCP-Failover
+              partner: 10.0.0.2
+        "#).unwrap();
+        assert!(cfg.failover_relations()[0].relation(cfg.server()).is_err());
+    }
+
+    #[test]
+    fn it_rejects_failover_percentages_above_100() {
+        let cfg = |percentage: u8| serde_yaml_ng::from_str::<SyncDhcpConfig>(&format!(r#"---
+        server: 10.0.0.1
+        failover_relations:
+            - name: DHCP-Failover
+              partner: 10.0.0.2
+              percentage: {}
+        "#, percentage));
+        assert_eq!(cfg(100).unwrap().failover_relations()[0].percentage(), 100);
+        assert!(cfg(101).unwrap_err().to_string().contains("invalid percentage 101, expected 0 to 100"));
+    }
+
+    fn reservation(dns_name: &str) -> IpAddress {
+        serde_json::from_str::<IpAddress>(&format!(r#"{{
+            "url": "https://netbox.example.com/api/ipam/ip-addresses/1/",
+            "address": "10.112.130.10/24",
+            "dns_name": "{}",
+            "description": "",
+            "custom_fields": {{}},
+            "assigned_object": {{
+                "url": "https://netbox.example.com/api/dcim/interfaces/1/",
+                "name": "Gi1/0/1",
+                "device": {{ "name": "SW01" }}
+            }}
+        }}"#, dns_name)).unwrap()
+    }
+
+    #[test]
+    fn it_parses_reservation_name_config() {
+        let cfg = serde_yaml_ng::from_str::<SyncDhcpConfig>(r#"---
+        server: dhcp.example.com
+        reservation_name:
+            fallback: device_interface
+            append_domain: true
+        "#).unwrap();
+        assert_eq!(cfg.reservation_name().fallback, ReservationNameFallback::DeviceInterface);
+        assert!(cfg.reservation_name().append_domain);
+    }
+
+    #[test]
+    fn it_uses_the_dns_name_as_client_name() {
+        let cfg = ReservationNameConfig { fallback: ReservationNameFallback::Device, append_domain: false };
+        assert_eq!(cfg.client_name(&reservation("host01.example.com"), None), "host01.example.com");
+    }
+
+    #[test]
+    fn it_returns_an_empty_client_name_without_fallback() {
+        let cfg = ReservationNameConfig::default();
+        assert_eq!(cfg.client_name(&reservation(""), None), "");
+    }
+
+    #[test]
+    fn it_falls_back_to_the_device_name() {
+        let cfg = ReservationNameConfig { fallback: ReservationNameFallback::Device, append_domain: false };
+        assert_eq!(cfg.client_name(&reservation(""), None), "sw01");
+    }
+
+    #[test]
+    fn it_falls_back_to_the_device_and_interface_name() {
+        let cfg = ReservationNameConfig { fallback: ReservationNameFallback::DeviceInterface, append_domain: false };
+        assert_eq!(cfg.client_name(&reservation(""), None), "sw01-gi1-0-1");
+    }
+
+    #[test]
+    fn it_appends_the_dns_domain() {
+        let cfg = ReservationNameConfig { fallback: ReservationNameFallback::Device, append_domain: true };
+        let domain = String::from("example.com");
+        assert_eq!(cfg.client_name(&reservation(""), Some(&domain)), "sw01.example.com");
+        assert_eq!(cfg.client_name(&reservation("host01"), Some(&domain)), "host01.example.com");
+        assert_eq!(cfg.client_name(&reservation("host01.example.org"), Some(&domain)), "host01.example.org");
+    }
+
+    fn prefix() -> Prefix {
+        serde_json::from_str::<Prefix>(r#"{
+            "prefix": "10.112.130.0/24",
+            "description": "Clients",
+            "site": { "name": "Zurich", "slug": "zrh" },
+            "vlan": { "vid": 130, "name": "CLIENTS" },
+            "custom_fields": {}
+        }"#).unwrap()
+    }
+
+    #[test]
+    fn it_parses_template_config() {
+        let cfg = serde_yaml_ng::from_str::<SyncDhcpConfig>(r#"---
+        server: dhcp.example.com
+        templates:
+            scope_name: "{site} VLAN{vlan.vid}"
+        "#).unwrap();
+        assert_eq!(cfg.templates().scope_name(&prefix()), "Zurich VLAN130");
+        assert_eq!(cfg.templates().scope_comment(&prefix()), "Clients");
+    }
+
+    #[test]
+    fn it_renders_the_reservation_comment() {
+        let cfg = TemplateConfig {
+            reservation_comment: String::from("{device} ({prefix.site})"),
+            ..Default::default()
+        };
+        assert_eq!(cfg.reservation_comment(&prefix(), &reservation("")), "SW01 (Zurich)");
+    }
+
+    #[test]
+    fn it_limits_the_scope_name_length() {
+        let cfg = TemplateConfig {
+            scope_name: "x".repeat(SUBNET_NAME_MAX_LENGTH + 10),
+            ..Default::default()
+        };
+        assert_eq!(cfg.scope_name(&prefix()).chars().count(), SUBNET_NAME_MAX_LENGTH);
+    }
+
+    #[test]
+    fn it_requires_the_netbox_keys_in_use() {
+        let cfg = serde_yaml_ng::from_str::<SyncConfig>(r#"---
+        netbox:
+            fields:
+                prefixes: [url, prefix, description, custom_fields, site]
+            filter_expression:
+                prefixes: "tenant.slug == acme"
+        dhcp:
+            server: dhcp.example.com
+            templates:
+                scope_name: "{site} VLAN{vlan.vid}"
+        logs: {}
+        "#).unwrap();
+        assert_eq!(cfg.validate().unwrap_err(), "Invalid Netbox fields for prefixes: vlan, tenant required");
+
+        let cfg = serde_yaml_ng::from_str::<SyncConfig>(r#"---
+        netbox:
+            brief: true
+        dhcp:
+            server: dhcp.example.com
+        logs: {}
+        stats:
+            enabled: true
+            target: range
+        "#).unwrap();
+        assert_eq!(cfg.validate().unwrap_err(), "Invalid Netbox brief: tags, custom_fields of ip-ranges required");
     }
 }
@@ -0,0 +1,203 @@
+use std::fs;
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::path::Path;
+
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+
+use super::state::{DhcpState, FailoverRelation, ScopeV4, ScopeV6};
+
+mod xml;
+
+/// Version of the export document, increased on incompatible changes.
+pub const EXPORT_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Yaml,
+    Json,
+}
+
+impl ExportFormat {
+    /// JSON for `.json` files, YAML for everything else.
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("json") => Self::Json,
+            _ => Self::Yaml,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExportScopeV4 {
+    #[serde(flatten)]
+    pub scope: ScopeV4,
+    #[serde(default)]
+    pub exclusions: Vec<(Ipv4Addr, Ipv4Addr)>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Export {
+    pub version: u32,
+    pub server: String,
+    pub timestamp: DateTime<Local>,
+    #[serde(default)]
+    pub failover_relations: Vec<FailoverRelation>,
+    #[serde(default)]
+    pub scopes_v4: Vec<ExportScopeV4>,
+    #[serde(default)]
+    pub scopes_v6: Vec<ScopeV6>,
+}
+
+impl Export {
+    pub fn new(server: &str) -> Self {
+        Self {
+            version: EXPORT_VERSION,
+            server: server.to_string(),
+            timestamp: Local::now(),
+            failover_relations: Vec::new(),
+            scopes_v4: Vec::new(),
+            scopes_v6: Vec::new(),
+        }
+    }
+
+    pub fn render(&self, format: ExportFormat) -> Result<String, Box<dyn std::error::Error + Send + std::marker::Sync>> {
+        Ok(match format {
+            ExportFormat::Yaml => serde_yaml_ng::to_string(self)?,
+            ExportFormat::Json => serde_json::to_string_pretty(self)?,
+        })
+    }
+
+    pub fn parse(data: &str, format: ExportFormat) -> Result<Self, Box<dyn std::error::Error + Send + std::marker::Sync>> {
+        let export: Self = match format {
+            ExportFormat::Yaml => serde_yaml_ng::from_str(data)?,
+            ExportFormat::Json => serde_json::from_str(data)?,
+        };
+
+        if export.version > EXPORT_VERSION {
+            return Err(format!("Export version {} is not supported, expected {} or older", export.version, EXPORT_VERSION).into());
+        }
+
+        Ok(export)
+    }
+
+    /// Reads an export of this tool or, for `.xml` files, of the Windows `Export-DhcpServer` cmdlet.
+    /// `server` is used as local address of failover relationships in Windows exports.
+    pub fn load(path: &Path, server: &str) -> Result<Self, Box<dyn std::error::Error + Send + std::marker::Sync>> {
+        let data = fs::read_to_string(path)
+            .map_err(|e| format!("Unable to read {}: {}", path.display(), e))?;
+
+        match path.extension().and_then(|e| e.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("xml") => xml::parse(&data, server)
+                .map_err(|e| format!("Unable to parse {}: {}", path.display(), e).into()),
+            _ => Self::parse(&data, ExportFormat::from_path(path)),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Box<dyn std::error::Error + Send + std::marker::Sync>> {
+        fs::write(path, self.render(ExportFormat::from_path(path))?)
+            .map_err(|e| format!("Unable to write {}: {}", path.display(), e).into())
+    }
+}
+
+impl DhcpState for Export {
+    fn subnets_v4(&self) -> Result<Vec<Ipv4Addr>, Box<dyn std::error::Error + Send + std::marker::Sync>> {
+        Ok(self.scopes_v4.iter().map(|s| s.scope.subnet).collect())
+    }
+
+    fn subnets_v6(&self) -> Result<Vec<Ipv6Addr>, Box<dyn std::error::Error + Send + std::marker::Sync>> {
+        Ok(self.scopes_v6.iter().map(|s| s.prefix).collect())
+    }
+
+    fn scope_v4(&self, subnet: &Ipv4Addr) -> Result<Option<ScopeV4>, Box<dyn std::error::Error + Send + std::marker::Sync>> {
+        Ok(self.scopes_v4.iter().find(|s| s.scope.subnet == *subnet).map(|s| s.scope.clone()))
+    }
+
+    fn scope_v6(&self, prefix: &Ipv6Addr) -> Result<Option<ScopeV6>, Box<dyn std::error::Error + Send + std::marker::Sync>> {
+        Ok(self.scopes_v6.iter().find(|s| s.prefix == *prefix).cloned())
+    }
+
+    fn failover_relation(&self, name: &str) -> Result<Option<FailoverRelation>, Box<dyn std::error::Error + Send + std::marker::Sync>> {
+        Ok(self.failover_relations.iter().find(|r| r.name == name).cloned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sync::state::{FailoverMode, FailoverServerType, ReservationV4};
+    use crate::sync::state::{DnsFlags, ReservationClientTypes};
+
+    fn export() -> Export {
+        let mut scope = ScopeV4::new("10.0.0.0".parse().unwrap(), "255.255.255.0".parse().unwrap());
+        scope.name = String::from("Clients");
+        scope.range = ("10.0.0.100".parse().unwrap(), "10.0.0.200".parse().unwrap());
+        scope.lease_duration = Some(3600);
+        scope.dns_flags = Some(DnsFlags { enabled: true, ..Default::default() });
+        scope.failover = Some(String::from("Failover"));
+        scope.reservations.insert("10.0.0.10".parse().unwrap(), ReservationV4 {
+            address: "10.0.0.10".parse().unwrap(),
+            mac: vec![0, 0x11, 0x22, 0x33, 0x44, 0x55],
+            client_type: ReservationClientTypes::Dhcp,
+            name: String::from("host"),
+            comment: String::new(),
+        });
+
+        let mut export = Export::new("dhcp.example.com");
+        export.failover_relations.push(FailoverRelation {
+            name: String::from("Failover"),
+            primary_server: "10.0.0.1".parse().unwrap(),
+            secondary_server: "10.0.0.2".parse().unwrap(),
+            mode: FailoverMode::HotStandby,
+            server_type: FailoverServerType::Primary,
+            mclt: 3600,
+            safe_period: 0,
+            percentage: 5,
+        });
+        export.scopes_v4.push(ExportScopeV4 {
+            scope,
+            exclusions: vec![("10.0.0.150".parse().unwrap(), "10.0.0.160".parse().unwrap())],
+        });
+        export
+    }
+
+    #[test]
+    fn it_roundtrips_yaml_exports() {
+        let export = export();
+        let yaml = export.render(ExportFormat::Yaml).unwrap();
+        assert!(yaml.contains("version: 1"));
+        assert!(yaml.contains("mac: 00:11:22:33:44:55"));
+        assert!(yaml.contains("client_type: dhcp"));
+        assert_eq!(serde_yaml_ng::from_str::<Export>(&yaml).unwrap(), export);
+    }
+
+    #[test]
+    fn it_roundtrips_json_exports() {
+        let export = export();
+        let json = export.render(ExportFormat::Json).unwrap();
+        assert_eq!(serde_json::from_str::<Export>(&json).unwrap(), export);
+    }
+
+    #[test]
+    fn it_rejects_newer_export_versions() {
+        let mut export = export();
+        export.version = EXPORT_VERSION + 1;
+        let json = export.render(ExportFormat::Json).unwrap();
+        assert!(Export::parse(&json, ExportFormat::Json).is_err());
+    }
+
+    #[test]
+    fn it_reads_scopes_from_exports() {
+        let export = export();
+        assert_eq!(export.subnets_v4().unwrap(), vec!["10.0.0.0".parse::<Ipv4Addr>().unwrap()]);
+        assert_eq!(export.scope_v4(&"10.0.0.0".parse().unwrap()).unwrap().unwrap().name, "Clients");
+        assert_eq!(export.scope_v4(&"10.0.1.0".parse().unwrap()).unwrap(), None);
+        assert_eq!(export.failover_relation("Failover").unwrap().unwrap().percentage, 5);
+    }
+
+    #[test]
+    fn it_detects_the_format() {
+        assert_eq!(ExportFormat::from_path(Path::new("dhcp.JSON")), ExportFormat::Json);
+        assert_eq!(ExportFormat::from_path(Path::new("dhcp.yml")), ExportFormat::Yaml);
+    }
+}
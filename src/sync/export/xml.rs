@@ -0,0 +1,384 @@
+//! Reader for the XML written by the Windows `Export-DhcpServer` cmdlet.
+
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::str::FromStr;
+
+use roxmltree::{Document, Node};
+
+use super::{Export, ExportScopeV4};
+use crate::sync::mac::MacAddr;
+use crate::sync::state::{FailoverMode, FailoverRelation, FailoverServerType, ReservationV4, ReservationV6, ScopeV4, ScopeV6};
+use crate::sync::state::{DnsFlags, ReservationClientTypes};
+
+const OPTION_ROUTER: &str = "3";
+const OPTION_DOMAIN_NAME_SERVERS: &str = "6";
+const OPTION_DOMAIN_NAME: &str = "15";
+const OPTION_V6_DNS_SERVERS: &str = "23";
+const OPTION_V6_DOMAIN_LIST: &str = "24";
+
+/// Windows exports DHCPv6 scopes without prefix length, they are always /64.
+const PREFIX_LEN_V6: u8 = 64;
+
+type ParseResult<T> = Result<T, Box<dyn std::error::Error + Send + std::marker::Sync>>;
+
+fn child<'a, 'input>(node: Node<'a, 'input>, name: &str) -> Option<Node<'a, 'input>> {
+    node.children().find(|n| n.has_tag_name(name))
+}
+
+fn children<'a, 'input: 'a>(node: Option<Node<'a, 'input>>, name: &'a str) -> impl Iterator<Item = Node<'a, 'input>> + 'a {
+    node.into_iter()
+        .flat_map(|n| n.children())
+        .filter(move |n| n.has_tag_name(name))
+}
+
+fn text<'a>(node: Node<'a, '_>, name: &str) -> &'a str {
+    child(node, name).and_then(|n| n.text()).unwrap_or_default().trim()
+}
+
+fn value<T: FromStr>(node: Node, name: &str) -> ParseResult<T>
+where T::Err: std::fmt::Display {
+    let value = text(node, name);
+    value.parse::<T>()
+        .map_err(|e| format!("Invalid {} '{}': {}", name, value, e).into())
+}
+
+fn flag(node: Node, name: &str) -> bool {
+    text(node, name).eq_ignore_ascii_case("true")
+}
+
+/// Parses a .NET TimeSpan (`[d.]hh:mm:ss`) into seconds.
+fn timespan(node: Node, name: &str) -> ParseResult<u32> {
+    let value = text(node, name);
+    if value.is_empty() { return Ok(0); }
+
+    let (days, time) = match value.split_once('.') {
+        Some((days, time)) if time.contains(':') => (days.parse::<u32>()?, time),
+        _ => (0, value),
+    };
+
+    let mut seconds = 0u32;
+    for part in time.split(':') {
+        seconds = seconds * 60 + part.split('.').next().unwrap_or_default().parse::<u32>()
+            .map_err(|e| format!("Invalid {} '{}': {}", name, value, e))?;
+    }
+
+    Ok(days * 86400 + seconds)
+}
+
+/// Values of a scope option without vendor or user class.
+fn option_values<'a>(node: Node<'a, '_>, id: &str) -> Vec<&'a str> {
+    children(child(node, "OptionValues"), "OptionValue")
+        .find(|o| text(*o, "OptionId") == id && text(*o, "VendorClass").is_empty() && text(*o, "UserClass").is_empty())
+        .map(|o| o.children()
+            .filter(|n| n.has_tag_name("Value"))
+            .filter_map(|n| n.text())
+            .map(|v| v.trim())
+            .collect())
+        .unwrap_or_default()
+}
+
+fn parse_list<T: FromStr>(values: Vec<&str>) -> ParseResult<Vec<T>>
+where T::Err: std::fmt::Display {
+    values.into_iter()
+        .map(|v| v.parse::<T>().map_err(|e| format!("Invalid option value '{}': {}", v, e).into()))
+        .collect()
+}
+
+fn dns_flags(node: Node) -> Option<DnsFlags> {
+    let settings = child(node, "DnsSettings")?;
+    let updates = text(settings, "DynamicUpdates");
+
+    Some(DnsFlags {
+        enabled: !updates.eq_ignore_ascii_case("Never"),
+        update_downlevel: flag(settings, "UpdateDnsRRForOlderClients"),
+        cleanup_expired: flag(settings, "DeleteDnsRROnLeaseExpiry"),
+        update_both_always: updates.eq_ignore_ascii_case("Always"),
+        update_dhcid: flag(settings, "NameProtection"),
+        disable_ptr_update: flag(settings, "DisableDnsPtrRRUpdate"),
+    })
+}
+
+fn client_type(node: Node) -> ReservationClientTypes {
+    match text(node, "Type").to_ascii_lowercase().as_str() {
+        "dhcp" => ReservationClientTypes::Dhcp,
+        "bootp" => ReservationClientTypes::Bootp,
+        _ => ReservationClientTypes::Both,
+    }
+}
+
+fn scope_v4(node: Node) -> ParseResult<ExportScopeV4> {
+    let mut scope = ScopeV4::new(value(node, "ScopeId")?, value(node, "SubnetMask")?);
+    scope.name = text(node, "Name").to_string();
+    scope.comment = text(node, "Description").to_string();
+    scope.range = (value(node, "StartRange")?, value(node, "EndRange")?);
+    if child(node, "LeaseDuration").is_some() {
+        scope.lease_duration = Some(timespan(node, "LeaseDuration")?);
+    }
+    scope.dns_flags = dns_flags(node);
+    scope.routers = parse_list(option_values(node, OPTION_ROUTER))?;
+    scope.dns_domain = option_values(node, OPTION_DOMAIN_NAME).first().map(|d| d.to_string());
+    scope.dns_servers = parse_list(option_values(node, OPTION_DOMAIN_NAME_SERVERS))?;
+
+    for reservation in children(child(node, "Reservations"), "Reservation") {
+        let address: Ipv4Addr = value(reservation, "IPAddress")?;
+        scope.reservations.insert(address, ReservationV4 {
+            address,
+            mac: Vec::<u8>::from_mac(text(reservation, "ClientId")),
+            client_type: client_type(reservation),
+            name: text(reservation, "Name").to_string(),
+            comment: text(reservation, "Description").to_string(),
+        });
+    }
+
+    let mut exclusions = Vec::new();
+    for range in children(child(node, "ExclusionRanges"), "IPRange") {
+        exclusions.push((value(range, "StartRange")?, value(range, "EndRange")?));
+    }
+
+    Ok(ExportScopeV4 { scope, exclusions })
+}
+
+fn scope_v6(node: Node) -> ParseResult<ScopeV6> {
+    let mut scope = ScopeV6::new(value(node, "Prefix")?, PREFIX_LEN_V6);
+    scope.name = text(node, "Name").to_string();
+    scope.comment = text(node, "Description").to_string();
+    scope.preference = match text(node, "Preference") {
+        "" => 0,
+        _ => value(node, "Preference")?,
+    };
+    scope.preferred_lifetime = timespan(node, "PreferredLifetime")?;
+    scope.valid_lifetime = timespan(node, "ValidLifetime")?;
+    scope.dns_servers = parse_list(option_values(node, OPTION_V6_DNS_SERVERS))?;
+    scope.domain_search = option_values(node, OPTION_V6_DOMAIN_LIST).into_iter().map(String::from).collect();
+
+    for reservation in children(child(node, "Reservations"), "Reservation") {
+        let address: Ipv6Addr = value(reservation, "IPAddress")?;
+        scope.reservations.insert(address, ReservationV6 {
+            address,
+            duid: Vec::<u8>::from_mac(text(reservation, "ClientDuid")),
+            iaid: value(reservation, "Iaid")?,
+            name: text(reservation, "Name").to_string(),
+            comment: text(reservation, "Description").to_string(),
+        });
+    }
+
+    Ok(scope)
+}
+
+fn failover_relation(node: Node, local: Ipv4Addr) -> ParseResult<FailoverRelation> {
+    let partner: Ipv4Addr = value(node, "PartnerServer")?;
+    let mode = match text(node, "Mode").eq_ignore_ascii_case("HotStandby") {
+        true => FailoverMode::HotStandby,
+        false => FailoverMode::LoadBalance,
+    };
+    let server_type = match text(node, "ServerType").to_ascii_lowercase().as_str() {
+        "secondary" | "secondaryserver" => FailoverServerType::Secondary,
+        "" if text(node, "ServerRole").eq_ignore_ascii_case("Standby") => FailoverServerType::Secondary,
+        _ => FailoverServerType::Primary,
+    };
+    let (primary_server, secondary_server) = match server_type {
+        FailoverServerType::Primary => (local, partner),
+        FailoverServerType::Secondary => (partner, local),
+    };
+
+    Ok(FailoverRelation {
+        name: text(node, "Name").to_string(),
+        primary_server,
+        secondary_server,
+        mode,
+        server_type,
+        mclt: timespan(node, "MaxClientLeadTime")?,
+        safe_period: timespan(node, "StateSwitchInterval")?,
+        percentage: match mode {
+            FailoverMode::LoadBalance => value(node, "LoadBalancePercent")?,
+            FailoverMode::HotStandby => value(node, "ReservePercent")?,
+        },
+    })
+}
+
+pub fn parse(data: &str, server: &str) -> ParseResult<Export> {
+    let document = Document::parse(data)?;
+    let root = document.root_element();
+    if !root.has_tag_name("DHCPServer") {
+        return Err(format!("Expected a DHCPServer document, found {}", root.tag_name().name()).into());
+    }
+
+    let mut export = Export::new(server);
+
+    if let Some(ipv4) = child(root, "IPv4") {
+        for scope in children(child(ipv4, "Scopes"), "Scope") {
+            export.scopes_v4.push(scope_v4(scope)?);
+        }
+
+        let local = server.parse().unwrap_or(Ipv4Addr::UNSPECIFIED);
+        for node in children(child(ipv4, "Failover"), "Relationship") {
+            let relation = failover_relation(node, local)?;
+            for scope_id in node.descendants().filter(|n| n.has_tag_name("ScopeId")).filter_map(|n| n.text()) {
+                let scope_id = scope_id.trim().parse::<Ipv4Addr>()?;
+                if let Some(scope) = export.scopes_v4.iter_mut().find(|s| s.scope.subnet == scope_id) {
+                    scope.scope.failover = Some(relation.name.clone());
+                }
+            }
+            export.failover_relations.push(relation);
+        }
+    }
+
+    if let Some(ipv6) = child(root, "IPv6") {
+        for scope in children(child(ipv6, "Scopes"), "Scope") {
+            export.scopes_v6.push(scope_v6(scope)?);
+        }
+    }
+
+    Ok(export)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXPORT: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<DHCPServer>
+  <MajorVersion>10</MajorVersion>
+  <MinorVersion>0</MinorVersion>
+  <IPv4>
+    <Scopes>
+      <Scope>
+        <ScopeId>10.0.0.0</ScopeId>
+        <Name>Clients</Name>
+        <SubnetMask>255.255.255.0</SubnetMask>
+        <StartRange>10.0.0.100</StartRange>
+        <EndRange>10.0.0.200</EndRange>
+        <LeaseDuration>8.00:00:00</LeaseDuration>
+        <State>Active</State>
+        <Type>Dhcp</Type>
+        <Description>Client network</Description>
+        <DnsSettings>
+          <DeleteDnsRROnLeaseExpiry>true</DeleteDnsRROnLeaseExpiry>
+          <DisableDnsPtrRRUpdate>false</DisableDnsPtrRRUpdate>
+          <DynamicUpdates>OnClientRequest</DynamicUpdates>
+          <UpdateDnsRRForOlderClients>false</UpdateDnsRRForOlderClients>
+          <NameProtection>true</NameProtection>
+        </DnsSettings>
+        <ExclusionRanges>
+          <IPRange>
+            <StartRange>10.0.0.150</StartRange>
+            <EndRange>10.0.0.160</EndRange>
+          </IPRange>
+        </ExclusionRanges>
+        <Reservations>
+          <Reservation>
+            <Name>host.example.com</Name>
+            <IPAddress>10.0.0.10</IPAddress>
+            <ClientId>00-11-22-33-44-55</ClientId>
+            <Type>Both</Type>
+            <Description>Printer</Description>
+          </Reservation>
+        </Reservations>
+        <OptionValues>
+          <OptionValue>
+            <OptionId>3</OptionId>
+            <Value>10.0.0.1</Value>
+          </OptionValue>
+          <OptionValue>
+            <OptionId>6</OptionId>
+            <Value>10.0.0.2</Value>
+            <Value>10.0.0.3</Value>
+          </OptionValue>
+          <OptionValue>
+            <OptionId>15</OptionId>
+            <Value>example.com</Value>
+          </OptionValue>
+        </OptionValues>
+      </Scope>
+    </Scopes>
+    <Failover>
+      <Relationship>
+        <Name>Failover</Name>
+        <PartnerServer>10.0.0.12</PartnerServer>
+        <Mode>HotStandby</Mode>
+        <MaxClientLeadTime>01:00:00</MaxClientLeadTime>
+        <StateSwitchInterval></StateSwitchInterval>
+        <ReservePercent>5</ReservePercent>
+        <ServerRole>Active</ServerRole>
+        <ScopeId>10.0.0.0</ScopeId>
+      </Relationship>
+    </Failover>
+  </IPv4>
+  <IPv6>
+    <Scopes>
+      <Scope>
+        <Prefix>2001:db8::</Prefix>
+        <Name>Clients v6</Name>
+        <Preference>0</Preference>
+        <PreferredLifetime>8.00:00:00</PreferredLifetime>
+        <ValidLifetime>12.00:00:00</ValidLifetime>
+        <Reservations>
+          <Reservation>
+            <Name>host.example.com</Name>
+            <IPAddress>2001:db8::10</IPAddress>
+            <ClientDuid>00-01-00-01-aa-bb</ClientDuid>
+            <Iaid>1</Iaid>
+            <Description></Description>
+          </Reservation>
+        </Reservations>
+        <OptionValues>
+          <OptionValue>
+            <OptionId>23</OptionId>
+            <Value>2001:db8::53</Value>
+          </OptionValue>
+        </OptionValues>
+      </Scope>
+    </Scopes>
+  </IPv6>
+</DHCPServer>"#;
+
+    #[test]
+    fn it_parses_timespans() {
+        let document = Document::parse("<a><b>1.02:03:04</b><c>01:00:00</c><d>00:00:30.5000000</d></a>").unwrap();
+        let root = document.root_element();
+        assert_eq!(timespan(root, "b").unwrap(), 86400 + 2 * 3600 + 3 * 60 + 4);
+        assert_eq!(timespan(root, "c").unwrap(), 3600);
+        assert_eq!(timespan(root, "d").unwrap(), 30);
+        assert_eq!(timespan(root, "e").unwrap(), 0);
+    }
+
+    #[test]
+    fn it_parses_windows_exports() {
+        let export = parse(EXPORT, "10.0.0.11").unwrap();
+
+        let scope = &export.scopes_v4[0];
+        assert_eq!(scope.scope.name, "Clients");
+        assert_eq!(scope.scope.comment, "Client network");
+        assert_eq!(scope.scope.range, ("10.0.0.100".parse().unwrap(), "10.0.0.200".parse().unwrap()));
+        assert_eq!(scope.scope.lease_duration, Some(691200));
+        assert_eq!(scope.scope.dns_flags, Some(DnsFlags { enabled: true, cleanup_expired: true, update_dhcid: true, ..Default::default() }));
+        assert_eq!(scope.scope.routers, vec!["10.0.0.1".parse::<Ipv4Addr>().unwrap()]);
+        assert_eq!(scope.scope.dns_servers.len(), 2);
+        assert_eq!(scope.scope.dns_domain.as_deref(), Some("example.com"));
+        assert_eq!(scope.scope.failover.as_deref(), Some("Failover"));
+        assert_eq!(scope.exclusions, vec![("10.0.0.150".parse().unwrap(), "10.0.0.160".parse().unwrap())]);
+
+        let reservation = &scope.scope.reservations[&"10.0.0.10".parse().unwrap()];
+        assert_eq!(reservation.mac, vec![0x00, 0x11, 0x22, 0x33, 0x44, 0x55]);
+        assert_eq!(reservation.name, "host.example.com");
+        assert_eq!(reservation.comment, "Printer");
+
+        let relation = &export.failover_relations[0];
+        assert_eq!(relation.primary_server, "10.0.0.11".parse::<Ipv4Addr>().unwrap());
+        assert_eq!(relation.secondary_server, "10.0.0.12".parse::<Ipv4Addr>().unwrap());
+        assert_eq!(relation.mode, FailoverMode::HotStandby);
+        assert_eq!(relation.mclt, 3600);
+        assert_eq!(relation.percentage, 5);
+
+        let scope = &export.scopes_v6[0];
+        assert_eq!(scope.prefix_len, 64);
+        assert_eq!(scope.valid_lifetime, 12 * 86400);
+        assert_eq!(scope.dns_servers, vec!["2001:db8::53".parse::<Ipv6Addr>().unwrap()]);
+        assert_eq!(scope.reservations[&"2001:db8::10".parse().unwrap()].duid, vec![0, 1, 0, 1, 0xaa, 0xbb]);
+    }
+
+    #[test]
+    fn it_rejects_other_documents() {
+        assert!(parse("<Other/>", "10.0.0.11").is_err());
+    }
+}
@@ -0,0 +1,46 @@
+pub fn sanitize_label(name: &str) -> String {
+    let mut label = String::with_capacity(name.len());
+
+    for c in name.chars() {
+        match c {
+            'a'..='z' | '0'..='9' => label.push(c),
+            'A'..='Z' => label.push(c.to_ascii_lowercase()),
+            _ => if !label.ends_with('-') { label.push('-') },
+        }
+    }
+
+    let mut label = label.trim_matches('-').to_string();
+    label.truncate(63);
+    label.trim_end_matches('-').to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_keeps_valid_labels() {
+        assert_eq!(sanitize_label("host-01"), "host-01");
+    }
+
+    #[test]
+    fn it_lowercases_labels() {
+        assert_eq!(sanitize_label("Host01"), "host01");
+    }
+
+    #[test]
+    fn it_replaces_invalid_characters() {
+        assert_eq!(sanitize_label("sw01 GigabitEthernet1/0/1"), "sw01-gigabitethernet1-0-1");
+    }
+
+    #[test]
+    fn it_trims_dashes() {
+        assert_eq!(sanitize_label("_foo__bar_"), "foo-bar");
+    }
+
+    #[test]
+    fn it_truncates_labels() {
+        let label = sanitize_label(&"a".repeat(70));
+        assert_eq!(label.len(), 63);
+    }
+}
@@ -0,0 +1,96 @@
+use std::fs::File;
+use std::io::{self, BufReader};
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::json;
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct IncrementalConfig {
+    state: Option<PathBuf>,
+    full_sync_interval: u64,
+    #[serde(skip)]
+    force_full: bool,
+}
+
+impl Default for IncrementalConfig {
+    fn default() -> Self {
+        Self { state: None, full_sync_interval: 24 * 60 * 60, force_full: false }
+    }
+}
+
+impl IncrementalConfig {
+    /// File remembering the last processed change, incremental syncs are disabled without it.
+    pub fn state(&self) -> Option<&PathBuf> {
+        self.state.as_ref()
+    }
+
+    pub fn set_force_full(&mut self, force_full: bool) {
+        self.force_full = force_full;
+    }
+
+    pub fn full_sync_due(&self, state: &IncrementalState, now: DateTime<Utc>) -> bool {
+        self.force_full || (now - state.last_full_sync).num_seconds() >= self.full_sync_interval as i64
+    }
+}
+
+/// Position in the Netbox change log after the last successful sync.
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct IncrementalState {
+    pub last_change_id: u64,
+    pub last_full_sync: DateTime<Utc>,
+}
+
+impl IncrementalState {
+    pub fn load(path: &Path) -> Result<Option<Self>, Box<dyn std::error::Error + Send + std::marker::Sync>> {
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(format!("Unable to open sync state {}: {}", path.display(), e).into()),
+        };
+        Ok(Some(serde_json::from_reader(BufReader::new(file))
+            .map_err(|e| format!("Unable to read sync state {}: {}", path.display(), e))?))
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        json::write_atomic(path, self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use chrono::Duration;
+
+    use super::*;
+
+    #[test]
+    fn it_saves_and_loads_the_state() {
+        let path = std::env::temp_dir()
+            .join(format!("netbox-windhcp-incremental-{}", std::process::id()))
+            .join("state.json");
+        assert_eq!(IncrementalState::load(&path).unwrap(), None);
+
+        let state = IncrementalState { last_change_id: 42, last_full_sync: Utc::now() };
+        state.save(&path).unwrap();
+        assert_eq!(IncrementalState::load(&path).unwrap(), Some(state));
+
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn it_schedules_full_syncs() {
+        let mut config = serde_yaml_ng::from_str::<IncrementalConfig>("state: sync.json").unwrap();
+        let now = Utc::now();
+        let state = IncrementalState { last_change_id: 1, last_full_sync: now - Duration::hours(1) };
+
+        assert!(!config.full_sync_due(&state, now));
+        assert!(config.full_sync_due(&state, now + Duration::days(1)));
+        config.set_force_full(true);
+        assert!(config.full_sync_due(&state, now));
+    }
+}
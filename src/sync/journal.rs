@@ -0,0 +1,276 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use super::mac::MacAddr;
+use super::plan::{Change, FailoverChange, ScopeV4Change, ScopeV6Change, Sources};
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct JournalConfig {
+    path: Option<PathBuf>,
+    max_size: u64,
+    keep: u32,
+}
+
+impl Default for JournalConfig {
+    fn default() -> Self {
+        Self { path: None, max_size: 10 * 1024 * 1024, keep: 10 }
+    }
+}
+
+impl JournalConfig {
+    pub fn path(&self) -> Option<&PathBuf> {
+        self.path.as_ref()
+    }
+
+    pub fn max_size(&self) -> u64 {
+        self.max_size
+    }
+
+    pub fn keep(&self) -> u32 {
+        self.keep
+    }
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+pub struct JournalEntry {
+    pub timestamp: DateTime<Local>,
+    pub run_id: String,
+    pub server: String,
+    pub scope: Option<String>,
+    pub object: String,
+    pub field: &'static str,
+    pub old: Value,
+    pub new: Value,
+    pub source: Option<String>,
+}
+
+impl JournalEntry {
+    pub fn new(run_id: &str, server: &str, change: &Change, sources: &Sources) -> Self {
+        let (object, field, old, new) = describe(change);
+
+        Self {
+            timestamp: Local::now(),
+            run_id: run_id.to_string(),
+            server: server.to_string(),
+            scope: change.scope_address().map(|s| s.to_string()),
+            object,
+            field,
+            old,
+            new,
+            source: change.source(sources).cloned(),
+        }
+    }
+}
+
+fn describe(change: &Change) -> (String, &'static str, Value, Value) {
+    match change {
+        Change::V4(subnet, change) => {
+            let scope = subnet.to_string();
+            match change {
+                ScopeV4Change::Create { mask } => (scope, "scope", Value::Null, json!({ "mask": mask })),
+                ScopeV4Change::Remove => (scope.clone(), "scope", json!(scope), Value::Null),
+                ScopeV4Change::Mask { old, new } => (scope, "mask", json!(old), json!(new)),
+                ScopeV4Change::Name { old, new } => (scope, "name", json!(old), json!(new)),
+                ScopeV4Change::Comment { old, new } => (scope, "comment", json!(old), json!(new)),
+                ScopeV4Change::Range { old, new } => (scope, "range", json!([old.0, old.1]), json!([new.0, new.1])),
+                ScopeV4Change::LeaseDuration { old, new } => (scope, "lease_duration", json!(old), json!(new)),
+                ScopeV4Change::DnsFlags { old, new } => (scope, "dns_flags", json!(old), json!(new)),
+                ScopeV4Change::Routers { old, new } => (scope, "routers", json!(old), json!(new)),
+                ScopeV4Change::DnsDomain { old, new } => (scope, "dns_domain", json!(old), json!(new)),
+                ScopeV4Change::DnsServers { old, new } => (scope, "dns_servers", json!(old), json!(new)),
+                ScopeV4Change::Failover { old, new } => (scope, "failover", json!(old), json!(new)),
+                ScopeV4Change::AddExclusion(range) => (scope, "exclusion", Value::Null, json!([range.0, range.1])),
+                ScopeV4Change::RemoveExclusion(range) => (scope, "exclusion", json!([range.0, range.1]), Value::Null),
+                ScopeV4Change::AddReservation(r) => (r.address.to_string(), "reservation", Value::Null, json!({ "mac": r.mac.as_mac() })),
+                ScopeV4Change::RemoveReservation(r) => (r.address.to_string(), "reservation", json!({ "mac": r.mac.as_mac() }), Value::Null),
+                ScopeV4Change::ReservationName { address, old, new } => (address.to_string(), "client_name", json!(old), json!(new)),
+                ScopeV4Change::ReservationComment { address, old, new } => (address.to_string(), "client_comment", json!(old), json!(new)),
+            }
+        },
+        Change::V6(prefix, change) => {
+            let scope = prefix.to_string();
+            match change {
+                ScopeV6Change::Create { prefix_len } => (scope, "scope", Value::Null, json!({ "prefix_len": prefix_len })),
+                ScopeV6Change::Remove => (scope.clone(), "scope", json!(scope), Value::Null),
+                ScopeV6Change::Name { old, new } => (scope, "name", json!(old), json!(new)),
+                ScopeV6Change::Comment { old, new } => (scope, "comment", json!(old), json!(new)),
+                ScopeV6Change::Preference { old, new } => (scope, "preference", json!(old), json!(new)),
+                ScopeV6Change::Lifetimes { old, new } => (scope, "lifetimes", json!([old.0, old.1]), json!([new.0, new.1])),
+                ScopeV6Change::DnsServers { old, new } => (scope, "dns_servers", json!(old), json!(new)),
+                ScopeV6Change::DomainSearch { old, new } => (scope, "domain_search", json!(old), json!(new)),
+                ScopeV6Change::AddReservation(r) => (r.address.to_string(), "reservation", Value::Null, json!({ "duid": r.duid.as_mac(), "iaid": r.iaid })),
+                ScopeV6Change::RemoveReservation(r) => (r.address.to_string(), "reservation", json!({ "duid": r.duid.as_mac(), "iaid": r.iaid }), Value::Null),
+                ScopeV6Change::ReservationName { address, old, new } => (address.to_string(), "client_name", json!(old), json!(new)),
+                ScopeV6Change::ReservationComment { address, old, new } => (address.to_string(), "client_comment", json!(old), json!(new)),
+            }
+        },
+        Change::Failover(name, change) => {
+            let name = name.clone();
+            match change {
+                FailoverChange::Create { relation, .. } => (name, "failover_relation", Value::Null, json!({
+                    "primary_server": relation.primary_server,
+                    "secondary_server": relation.secondary_server,
+                    "mode": relation.mode,
+                    "server_type": relation.server_type,
+                    "mclt": relation.mclt,
+                    "safe_period": relation.safe_period,
+                    "percentage": relation.percentage,
+                })),
+                FailoverChange::Mode { old, new } => (name, "mode", json!(old), json!(new)),
+                FailoverChange::Mclt { old, new } => (name, "mclt", json!(old), json!(new)),
+                FailoverChange::SafePeriod { old, new } => (name, "safe_period", json!(old), json!(new)),
+                FailoverChange::Percentage { old, new } => (name, "percentage", json!(old), json!(new)),
+            }
+        },
+    }
+}
+
+/// Append only JSON Lines journal, rotated to `<name>.1.jsonl` ... `<name>.<keep>.jsonl`.
+pub struct Journal {
+    path: PathBuf,
+    max_size: u64,
+    keep: u32,
+    file: File,
+}
+
+impl Journal {
+    pub fn open(config: &JournalConfig) -> io::Result<Option<Self>> {
+        let path = match config.path() {
+            Some(path) => path.clone(),
+            None => return Ok(None),
+        };
+
+        let file = Self::open_file(&path)?;
+        Ok(Some(Self { path, max_size: config.max_size(), keep: config.keep(), file }))
+    }
+
+    fn open_file(path: &Path) -> io::Result<File> {
+        if let Some(dir) = path.parent() {
+            if !dir.as_os_str().is_empty() {
+                fs::create_dir_all(dir)?;
+            }
+        }
+        OpenOptions::new().create(true).append(true).open(path)
+    }
+
+    fn rotated_path(&self, idx: u32) -> PathBuf {
+        let stem = self.path.file_stem().unwrap_or_default().to_string_lossy();
+        match self.path.extension() {
+            Some(ext) => self.path.with_file_name(format!("{}.{}.{}", stem, idx, ext.to_string_lossy())),
+            None => self.path.with_file_name(format!("{}.{}", stem, idx)),
+        }
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        if self.keep == 0 {
+            self.file.set_len(0)?;
+            return Ok(());
+        }
+
+        let oldest = self.rotated_path(self.keep);
+        if oldest.exists() {
+            fs::remove_file(oldest)?;
+        }
+        for idx in (1..self.keep).rev() {
+            let from = self.rotated_path(idx);
+            if from.exists() {
+                fs::rename(from, self.rotated_path(idx + 1))?;
+            }
+        }
+        fs::rename(&self.path, self.rotated_path(1))?;
+        self.file = Self::open_file(&self.path)?;
+
+        Ok(())
+    }
+
+    pub fn record(&mut self, entry: &JournalEntry) -> io::Result<()> {
+        if self.file.metadata()?.len() >= self.max_size {
+            self.rotate()?;
+        }
+
+        let mut line = serde_json::to_string(entry)?;
+        line.push('\n');
+        self.file.write_all(line.as_bytes())?;
+        self.file.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::net::Ipv4Addr;
+
+    fn change() -> Change {
+        Change::V4(Ipv4Addr::new(10, 0, 0, 0), ScopeV4Change::ReservationName {
+            address: Ipv4Addr::new(10, 0, 0, 10),
+            old: String::from("old"),
+            new: String::from("new"),
+        })
+    }
+
+    #[test]
+    fn it_parses_journal_config() {
+        let cfg = serde_yaml_ng::from_str::<JournalConfig>(r#"---
+        path: C:\ProgramData\netbox-windhcp\journal.jsonl
+        keep: 3
+        "#).unwrap();
+        assert_eq!(cfg.path(), Some(&PathBuf::from("C:\\ProgramData\\netbox-windhcp\\journal.jsonl")));
+        assert_eq!(cfg.max_size(), 10 * 1024 * 1024);
+        assert_eq!(cfg.keep(), 3);
+    }
+
+    #[test]
+    fn it_builds_journal_entries() {
+        let sources = Sources::from([
+            ("10.0.0.10".parse().unwrap(), String::from("https://netbox.example.com/api/ipam/ip-addresses/1/")),
+        ]);
+        let entry = JournalEntry::new("run", "dhcp.example.com", &change(), &sources);
+        assert_eq!(entry.scope.as_deref(), Some("10.0.0.0"));
+        assert_eq!(entry.object, "10.0.0.10");
+        assert_eq!(entry.field, "client_name");
+        assert_eq!(entry.old, json!("old"));
+        assert_eq!(entry.new, json!("new"));
+        assert_eq!(entry.source.as_deref(), Some("https://netbox.example.com/api/ipam/ip-addresses/1/"));
+    }
+
+    #[test]
+    fn it_writes_and_rotates_the_journal() {
+        let dir = std::env::temp_dir().join(format!("netbox-windhcp-journal-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let config = JournalConfig { path: Some(dir.join("journal.jsonl")), max_size: 1, keep: 2 };
+
+        let mut journal = Journal::open(&config).unwrap().unwrap();
+        let entry = JournalEntry::new("run", "dhcp.example.com", &change(), &Sources::new());
+        for _ in 0..4 {
+            journal.record(&entry).unwrap();
+        }
+
+        let line = fs::read_to_string(dir.join("journal.jsonl")).unwrap();
+        assert_eq!(line.lines().count(), 1);
+        assert!(line.contains(r#""field":"client_name""#));
+        assert!(dir.join("journal.1.jsonl").exists());
+        assert!(dir.join("journal.2.jsonl").exists());
+        assert!(!dir.join("journal.3.jsonl").exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn it_names_rotated_files_without_extension() {
+        let dir = std::env::temp_dir().join(format!("netbox-windhcp-journal-noext-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let config = JournalConfig { path: Some(dir.join("changes")), max_size: 1, keep: 1 };
+
+        let journal = Journal::open(&config).unwrap().unwrap();
+        assert_eq!(journal.rotated_path(1), dir.join("changes.1"));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -16,6 +16,21 @@ impl<T: std::fmt::UpperHex + num::Num + Default> MacAddr<T> for Vec<T> {
     }
 }
 
+/// Serializes byte addresses (MAC, DUID) as `AA:BB:..` strings.
+pub mod serde_mac {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    use super::MacAddr;
+
+    pub fn serialize<S: Serializer>(mac: &Vec<u8>, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&mac.as_mac())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        Ok(Vec::<u8>::from_mac(&String::deserialize(deserializer)?))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1,259 +1,709 @@
-use std::net::Ipv4Addr;
+#[cfg(target_os = "windows")]
+use std::collections::BTreeMap;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::path::{Path, PathBuf};
 
+use ipnet::IpNet;
 use log::{debug, info, warn};
 
 pub mod config;
-use self::netbox::address::{AssignedObject, IpAddress};
-use self::netbox::prefix::Prefix;
+use self::netbox::address::{AssignedObject, IpAddress, IpAddressV6};
+use self::netbox::prefix::{Prefix, PrefixV6};
+#[cfg(target_os = "windows")]
+use self::netbox::journal::journal_updates;
+#[cfg(target_os = "windows")]
+use self::netbox::model::NestedTag;
 use self::netbox::range::IpRange;
-use self::windhcp::reservation::Reservation;
+use self::plan::{diff_failover, diff_scope_v4, diff_scope_v6, failover_conflicts, remove_scope_v4, remove_scope_v6, Plan, Sources};
+#[cfg(target_os = "windows")]
+use self::plan::Change;
+use self::state::{DhcpState, DnsFlags, ReservationClientTypes, ReservationV4, ReservationV6, ScopeV4, ScopeV6};
 use self::{config::SyncConfig, netbox::NetboxApi};
+mod export;
+use self::export::Export;
+#[cfg(target_os = "windows")]
+use self::export::ExportScopeV4;
+mod hostname;
+mod incremental;
+#[cfg(target_os = "windows")]
+use self::incremental::IncrementalState;
+#[cfg_attr(not(target_os = "windows"), allow(dead_code))]
+mod journal;
+#[cfg(target_os = "windows")]
+use self::journal::{Journal, JournalEntry};
 mod mac;
 use self::mac::MacAddr;
+pub mod owned;
+#[cfg(target_os = "windows")]
+use self::owned::SyncOwned;
+mod plan;
+#[cfg_attr(not(target_os = "windows"), allow(dead_code))]
+mod snapshot;
+#[cfg(target_os = "windows")]
+use self::snapshot::Snapshot;
+pub mod stats;
+#[cfg_attr(not(target_os = "windows"), allow(dead_code))]
+mod status;
+use self::status::{ObjectStatus, StatusReport, STATUS_FIELD};
+mod state;
+mod template;
 pub mod netbox;
 
+#[cfg(target_os = "windows")]
 mod windhcp;
-use self::windhcp::{DnsFlags, Subnet, WinDhcp};
+#[cfg(target_os = "windows")]
+use self::windhcp::WinDhcp;
 
-pub struct Sync {
+/// DHCP server a `Sync` reads and writes by default, without the Windows API only export files.
+#[cfg(target_os = "windows")]
+type DefaultDhcp = WinDhcp;
+#[cfg(not(target_os = "windows"))]
+type DefaultDhcp = Export;
+
+pub struct Sync<D = DefaultDhcp> {
     config: SyncConfig,
     netbox: NetboxApi,
-    dhcp: WinDhcp,
+    dhcp: D,
+    #[cfg_attr(not(target_os = "windows"), allow(dead_code))]
     noop: bool,
-    scope: Option<Ipv4Addr>,
+    scope: Option<IpAddr>,
+    changed: Option<Vec<IpNet>>,
+    #[cfg_attr(not(target_os = "windows"), allow(dead_code))]
+    run_id: String,
+    #[cfg_attr(not(target_os = "windows"), allow(dead_code))]
+    plan_file: Option<PathBuf>,
 }
 
+#[cfg(target_os = "windows")]
 impl Sync {
-    pub fn new(config: SyncConfig, noop: bool, scope: Option<Ipv4Addr>) -> Self {
-        let netbox = NetboxApi::new(&config.netbox);
+    pub fn new(config: SyncConfig, noop: bool, scope: Option<IpAddr>) -> Result<Self, Box<dyn std::error::Error + Send + std::marker::Sync>> {
+        config.validate()?;
+        let netbox = NetboxApi::new(&config.netbox)?;
         let dhcp = WinDhcp::new(config.dhcp.server());
 
-        Self { config, netbox, dhcp, noop, scope}
+        let run_id = format!("{}-{}", chrono::Local::now().format("%Y%m%dT%H%M%S"), std::process::id());
+
+        Ok(Self { config, netbox, dhcp, noop, scope, changed: None, run_id, plan_file: None })
     }
 
-    pub fn run(&self) -> Result<(), Box<dyn std::error::Error + Send + std::marker::Sync>> {
+    pub fn run(&mut self) -> Result<(), Box<dyn std::error::Error + Send + std::marker::Sync>> {
         info!("Start sync from {} to {} ({} {})", self.config.netbox.apiurl(), self.config.dhcp.server(), env!("CARGO_PKG_NAME"), git_version::git_version!(prefix = "git:", cargo_prefix = "cargo:", fallback = "unknown"));
 
+        self.netbox.check_token()?;
         let netbox_version = self.netbox.version()?;
         debug!("Netbox Version: {}", netbox_version);
         let dhcp_version = self.dhcp.get_version()?;
         debug!("Windows DHCp Server Version: {}.{}", dhcp_version.0, dhcp_version.1);
 
-        let prefixes = self.netbox.get_prefixes()?;
-        let ranges = self.netbox.get_ranges()?;
-        info!("Found {} Prefixes and {} Ranges", prefixes.len(), ranges.len());
+        let state = self.incremental()?;
 
-        for prefix in prefixes.iter() {
-            if let Some(scope) = self.scope {
-                if !prefix.prefix().contains(&scope) {
-                    debug!("Skip Prefix {} - {}", prefix.prefix(), prefix.description());
-                    continue;
+        let mut sources = Sources::new();
+        let mut report = StatusReport::default();
+        let plan = self.plan(&mut sources, &mut report)?;
+        self.write_plan(&plan)?;
+
+        self.snapshot(&plan)?;
+        let result = self.apply(&plan, &sources, &mut report);
+        self.write_status(&report);
+        self.write_journal(&report);
+        result?;
+        self.write_stats(&report);
+
+        if let (Some(state), Some(path), false) = (state, self.config.incremental.state(), self.noop) {
+            state.save(path).map_err(|e| format!("Unable to write sync state {}: {}", path.display(), e))?;
+        }
+
+        Ok(())
+    }
+
+    fn write_plan(&self, plan: &Plan) -> Result<(), Box<dyn std::error::Error + Send + std::marker::Sync>> {
+        let Some(path) = self.plan_file.as_ref() else { return Ok(()) };
+
+        let changes: Vec<String> = plan.iter().map(Change::to_string).collect();
+        std::fs::write(path, serde_json::to_vec(&changes)?)
+            .map_err(|e| format!("Unable to write plan {}: {}", path.display(), e).into())
+    }
+
+    /// Writes the sync status to the Netbox objects, failures are only logged.
+    fn write_status(&self, report: &StatusReport) {
+        if self.noop || !self.config.netbox.write_status() { return; }
+
+        let updates = report.updates(chrono::Utc::now());
+        if updates.is_empty() { return; }
+
+        match self.netbox.patch_objects(&updates) {
+            Ok(()) => info!("Updated the sync status of {} Netbox objects", updates.len()),
+            Err(e) => warn!("Unable to update the sync status in Netbox: {}", e),
+        }
+    }
+
+    /// Journals new and resolved problems on the Netbox objects, failures are only logged.
+    fn write_journal(&self, report: &StatusReport) {
+        if self.noop || !self.config.netbox.journal_entries() { return; }
+
+        let (problems, solved) = report.journal();
+        let objects: Vec<_> = problems.iter().chain(solved.iter()).collect();
+        let entries = match self.netbox.get_journal_entries(&objects) {
+            Ok(existing) => journal_updates(problems, solved, &existing),
+            Err(e) => {
+                warn!("Unable to read the journal entries from Netbox: {}", e);
+                return;
+            },
+        };
+        if entries.is_empty() { return; }
+
+        match self.netbox.create_journal_entries(&entries) {
+            Ok(()) => info!("Created {} journal entries in Netbox", entries.len()),
+            Err(e) => warn!("Unable to create journal entries in Netbox: {}", e),
+        }
+    }
+
+    /// Writes the address usage of the synced scopes to their prefix or range, failures are only logged.
+    fn write_stats(&self, report: &StatusReport) {
+        let config = &self.config.stats;
+        if self.noop || !config.enabled() { return; }
+
+        let stats = match self.dhcp.get_scope_stats() {
+            Ok(stats) => stats,
+            Err(e) => {
+                warn!("Unable to get the scope statistics: {}", e);
+                return;
+            },
+        };
+
+        let mut updates = Vec::new();
+        for (prefix, range) in report.scopes() {
+            let Some(stats) = stats.get(&prefix.addr()) else { continue };
+            debug!("Scope {} uses {} of {} addresses", prefix.prefix(), stats.in_use, stats.in_use + stats.free);
+
+            if config.prefix() {
+                let tags = self.stats_tags(prefix.url(), prefix.fetched_tags());
+                if let Some(update) = config.update(stats, |f| prefix.custom_field(f), tags.as_deref()) {
+                    updates.push((prefix.url().to_string(), update));
+                }
+            }
+            if config.range() {
+                let tags = self.stats_tags(range.url(), range.fetched_tags());
+                if let Some(update) = config.update(stats, |f| range.custom_field(f), tags.as_deref()) {
+                    updates.push((range.url().to_string(), update));
                 }
             }
+        }
+        updates.retain(|(url, _)| !url.is_empty());
+        if updates.is_empty() { return; }
 
-            info!("Sync Prefix {} - {}", prefix.prefix(), prefix.description());
+        match self.netbox.patch_objects(&updates) {
+            Ok(()) => info!("Updated the scope statistics of {} Netbox objects", updates.len()),
+            Err(e) => warn!("Unable to update the scope statistics in Netbox: {}", e),
+        }
+    }
 
-            let range = match ranges.iter().find(|&r| r.is_contained(prefix)) {
-                Some(r) => r,
-                None => {
-                    warn!("Skip Prefix {} no range found", prefix.prefix());
-                    continue;
-                }
-            };
-            let subnet = self.sync_subnetv4(prefix, range)?;
+    /// Current tags of an object for the full tag, fetched from Netbox if the list left them out.
+    /// `None` if they are unavailable, the tags are left alone then.
+    fn stats_tags(&self, url: &str, tags: Option<&[NestedTag]>) -> Option<Vec<NestedTag>> {
+        if let Some(tags) = tags { return Some(tags.to_vec()); }
+        if url.is_empty() || self.config.stats.full_tag().is_none() { return None; }
+
+        match self.netbox.get_tags(url) {
+            Ok(tags) => Some(tags),
+            Err(e) => {
+                warn!("Unable to get the tags of {}: {}", url, e);
+                None
+            },
+        }
+    }
+
+    /// Restricts the sync to the scopes touched by Netbox changes since the last run,
+    /// returns the state to save after the sync succeeded.
+    fn incremental(&mut self) -> Result<Option<IncrementalState>, Box<dyn std::error::Error + Send + std::marker::Sync>> {
+        let path = match self.config.incremental.state() {
+            Some(path) if self.scope.is_none() && self.changed.is_none() => path,
+            _ => return Ok(None),
+        };
+        let now = chrono::Utc::now();
+
+        let previous = match IncrementalState::load(path)? {
+            Some(previous) if !self.config.incremental.full_sync_due(&previous, now) => previous,
+            _ => {
+                let last_change_id = self.netbox.get_last_change_id()?;
+                info!("Full sync up to change {}", last_change_id);
+                return Ok(Some(IncrementalState { last_change_id, last_full_sync: now }));
+            },
+        };
+
+        let mut changes = self.netbox.get_object_changes(previous.last_change_id)?;
+        let last_change_id = changes.iter().map(|c| c.id).max().unwrap_or(previous.last_change_id);
 
-            /* Update Reservations */
-            let mut dhcp_reservations = subnet.get_reservations().unwrap();
-            let reservations = self.netbox.get_reservations_for_subnet(&prefix.prefix())?;
-            info!("  Subnet {}: Found {} reservations", &prefix.addr(), reservations.len());
+        let owned = SyncOwned::new(&self.config.stats);
+        let count = changes.len();
+        changes.retain(|c| !owned.only_owned_changed(c.prechange(), c.postchange()));
+        if changes.len() < count {
+            debug!("Ignore {} changes of fields written by the sync", count - changes.len());
+        }
+
+        if let Some(change) = changes.iter().find(|c| c.needs_full_sync()) {
+            info!("Full sync up to change {}, {} {} changed", last_change_id, change.changed_object_type, change.changed_object_id);
+            return Ok(Some(IncrementalState { last_change_id, last_full_sync: now }));
+        }
 
-            for reservation in reservations.iter() {
-                self.sync_reservationv4(&subnet, reservation,  dhcp_reservations.remove(&reservation.address()))?;
+        let mut changed = Vec::new();
+        for change in changes.iter() {
+            changed.append(&mut change.networks());
+            if let Some((filter, id)) = change.interface() {
+                for address in self.netbox.get_interface_addresses(filter, id)? {
+                    changed.push(IpNet::from(address.address()));
+                }
             }
+        }
+        info!("Incremental sync of {} changes after change {}", changes.len(), previous.last_change_id);
+
+        self.changed = Some(changed);
+        Ok(Some(IncrementalState { last_change_id, last_full_sync: previous.last_full_sync }))
+    }
 
-            /* Cleanup old Reservations */
-            for (reservationaddress, macaddress) in dhcp_reservations {
-                if !self.noop { subnet.remove_reservation(reservationaddress, &macaddress.for_client)?; }
-                info!("  Reservation {}: Remove Reservation {}", &reservationaddress, &macaddress.for_client.as_mac());
+    pub fn rollback(&self, path: &Path) -> Result<(), Box<dyn std::error::Error + Send + std::marker::Sync>> {
+        let snapshot = Snapshot::load(path)?;
+        info!("Start rollback of {} to snapshot {} from {}", self.config.dhcp.server(), snapshot.run_id, snapshot.timestamp);
+
+        if snapshot.server != self.config.dhcp.server() {
+            return Err(format!("Snapshot {} was taken from {} and not from {}", path.display(), snapshot.server, self.config.dhcp.server()).into());
+        }
+
+        let mut current_v4 = BTreeMap::new();
+        let mut current_exclusions = BTreeMap::new();
+        for subnet in snapshot.scopes_v4.keys() {
+            let scope = self.dhcp.get_scope_v4(subnet)?;
+            if scope.is_some() {
+                current_exclusions.insert(*subnet, self.dhcp.get_exclusions(subnet)?);
             }
+            current_v4.insert(*subnet, scope);
+        }
+        let mut current_v6 = BTreeMap::new();
+        for prefix in snapshot.scopes_v6.keys() {
+            current_v6.insert(*prefix, self.dhcp.get_scope_v6(prefix)?);
         }
 
-        /* Cleanup old Subnets */
-        let prefixes_ip: Vec<Ipv4Addr> = prefixes.iter().map(|i| i.addr()).collect();
+        let plan = snapshot.restore_plan(&current_v4, &current_exclusions, &current_v6);
+
+        self.snapshot(&plan)?;
+        self.apply(&plan, &Sources::new(), &mut StatusReport::default())
+    }
+
+    pub fn export(&self, path: &Path) -> Result<(), Box<dyn std::error::Error + Send + std::marker::Sync>> {
+        info!("Start export of {} to {}", self.config.dhcp.server(), path.display());
+
+        let mut export = Export::new(self.config.dhcp.server());
+        let mut relations = Vec::new();
+
         for subnet in self.dhcp.get_subnets()? {
-            if prefixes_ip.contains(&subnet) {
-                continue;
+            let scope = match self.dhcp.get_scope_v4(&subnet)? {
+                Some(scope) => scope,
+                None => continue,
+            };
+            if let Some(relation) = scope.failover.as_ref() {
+                if !relations.contains(relation) { relations.push(relation.clone()); }
+            }
+            let exclusions = self.dhcp.get_exclusions(&subnet)?;
+            export.scopes_v4.push(ExportScopeV4 { scope, exclusions });
+        }
+
+        for name in relations.iter() {
+            match self.dhcp.get_failover_relation(name)? {
+                Some(relation) => export.failover_relations.push(relation),
+                None => warn!("Failover relationship {} not found", name),
+            }
+        }
+
+        for prefix in self.dhcp.get_subnets_v6()? {
+            if let Some(scope) = self.dhcp.get_scope_v6(&prefix)? {
+                export.scopes_v6.push(scope);
             }
+        }
+
+        info!("Exported {} scopes, {} v6 scopes and {} failover relationships", export.scopes_v4.len(), export.scopes_v6.len(), export.failover_relations.len());
+        export.save(path)
+    }
 
-            let dhcp_subnet = self.dhcp.get_subnet(&subnet).unwrap().unwrap();
-            let failover = dhcp_subnet.get_failover_relationship().unwrap();
-            if let Some(failover) = failover {
-                info!("Subnet {}: Remove from Failover Relation: {:?}", &subnet, &failover);
-                if !self.noop {
-                    if let Err(e) = dhcp_subnet.remove_failover_relationship(&failover) {
-                        warn!("Removing {} from {} faild with error code: {}", dhcp_subnet.subnet_mask, &failover, e)
+    /// Saves the current state of all scopes touched by the plan.
+    fn snapshot(&self, plan: &Plan) -> Result<(), Box<dyn std::error::Error + Send + std::marker::Sync>> {
+        if self.noop || self.config.snapshot.dir().is_none() { return Ok(()); }
+
+        let mut snapshot = Snapshot::new(&self.run_id, self.config.dhcp.server());
+        for change in plan.iter() {
+            match change {
+                Change::V4(subnet, _) if !snapshot.scopes_v4.contains_key(subnet) => {
+                    let scope = self.dhcp.get_scope_v4(subnet)?;
+                    if scope.is_some() {
+                        snapshot.exclusions_v4.insert(*subnet, self.dhcp.get_exclusions(subnet)?);
                     }
+                    snapshot.scopes_v4.insert(*subnet, scope);
+                },
+                Change::V6(prefix, _) if !snapshot.scopes_v6.contains_key(prefix) => {
+                    snapshot.scopes_v6.insert(*prefix, self.dhcp.get_scope_v6(prefix)?);
+                },
+                _ => (),
+            }
+        }
+        if snapshot.is_empty() { return Ok(()); }
+
+        match snapshot.save(&self.config.snapshot) {
+            Ok(Some(path)) => info!("Wrote snapshot {}", path.display()),
+            Ok(None) => (),
+            Err(e) => return Err(format!("Unable to write snapshot: {}", e).into()),
+        }
+
+        Ok(())
+    }
+
+    fn apply(&self, plan: &Plan, sources: &Sources, report: &mut StatusReport) -> Result<(), Box<dyn std::error::Error + Send + std::marker::Sync>> {
+        let mut journal = match self.noop || plan.is_empty() {
+            true => None,
+            false => Journal::open(&self.config.journal)?,
+        };
+        let urls = |change: &Change| [change.scope_address(), change.object_address()].into_iter()
+            .flatten()
+            .filter_map(|address| sources.get(&address))
+            .collect::<Vec<&String>>();
+
+        for (idx, change) in plan.iter().enumerate() {
+            info!("  {}", change);
+            if self.noop { continue; }
+
+            if let Err(e) = self.dhcp.apply(change) {
+                match change.is_failover() {
+                    true => {
+                        warn!("{} failed: {}", change, e);
+                        continue;
+                    },
+                    false => {
+                        let error = e.to_string();
+                        for url in plan.iter().skip(idx).flat_map(urls) {
+                            report.fail(url, &error);
+                        }
+                        return Err(e);
+                    },
                 }
-            }       
-            
-            if !self.noop { self.dhcp.remove_subnet(subnet)?; }
-            info!("Subnet {}: Removed", &subnet);
+            }
+
+            for url in urls(change) {
+                report.changed(url);
+            }
+
+            // The change is already applied, a failing journal must not abort the rest of the plan.
+            if let Some(journal) = journal.as_mut() {
+                if let Err(e) = journal.record(&JournalEntry::new(&self.run_id, self.config.dhcp.server(), change, sources)) {
+                    warn!("Unable to write the change journal: {}", e);
+                }
+            }
         }
 
         Ok(())
     }
 
-    fn sync_subnetv4(
-        &self,
-        prefix: &Prefix,
-        range: &IpRange,
-    ) -> Result<Subnet, Box<dyn std::error::Error + Send + std::marker::Sync>> {
-        let subnetaddress = &prefix.addr();
+}
+
+impl Sync<Export> {
+    /// Sync against an export file instead of a live DHCP server, nothing is applied.
+    pub fn offline(config: SyncConfig, scope: Option<IpAddr>, path: &Path) -> Result<Self, Box<dyn std::error::Error + Send + std::marker::Sync>> {
+        config.validate()?;
+        let netbox = NetboxApi::new(&config.netbox)?;
+        let dhcp = Export::load(path, config.dhcp.server())?;
+
+        let run_id = format!("{}-{}", chrono::Local::now().format("%Y%m%dT%H%M%S"), std::process::id());
 
-        let subnet = self.dhcp.get_or_create_subnet(subnetaddress, &prefix.netmask()).unwrap();
-        debug!("Found: {} - {}", subnetaddress, subnet.subnet_name);
+        Ok(Self { config, netbox, dhcp, noop: true, scope, changed: None, run_id, plan_file: None })
+    }
+
+    pub fn diff(&self) -> Result<Plan, Box<dyn std::error::Error + Send + std::marker::Sync>> {
+        info!("Start diff from {} to export of {} from {}", self.config.netbox.apiurl(), self.dhcp.server, self.dhcp.timestamp);
 
-        /* Subnet Netmask */
-        if subnet.subnet_mask != prefix.netmask() {
-            if !self.noop { subnet.set_mask(prefix.netmask())?; }
-            info!("  Subnet {}: Updated netmask to {}", &subnetaddress, prefix.netmask());
+        self.netbox.check_token()?;
+        let plan = self.plan(&mut Sources::new(), &mut StatusReport::default())?;
+        for change in plan.iter() {
+            info!("  {}", change);
         }
+        info!("{} changes", plan.len());
 
-        /* Subnet Name */
-        if subnet.subnet_name != prefix.description() {
-            if !self.noop { subnet.set_name(prefix.description())?; }
-            info!("  Subnet {}: Updated name to {}", &subnetaddress, prefix.description());
+        Ok(plan)
+    }
+}
+
+impl<D: DhcpState> Sync<D> {
+    fn plan(&self, sources: &mut Sources, report: &mut StatusReport) -> Result<Plan, Box<dyn std::error::Error + Send + std::marker::Sync>> {
+        let mut plan = self.plan_failover()?;
+        plan.append(&mut self.plan_v4(sources, report)?);
+        if self.config.dhcp.v6().enabled() {
+            plan.append(&mut self.plan_v6(sources, report)?);
         }
 
-        /* Subnet Comment */
-        if subnet.subnet_comment != prefix.description() {
-            if !self.noop { subnet.set_comment(prefix.description())?; }
-            info!("  Subnet {}: Updated comment to {}", &subnetaddress, prefix.description());
+        Ok(plan)
+    }
+
+    /// Restricts the sync to the scopes containing one of the networks or an address assigned
+    /// to one of the device or VM interfaces, nothing is restricted without any of them.
+    pub fn set_targets(
+        &mut self,
+        networks: &[IpNet],
+        interfaces: &[u64],
+        vminterfaces: &[u64],
+    ) -> Result<(), Box<dyn std::error::Error + Send + std::marker::Sync>> {
+        if networks.is_empty() && interfaces.is_empty() && vminterfaces.is_empty() {
+            return Ok(());
         }
 
-        /* DHCP Range */
-        if (range.start_address(), range.end_address()) != subnet.get_subnet_range()? {
-            if !self.noop { subnet.set_subnet_range(range.start_address(), range.end_address())?; }
-            info!("  Subnet {}: Updated range to {}-{}", &subnetaddress, range.start_address(), range.end_address());
+        let mut changed = networks.to_vec();
+        for (filter, ids) in [("interface_id", interfaces), ("vminterface_id", vminterfaces)] {
+            for id in ids {
+                for address in self.netbox.get_interface_addresses(filter, *id)? {
+                    changed.push(IpNet::from(address.address()));
+                }
+            }
         }
+        info!("Sync only the scopes of {}", changed.iter().map(IpNet::to_string).collect::<Vec<_>>().join(", "));
 
-        /* Lease Duration */
-        let lease_duration = prefix.lease_duration()
-            .or_else(|| Some(self.config.dhcp.lease_duration()));
-        if lease_duration != subnet.get_lease_duration()? {
-            if !self.noop { subnet.set_lease_duration(lease_duration)?; }
-            info!("  Subnet {}: Updated lease duration to {}", &subnetaddress, lease_duration.unwrap_or_default());
+        self.changed = Some(changed);
+        Ok(())
+    }
+
+    /// Writes the planned changes as JSON list to this file, used by the jobs of the webhook server.
+    pub fn set_plan_file(&mut self, path: Option<PathBuf>) {
+        self.plan_file = path;
+    }
+
+    fn in_scope(&self, net: IpNet) -> bool {
+        let scope = match self.scope {
+            Some(scope) => net.contains(&scope),
+            None => true,
+        };
+        let changed = match self.changed.as_ref() {
+            Some(changed) => changed.iter().any(|c| net.contains(c)),
+            None => true,
+        };
+        scope && changed
+    }
+
+    fn plan_failover(&self) -> Result<Plan, Box<dyn std::error::Error + Send + std::marker::Sync>> {
+        let mut plan = Plan::new();
+
+        for relation_config in self.config.dhcp.failover_relations() {
+            let relation = relation_config.relation(self.config.dhcp.server())?;
+            let actual = self.dhcp.failover_relation(&relation.name)?;
+
+            if let Some(actual) = actual.as_ref() {
+                for conflict in failover_conflicts(&relation, actual) {
+                    warn!("Failover {}: {}, the relationship has to be recreated manually", &relation.name, conflict);
+                }
+            }
+
+            plan.append(&mut diff_failover(&relation, actual.as_ref(), relation_config.shared_secret()));
         }
 
-        /* DNS Update */
-        let dns_flags = prefix.dns_flags()
-            .map(DnsFlags::from).or_else(|| self.config.dhcp.default_dns_flags());
-        if dns_flags != subnet.get_dns_flags()? {
-            if !self.noop { subnet.set_dns_flags(dns_flags.as_ref())?; }
-            info!("  Subnet {}: Updated dns flags to {:?}", &subnetaddress, dns_flags);
+        Ok(plan)
+    }
+
+    fn plan_v4(&self, sources: &mut Sources, report: &mut StatusReport) -> Result<Plan, Box<dyn std::error::Error + Send + std::marker::Sync>> {
+        let prefixes = self.netbox.get_prefixes()?;
+        let ranges = self.netbox.get_ranges()?;
+        info!("Found {} Prefixes and {} Ranges", prefixes.len(), ranges.len());
+
+        let mut plan = Plan::new();
+
+        for prefix in prefixes.iter() {
+            if !self.in_scope(IpNet::V4(prefix.prefix())) {
+                debug!("Skip Prefix {} - {}", prefix.prefix(), prefix.description());
+                continue;
+            }
+
+            info!("Sync Prefix {} - {}", prefix.prefix(), prefix.description());
+
+            let current_status = prefix.template_value(&format!("cf.{}", STATUS_FIELD));
+            let range = match ranges.iter().find(|&r| r.is_contained(prefix)) {
+                Some(r) => r,
+                None => {
+                    warn!("Skip Prefix {} no range found", prefix.prefix());
+                    report.set(prefix.url(), current_status, ObjectStatus::Skipped(String::from("no range found")));
+                    continue;
+                }
+            };
+            report.set(prefix.url(), current_status, ObjectStatus::Ok);
+            report.add_scope(prefix, range);
+
+            let actual = self.dhcp.scope_v4(&prefix.addr())?;
+            let desired = self.desired_scope_v4(prefix, range, actual.as_ref(), sources, report)?;
+            plan.append(&mut diff_scope_v4(&desired, actual.as_ref()));
         }
 
-        /* Router */
-        let routers = match prefix.routers() {
+        /* Cleanup old Subnets */
+        let prefixes_ip: Vec<Ipv4Addr> = prefixes.iter().map(|i| i.addr()).collect();
+        for subnet in self.dhcp.subnets_v4()? {
+            if prefixes_ip.contains(&subnet) {
+                continue;
+            }
+
+            if let Some(actual) = self.dhcp.scope_v4(&subnet)? {
+                plan.append(&mut remove_scope_v4(&actual));
+            }
+        }
+
+        Ok(plan)
+    }
+
+    fn desired_scope_v4(
+        &self,
+        prefix: &Prefix,
+        range: &IpRange,
+        actual: Option<&ScopeV4>,
+        sources: &mut Sources,
+        report: &mut StatusReport,
+    ) -> Result<ScopeV4, Box<dyn std::error::Error + Send + std::marker::Sync>> {
+        let mut scope = ScopeV4::new(prefix.addr(), prefix.netmask());
+        sources.insert(IpAddr::V4(prefix.addr()), prefix.url().to_string());
+
+        scope.name = self.config.dhcp.templates().scope_name(prefix);
+        scope.comment = self.config.dhcp.templates().scope_comment(prefix);
+        scope.range = (range.start_address(), range.end_address());
+        scope.lease_duration = prefix.lease_duration()
+            .or_else(|| Some(self.config.dhcp.lease_duration()));
+        scope.dns_flags = prefix.dns_flags()
+            .map(DnsFlags::from).or_else(|| self.config.dhcp.default_dns_flags());
+        scope.routers = match prefix.routers() {
             Some(ip) => ip,
             None => {
                 let routers = self.netbox.get_router_for_subnet(&prefix.prefix())?;
                 routers.iter().map(|i| i.address()).collect()
             }
         };
-        if routers != subnet.get_routers()? {
-            if !self.noop { subnet.set_routers(&routers)?; }
-            info!("  Subnet {}: Updated routers to {:?}", &subnetaddress, routers);
-        }
+        scope.dns_domain = prefix.dns_domain()
+            .or_else(|| self.config.dhcp.default_dns_domain()).cloned();
+        scope.dns_servers = prefix.dns_servers()
+            .unwrap_or_else(|| self.config.dhcp.default_dns_servers().to_vec());
+        scope.failover = prefix.failover_relation()
+            .or_else(|| self.config.dhcp.default_failover_relation()).cloned();
 
-        /* DNS Domain */
-        let dns_domain = prefix.dns_domain()
-            .or_else(|| self.config.dhcp.default_dns_domain());
-        if dns_domain != subnet.get_dns_domain()?.as_ref() {
-            if !self.noop { subnet.set_dns_domain(dns_domain)?; }
-            info!("  Subnet {}: Updated dns domain to {:?}", &subnetaddress, dns_domain);
-        }
+        /* Reservations */
+        let reservations = self.netbox.get_reservations_for_subnet(&prefix.prefix())?;
+        info!("  Subnet {}: Found {} reservations", &prefix.addr(), reservations.len());
 
-        /* DNS Server */
-        let dns = prefix.dns_servers()
-            .unwrap_or_else(|| self.config.dhcp.default_dns_servers().to_vec());
-        if dns != subnet.get_dns_servers()? {
-            if !self.noop { subnet.set_dns_servers(&dns)?; }
-            info!("  Subnet {}: Updated dns to {:?}", &subnetaddress, dns);
-        }
+        for reservation in reservations.iter() {
+            let current = actual.and_then(|a| a.reservations.get(&reservation.address()));
+            let current_status = reservation.template_value(&format!("cf.{}", STATUS_FIELD));
 
-        /* Failover */
-        let expected_failover = prefix.failover_relation()
-            .or_else(|| self.config.dhcp.default_failover_relation());
-        let failover = subnet.get_failover_relationship().unwrap();
-
-        if failover != expected_failover.cloned() {
-            
-            if let Some(failover) = failover {
-                info!("   Remove from Failover Relation: {:?}", &failover);
-                if !self.noop {
-                    if let Err(e) = subnet.remove_failover_relationship(&failover) {
-                        warn!("Removing {} from {} faild with error code: {}", subnet.subnet_mask, &failover, e)
-                    }
-                }
-            }
-            if let Some(expected_failover) = expected_failover {
-                info!("   Add to Failover Relation: {:?}", &expected_failover); 
-                if !self.noop {
-                    if let Err(e) = subnet.add_failover_relationship(&expected_failover) {
-                        warn!("Adding {} to {} faild with error code: {}", subnet.subnet_mask, &expected_failover, e)
+            let mac = match self.get_macaddress_for_reservation(reservation)? {
+                Some(mac) => mac,
+                None => {
+                    warn!("Error no MAC address found for IP {}", &reservation.address());
+                    report.set(reservation.url(), current_status, ObjectStatus::Skipped(String::from("no MAC address found")));
+                    if let Some(current) = current {
+                        scope.reservations.insert(current.address, current.clone());
                     }
-                }
-            }
+                    continue;
+                },
+            };
+            report.set(reservation.url(), current_status, ObjectStatus::Ok);
+
+            sources.insert(IpAddr::V4(reservation.address()), reservation.url().to_string());
+            scope.reservations.insert(reservation.address(), ReservationV4 {
+                address: reservation.address(),
+                client_type: current.map(|c| c.client_type).unwrap_or(ReservationClientTypes::Both),
+                mac,
+                name: self.config.dhcp.reservation_name().client_name(reservation, scope.dns_domain.as_ref()),
+                comment: self.config.dhcp.templates().reservation_comment(prefix, reservation),
+            });
         }
 
-        Ok(subnet)
+        Ok(scope)
     }
 
-    fn sync_reservationv4(
-        &self,
-        subnet: &Subnet,
-        reservation: &IpAddress,
-        dhcp_reservation: Option<Reservation>,
-    ) -> Result<(), Box<dyn std::error::Error + Send + std::marker::Sync>> {
-        let mac = match self.get_macaddress_for_reservation(reservation)? {
-            Some(mac) => mac,
-            None => {
-                warn!("Error no MAC address found for IP {}", &reservation.address());
-                return Ok(());
-            },
-        };
+    fn plan_v6(&self, sources: &mut Sources, report: &mut StatusReport) -> Result<Plan, Box<dyn std::error::Error + Send + std::marker::Sync>> {
+        let prefixes = self.netbox.get_prefixes6()?;
+        info!("Found {} IPv6 Prefixes", prefixes.len());
 
-        /* Reservation */
-        if let Some(macaddress) = dhcp_reservation.map(|r| r.for_client) {
-            if macaddress != mac {
-                if !self.noop {
-                    subnet.remove_reservation(reservation.address(), &macaddress)?;
-                    subnet.add_reservation(reservation.address(), &mac)?;
-                }
-                info!("  Reservation {}: Update Reservation {:?}", &reservation.address(), &mac.as_mac());
+        let mut plan = Plan::new();
+
+        for prefix in prefixes.iter() {
+            if !self.in_scope(IpNet::V6(prefix.prefix())) {
+                debug!("Skip Prefix {} - {}", prefix.prefix(), prefix.description());
+                continue;
             }
-        } else {
-            if !self.noop { subnet.add_reservation(reservation.address(), &mac)?; }
 
-            info!("  Reservation {}: Create Reservation {:?}", &reservation.address(), &mac.as_mac());
+            info!("Sync Prefix {} - {}", prefix.prefix(), prefix.description());
+
+            report.set(prefix.url(), prefix.template_value(&format!("cf.{}", STATUS_FIELD)), ObjectStatus::Ok);
+
+            let actual = self.dhcp.scope_v6(&prefix.addr())?;
+            let desired = self.desired_scope_v6(prefix, actual.as_ref(), sources, report)?;
+            plan.append(&mut diff_scope_v6(&desired, actual.as_ref()));
         }
 
-        /* Client Name */
-        let name = self.dhcp.get_client_name(reservation.address()).unwrap_or_default();
-        if name != reservation.dns_name() {
-            if !self.noop { self.dhcp.set_client_name(reservation.address(), reservation.dns_name())?; }
-            info!("  Reservation {}: Set client name to {}", &reservation.address(), &reservation.dns_name());
+        /* Cleanup old Subnets */
+        let prefixes_ip: Vec<Ipv6Addr> = prefixes.iter().map(|i| i.addr()).collect();
+        for subnet in self.dhcp.subnets_v6()? {
+            if prefixes_ip.contains(&subnet) {
+                continue;
+            }
+
+            if let Some(actual) = self.dhcp.scope_v6(&subnet)? {
+                plan.append(&mut remove_scope_v6(&actual));
+            }
         }
 
-        /* Client Comment */
-        let comment = self.dhcp.get_client_comment(reservation.address()).unwrap_or_default();
-        if comment != reservation.description() {
-            if !self.noop { self.dhcp.set_client_comment(reservation.address(), reservation.description())?; }
-            info!("  Reservation {}: Set client comment to {}", &reservation.address(), &reservation.description());
+        Ok(plan)
+    }
+
+    fn desired_scope_v6(
+        &self,
+        prefix: &PrefixV6,
+        actual: Option<&ScopeV6>,
+        sources: &mut Sources,
+        report: &mut StatusReport,
+    ) -> Result<ScopeV6, Box<dyn std::error::Error + Send + std::marker::Sync>> {
+        let v6 = self.config.dhcp.v6();
+        let mut scope = ScopeV6::new(prefix.addr(), prefix.prefix_len());
+        sources.insert(IpAddr::V6(prefix.addr()), prefix.url().to_string());
+
+        scope.name = self.config.dhcp.templates().scope_name(prefix);
+        scope.comment = self.config.dhcp.templates().scope_comment(prefix);
+        scope.preference = prefix.preference().unwrap_or(v6.preference());
+        scope.preferred_lifetime = prefix.preferred_lifetime().unwrap_or(v6.preferred_lifetime());
+        scope.valid_lifetime = prefix.valid_lifetime().unwrap_or(v6.valid_lifetime());
+        scope.dns_servers = prefix.dns_servers()
+            .unwrap_or_else(|| v6.default_dns_servers().to_vec());
+        scope.domain_search = prefix.domain_search()
+            .unwrap_or_else(|| v6.default_domain_search().to_vec());
+
+        let dns_domain = prefix.dns_domain()
+            .or_else(|| self.config.dhcp.default_dns_domain());
+
+        /* Reservations */
+        let reservations: Vec<IpAddressV6> = self.netbox.get_reservations_for_subnet6(&prefix.prefix())?;
+        info!("  Subnet {}: Found {} reservations", &prefix.addr(), reservations.len());
+
+        for reservation in reservations.iter() {
+            let current_status = reservation.template_value(&format!("cf.{}", STATUS_FIELD));
+            let duid = match reservation.reservation_duid() {
+                Some(duid) => Vec::<u8>::from_mac(duid),
+                None => {
+                    warn!("Error no DUID found for IP {}", &reservation.address());
+                    report.set(reservation.url(), current_status, ObjectStatus::Skipped(String::from("no DUID found")));
+                    if let Some(current) = actual.and_then(|a| a.reservations.get(&reservation.address())) {
+                        scope.reservations.insert(current.address, current.clone());
+                    }
+                    continue;
+                },
+            };
+            report.set(reservation.url(), current_status, ObjectStatus::Ok);
+
+            sources.insert(IpAddr::V6(reservation.address()), reservation.url().to_string());
+            scope.reservations.insert(reservation.address(), ReservationV6 {
+                address: reservation.address(),
+                duid,
+                iaid: reservation.reservation_iaid().unwrap_or_default(),
+                name: self.config.dhcp.reservation_name().client_name(reservation, dns_domain),
+                comment: self.config.dhcp.templates().reservation_comment(prefix, reservation),
+            });
         }
 
-        Ok(())
+        Ok(scope)
     }
 
     fn get_macaddress_for_reservation(
@@ -262,9 +712,10 @@ impl Sync {
     ) -> Result<Option<Vec<u8>>, Box<dyn std::error::Error + Send + std::marker::Sync>> {
         let mac = match reservation.reservation_mac() {
             Some(mac) => Some(mac.clone()),
-            None => match reservation.assigned_object_url() {
-                Some(url) => self.get_macaddress_for_reservation_from_assigned_object(url)?,
-                None => None,
+            None => match (reservation.assigned_object_mac(), reservation.assigned_object_url()) {
+                (Some(mac), _) => Some(mac.clone()),
+                (None, Some(url)) => self.get_macaddress_for_reservation_from_assigned_object(url)?,
+                (None, None) => None,
             },
         };
 
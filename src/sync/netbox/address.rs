@@ -1,37 +1,61 @@
-use std::net::Ipv4Addr;
+use std::fmt::Display;
 
 use chrono::NaiveDate;
-use ipnet::Ipv4Net;
+use ipnet::{Ipv4Net, Ipv6Net};
 use serde::Deserialize;
+use serde_json::{Map, Value};
 
-#[derive(Debug, Deserialize)]
-pub struct IpAddress {
+use super::model::{custom_field_to_string, NestedChoice, NestedObject, NestedTag, NetAddr};
+
+pub type IpAddressV6 = IpAddress<Ipv6Net>;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct IpAddress<A = Ipv4Net> {
     url: String,
-    address: Ipv4Net,
+    address: A,
     dns_name: String,
     description: String,
+    tenant: Option<NestedObject>,
+    role: Option<NestedChoice>,
+    vrf: Option<NestedObject>,
+    #[serde(default)]
+    tags: Vec<NestedTag>,
     custom_fields: IpAddressCustomField,
     assigned_object: Option<IpAddressAssignedObject>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 struct IpAddressCustomField {
     dhcp_reservation_mac: Option<String>,
     dhcp_reservation_last_active: Option<NaiveDate>,
+    dhcp_reservation_duid: Option<String>,
+    dhcp_reservation_iaid: Option<u32>,
+    #[serde(flatten)]
+    other: Map<String, Value>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 struct IpAddressAssignedObject {
     url: Option<String>,
+    name: Option<String>,
+    device: Option<IpAddressAssignedParent>,
+    virtual_machine: Option<IpAddressAssignedParent>,
+    #[serde(default)]
+    mac_address: Option<String>,
 }
 
-impl IpAddress {
+#[derive(Debug, Clone, Deserialize)]
+struct IpAddressAssignedParent {
+    name: Option<String>,
+}
+
+impl<A: NetAddr> IpAddress<A> where A::Addr: Display {
     pub fn url(&self) -> &str {
         self.url.as_ref()
     }
 
-    pub fn address(&self) -> Ipv4Addr {
-        self.address.addr()
+    pub fn address(&self) -> A::Addr {
+        self.address.net_addr()
     }
 
     pub fn dns_name(&self) -> &str {
@@ -42,6 +66,10 @@ impl IpAddress {
         self.description.as_ref()
     }
 
+    pub fn tags(&self) -> &[NestedTag] {
+        &self.tags
+    }
+
     pub fn reservation_mac(&self) -> Option<&String> {
         self.custom_fields.dhcp_reservation_mac.as_ref()
     }
@@ -50,6 +78,14 @@ impl IpAddress {
         self.custom_fields.dhcp_reservation_last_active
     }
 
+    pub fn reservation_duid(&self) -> Option<&String> {
+        self.custom_fields.dhcp_reservation_duid.as_ref()
+    }
+
+    pub fn reservation_iaid(&self) -> Option<u32> {
+        self.custom_fields.dhcp_reservation_iaid
+    }
+
     pub fn assigned_object_url(&self) -> Option<&String> {
         match &self.assigned_object {
             Some(ao) => match ao.url.as_ref() {
@@ -59,6 +95,41 @@ impl IpAddress {
             None => None,
         }
     }
+
+    /// MAC address of the assigned interface, only included by the GraphQL source.
+    pub fn assigned_object_mac(&self) -> Option<&String> {
+        self.assigned_object.as_ref()
+            .and_then(|ao| ao.mac_address.as_ref())
+    }
+
+    pub fn assigned_object_name(&self) -> Option<&String> {
+        self.assigned_object.as_ref()
+            .and_then(|ao| ao.name.as_ref())
+    }
+
+    pub fn assigned_parent_name(&self) -> Option<&String> {
+        self.assigned_object.as_ref()
+            .and_then(|ao| ao.device.as_ref().or(ao.virtual_machine.as_ref()))
+            .and_then(|p| p.name.as_ref())
+    }
+
+    pub fn template_value(&self, key: &str) -> Option<String> {
+        match key {
+            "address" => Some(self.address().to_string()),
+            "dns_name" => Some(self.dns_name.clone()),
+            "description" => Some(self.description.clone()),
+            "device" => self.assigned_parent_name().cloned(),
+            "interface" => self.assigned_object_name().cloned(),
+            "tenant" => self.tenant.as_ref().and_then(|t| t.name.clone()),
+            "tenant.slug" => self.tenant.as_ref().and_then(|t| t.slug.clone()),
+            "role" => self.role.as_ref().map(|r| r.label().to_string()),
+            "role.slug" => self.role.as_ref().map(|r| r.value().to_string()),
+            "vrf" => self.vrf.as_ref().and_then(|v| v.name.clone()),
+            key => key.strip_prefix("cf.")
+                .and_then(|cf| self.custom_fields.other.get(cf))
+                .and_then(custom_field_to_string),
+        }
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -70,4 +141,120 @@ impl AssignedObject {
     pub fn mac_address(&self) -> Option<&String> {
         self.mac_address.as_ref()
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_parses_netbox_ip_address() {
+        let ip = serde_json::from_str::<IpAddress>(r#"{
+            "url": "https://netbox.example.com/api/ipam/ip-addresses/1/",
+            "address": "10.112.130.10/24",
+            "dns_name": "",
+            "description": "foo",
+            "custom_fields": {},
+            "assigned_object": null
+        }"#);
+        assert!(ip.is_ok());
+        let ip = ip.unwrap();
+
+        assert_eq!(ip.address(), "10.112.130.10".parse::<std::net::Ipv4Addr>().unwrap());
+        assert_eq!(ip.dns_name(), "");
+        assert_eq!(ip.assigned_object_url(), None);
+        assert_eq!(ip.assigned_parent_name(), None);
+    }
+
+    #[test]
+    fn it_parses_netbox_ipv6_address() {
+        let ip = serde_json::from_str::<IpAddressV6>(r#"{
+            "url": "https://netbox.example.com/api/ipam/ip-addresses/1/",
+            "address": "2001:db8::10/64",
+            "dns_name": "",
+            "description": "foo",
+            "custom_fields": {
+                "dhcp_reservation_duid": "00:01:00:01:2a:3b:4c:5d:00:11:22:33:44:55",
+                "dhcp_reservation_iaid": 12345
+            },
+            "assigned_object": null
+        }"#).unwrap();
+
+        assert_eq!(ip.address(), "2001:db8::10".parse::<std::net::Ipv6Addr>().unwrap());
+        assert_eq!(ip.reservation_duid(), Some(&String::from("00:01:00:01:2a:3b:4c:5d:00:11:22:33:44:55")));
+        assert_eq!(ip.reservation_iaid(), Some(12345));
+    }
+
+    #[test]
+    fn it_parses_netbox_ip_address_assigned_to_device() {
+        let ip = serde_json::from_str::<IpAddress>(r#"{
+            "url": "https://netbox.example.com/api/ipam/ip-addresses/1/",
+            "address": "10.112.130.10/24",
+            "dns_name": "",
+            "description": "foo",
+            "custom_fields": {},
+            "assigned_object": {
+                "url": "https://netbox.example.com/api/dcim/interfaces/1/",
+                "name": "eth0",
+                "device": { "name": "host01" }
+            }
+        }"#).unwrap();
+
+        assert_eq!(ip.assigned_object_name(), Some(&String::from("eth0")));
+        assert_eq!(ip.assigned_parent_name(), Some(&String::from("host01")));
+    }
+
+    #[test]
+    fn it_parses_netbox_ip_address_assigned_to_vm() {
+        let ip = serde_json::from_str::<IpAddress>(r#"{
+            "url": "https://netbox.example.com/api/ipam/ip-addresses/1/",
+            "address": "10.112.130.10/24",
+            "dns_name": "",
+            "description": "foo",
+            "custom_fields": {},
+            "assigned_object": {
+                "url": "https://netbox.example.com/api/virtualization/interfaces/1/",
+                "name": "eth0",
+                "virtual_machine": { "name": "vm01" }
+            }
+        }"#).unwrap();
+
+        assert_eq!(ip.assigned_parent_name(), Some(&String::from("vm01")));
+        assert_eq!(ip.template_value("device"), Some(String::from("vm01")));
+        assert_eq!(ip.template_value("interface"), Some(String::from("eth0")));
+        assert_eq!(ip.template_value("description"), Some(String::from("foo")));
+    }
+
+    #[test]
+    fn it_returns_tenant_role_and_vrf() {
+        let ip = serde_json::from_str::<IpAddress>(r#"{
+            "url": "https://netbox.example.com/api/ipam/ip-addresses/1/",
+            "address": "10.112.130.10/24",
+            "dns_name": "",
+            "description": "foo",
+            "tenant": { "name": "Acme", "slug": "acme" },
+            "role": { "value": "vip", "label": "VIP" },
+            "vrf": { "name": "Office" },
+            "custom_fields": {},
+            "assigned_object": null
+        }"#).unwrap();
+
+        assert_eq!(ip.template_value("tenant"), Some(String::from("Acme")));
+        assert_eq!(ip.template_value("tenant.slug"), Some(String::from("acme")));
+        assert_eq!(ip.template_value("role"), Some(String::from("VIP")));
+        assert_eq!(ip.template_value("role.slug"), Some(String::from("vip")));
+        assert_eq!(ip.template_value("vrf"), Some(String::from("Office")));
+
+        let ip = serde_json::from_str::<IpAddress>(r#"{
+            "url": "",
+            "address": "10.112.130.11/24",
+            "dns_name": "",
+            "description": "",
+            "role": "vip",
+            "custom_fields": {},
+            "assigned_object": null
+        }"#).unwrap();
+        assert_eq!(ip.template_value("role.slug"), Some(String::from("vip")));
+        assert_eq!(ip.template_value("tenant"), None);
+    }
+}
@@ -0,0 +1,191 @@
+use std::net::IpAddr;
+
+use ipnet::IpNet;
+use serde::Deserialize;
+use serde_json::{Map, Value};
+
+/// Object types changing the rendering of every scope (names, comments, options), handled with a full sync.
+const FULL_SYNC_TYPES: [&str; 8] = [
+    "dcim.site",
+    "dcim.location",
+    "ipam.vlan",
+    "ipam.role",
+    "tenancy.tenant",
+    "extras.tag",
+    "extras.customfield",
+    "extras.customfieldchoiceset",
+];
+
+#[derive(Debug, Deserialize)]
+pub struct ObjectChange {
+    pub id: u64,
+    pub changed_object_type: String,
+    pub changed_object_id: u64,
+    prechange_data: Option<Value>,
+    postchange_data: Option<Value>,
+}
+
+impl ObjectChange {
+    fn data(&self) -> impl Iterator<Item = &Value> {
+        self.prechange_data.iter().chain(self.postchange_data.iter())
+    }
+
+    /// Created and deleted objects have no addresses assigned, only a rename matters.
+    fn is_renamed(&self) -> bool {
+        let name = |data: &Option<Value>| data.as_ref().and_then(|d| d.get("name")).cloned();
+        self.prechange_data.is_some() && self.postchange_data.is_some()
+            && name(&self.prechange_data) != name(&self.postchange_data)
+    }
+
+    pub fn prechange(&self) -> Option<&Map<String, Value>> {
+        self.prechange_data.as_ref().and_then(Value::as_object)
+    }
+
+    pub fn postchange(&self) -> Option<&Map<String, Value>> {
+        self.postchange_data.as_ref().and_then(Value::as_object)
+    }
+
+    pub fn needs_full_sync(&self) -> bool {
+        FULL_SYNC_TYPES.contains(&self.changed_object_type.as_str())
+    }
+
+    /// Networks and addresses of changed prefixes, ranges and IP addresses, before and after the change.
+    pub fn networks(&self) -> Vec<IpNet> {
+        let fields: &[&str] = match self.changed_object_type.as_str() {
+            "ipam.prefix" => &["prefix"],
+            "ipam.iprange" => &["start_address", "end_address"],
+            "ipam.ipaddress" => &["address"],
+            _ => &[],
+        };
+
+        self.data()
+            .flat_map(|data| fields.iter().filter_map(|f| data.get(*f).and_then(Value::as_str)))
+            .filter_map(|value| value.parse::<IpNet>().ok())
+            .map(|net| match self.changed_object_type.as_str() {
+                "ipam.prefix" => net.trunc(),
+                _ => IpNet::from(net.addr()),
+            })
+            .collect()
+    }
+
+    /// Filter and id of the changed interface, whose addresses are affected by its MAC address,
+    /// or of the renamed device or VM, whose name the reservations of its addresses fall back to.
+    pub fn interface(&self) -> Option<(&'static str, u64)> {
+        match self.changed_object_type.as_str() {
+            "dcim.interface" => Some(("interface_id", self.changed_object_id)),
+            "virtualization.vminterface" => Some(("vminterface_id", self.changed_object_id)),
+            "dcim.device" if self.is_renamed() => Some(("device_id", self.changed_object_id)),
+            "virtualization.virtualmachine" if self.is_renamed() => Some(("virtual_machine_id", self.changed_object_id)),
+            "dcim.macaddress" => self.data().find_map(|data| {
+                let filter = match data.get("assigned_object_type").and_then(Value::as_str)? {
+                    "dcim.interface" => "interface_id",
+                    "virtualization.vminterface" => "vminterface_id",
+                    _ => return None,
+                };
+                Some((filter, data.get("assigned_object_id").and_then(Value::as_u64)?))
+            }),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct InterfaceAddress {
+    address: IpNet,
+}
+
+impl InterfaceAddress {
+    pub fn address(&self) -> IpAddr {
+        self.address.addr()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn change(json: &str) -> ObjectChange {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn it_returns_changed_networks() {
+        let prefix = change(r#"{
+            "id": 1, "changed_object_type": "ipam.prefix", "changed_object_id": 10,
+            "prechange_data": { "prefix": "10.0.0.0/24" },
+            "postchange_data": { "prefix": "10.0.0.0/23" }
+        }"#);
+        assert_eq!(prefix.networks(), vec!["10.0.0.0/24".parse::<IpNet>().unwrap(), "10.0.0.0/23".parse().unwrap()]);
+        assert!(!prefix.needs_full_sync());
+
+        let address = change(r#"{
+            "id": 2, "changed_object_type": "ipam.ipaddress", "changed_object_id": 11,
+            "prechange_data": null,
+            "postchange_data": { "address": "2001:db8::10/64" }
+        }"#);
+        assert_eq!(address.networks(), vec!["2001:db8::10/128".parse::<IpNet>().unwrap()]);
+
+        let range = change(r#"{
+            "id": 3, "changed_object_type": "ipam.iprange", "changed_object_id": 12,
+            "prechange_data": { "start_address": "10.0.0.100/24", "end_address": "10.0.0.200/24" },
+            "postchange_data": null
+        }"#);
+        assert_eq!(range.networks(), vec!["10.0.0.100/32".parse::<IpNet>().unwrap(), "10.0.0.200/32".parse().unwrap()]);
+        assert_eq!(range.interface(), None);
+    }
+
+    #[test]
+    fn it_returns_changed_interfaces() {
+        let interface = change(r#"{
+            "id": 4, "changed_object_type": "dcim.interface", "changed_object_id": 20,
+            "prechange_data": {}, "postchange_data": {}
+        }"#);
+        assert_eq!(interface.interface(), Some(("interface_id", 20)));
+        assert!(interface.networks().is_empty());
+
+        let mac = change(r#"{
+            "id": 5, "changed_object_type": "dcim.macaddress", "changed_object_id": 30,
+            "prechange_data": null,
+            "postchange_data": { "assigned_object_type": "virtualization.vminterface", "assigned_object_id": 21 }
+        }"#);
+        assert_eq!(mac.interface(), Some(("vminterface_id", 21)));
+    }
+
+    #[test]
+    fn it_returns_renamed_devices_and_vms() {
+        let device = change(r#"{
+            "id": 7, "changed_object_type": "dcim.device", "changed_object_id": 50,
+            "prechange_data": { "name": "host01" }, "postchange_data": { "name": "host02" }
+        }"#);
+        assert_eq!(device.interface(), Some(("device_id", 50)));
+        assert!(!device.needs_full_sync());
+
+        let vm = change(r#"{
+            "id": 8, "changed_object_type": "virtualization.virtualmachine", "changed_object_id": 51,
+            "prechange_data": { "name": "vm01" }, "postchange_data": { "name": "vm02" }
+        }"#);
+        assert_eq!(vm.interface(), Some(("virtual_machine_id", 51)));
+
+        let status = change(r#"{
+            "id": 9, "changed_object_type": "dcim.device", "changed_object_id": 50,
+            "prechange_data": { "name": "host02", "status": "active" },
+            "postchange_data": { "name": "host02", "status": "offline" }
+        }"#);
+        assert_eq!(status.interface(), None);
+
+        let created = change(r#"{
+            "id": 10, "changed_object_type": "dcim.device", "changed_object_id": 52,
+            "prechange_data": null, "postchange_data": { "name": "host03" }
+        }"#);
+        assert_eq!(created.interface(), None);
+    }
+
+    #[test]
+    fn it_needs_a_full_sync_for_shared_objects() {
+        let vlan = change(r#"{
+            "id": 6, "changed_object_type": "ipam.vlan", "changed_object_id": 40,
+            "prechange_data": {}, "postchange_data": {}
+        }"#);
+        assert!(vlan.needs_full_sync());
+    }
+}
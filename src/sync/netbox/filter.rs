@@ -0,0 +1,510 @@
+use std::fmt::{self, Display};
+
+use regex::Regex;
+
+use super::address::IpAddress;
+use super::model::{NestedTag, NetAddr};
+use super::prefix::Prefix;
+use super::range::IpRange;
+
+/// Value of an object field, tags are a list.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FieldValue {
+    One(String),
+    Many(Vec<String>),
+}
+
+/// Objects a filter expression can be evaluated on.
+pub trait FilterObject {
+    fn filter_value(&self, field: &str) -> Option<FieldValue>;
+    fn filter_label(&self) -> String;
+}
+
+fn tag_values(tags: &[NestedTag]) -> FieldValue {
+    FieldValue::Many(tags.iter().flat_map(|t| [t.name.clone(), t.slug.clone()]).collect())
+}
+
+impl<N: NetAddr + Copy + Display> FilterObject for Prefix<N> {
+    fn filter_value(&self, field: &str) -> Option<FieldValue> {
+        match field {
+            "tags" => Some(tag_values(self.tags())),
+            field => self.template_value(field).map(FieldValue::One),
+        }
+    }
+
+    fn filter_label(&self) -> String {
+        self.prefix().to_string()
+    }
+}
+
+impl<A: NetAddr> FilterObject for IpAddress<A> where A::Addr: Display {
+    fn filter_value(&self, field: &str) -> Option<FieldValue> {
+        match field {
+            "tags" => Some(tag_values(self.tags())),
+            field => self.template_value(field).map(FieldValue::One),
+        }
+    }
+
+    fn filter_label(&self) -> String {
+        self.address().to_string()
+    }
+}
+
+impl FilterObject for IpRange {
+    fn filter_value(&self, field: &str) -> Option<FieldValue> {
+        match field {
+            "tags" => Some(tag_values(self.tags())),
+            field => self.template_value(field).map(FieldValue::One),
+        }
+    }
+
+    fn filter_label(&self) -> String {
+        format!("{}-{}", self.start_address(), self.end_address())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Contains,
+    StartsWith,
+    EndsWith,
+    Matches,
+    In,
+}
+
+impl Display for Op {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Op::Eq => "==",
+            Op::Ne => "!=",
+            Op::Lt => "<",
+            Op::Le => "<=",
+            Op::Gt => ">",
+            Op::Ge => ">=",
+            Op::Contains => "contains",
+            Op::StartsWith => "startswith",
+            Op::EndsWith => "endswith",
+            Op::Matches => "matches",
+            Op::In => "in",
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Operand {
+    Value(String),
+    List(Vec<String>),
+    Regex(Regex),
+}
+
+impl Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Operand::Value(value) => write!(f, "{:?}", value),
+            Operand::List(values) => write!(f, "[{}]", values.iter()
+                .map(|v| format!("{:?}", v))
+                .collect::<Vec<String>>()
+                .join(", ")),
+            Operand::Regex(regex) => write!(f, "{:?}", regex.as_str()),
+        }
+    }
+}
+
+/// Filter expression evaluated on fetched Netbox objects, e.g.
+/// `vlan.vid < 100 and not description startswith "TEMP"`.
+#[derive(Debug, Clone)]
+pub enum Expr {
+    And(Vec<Expr>),
+    Or(Vec<Expr>),
+    Not(Box<Expr>),
+    Compare { field: String, op: Op, operand: Operand },
+}
+
+impl Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let join = |f: &mut fmt::Formatter<'_>, exprs: &[Expr], sep: &str| -> fmt::Result {
+            for (idx, expr) in exprs.iter().enumerate() {
+                if idx > 0 { write!(f, " {} ", sep)?; }
+                match expr {
+                    Expr::And(_) | Expr::Or(_) => write!(f, "({})", expr)?,
+                    expr => write!(f, "{}", expr)?,
+                }
+            }
+            Ok(())
+        };
+
+        match self {
+            Expr::And(exprs) => join(f, exprs, "and"),
+            Expr::Or(exprs) => join(f, exprs, "or"),
+            Expr::Not(expr) => match expr.as_ref() {
+                Expr::Compare { .. } => write!(f, "not {}", expr),
+                expr => write!(f, "not ({})", expr),
+            },
+            Expr::Compare { field, op, operand } => write!(f, "{} {} {}", field, op, operand),
+        }
+    }
+}
+
+impl Expr {
+    pub fn parse(input: &str) -> Result<Self, String> {
+        let tokens = tokenize(input)?;
+        let mut parser = Parser { tokens: &tokens, pos: 0 };
+        let expr = parser.or()?;
+        match parser.next() {
+            None => Ok(expr),
+            Some(token) => Err(format!("unexpected {}", token)),
+        }
+    }
+
+    pub fn matches<T: FilterObject>(&self, object: &T) -> bool {
+        match self {
+            Expr::And(exprs) => exprs.iter().all(|e| e.matches(object)),
+            Expr::Or(exprs) => exprs.iter().any(|e| e.matches(object)),
+            Expr::Not(expr) => !expr.matches(object),
+            Expr::Compare { field, op, operand } => compare(object.filter_value(field), *op, operand),
+        }
+    }
+
+    /// Fields compared by the expression.
+    pub fn fields(&self) -> Vec<&str> {
+        match self {
+            Expr::And(exprs) | Expr::Or(exprs) => exprs.iter().flat_map(Expr::fields).collect(),
+            Expr::Not(expr) => expr.fields(),
+            Expr::Compare { field, .. } => vec![field.as_str()],
+        }
+    }
+
+    /// The clause excluding the object, `None` if the object matches.
+    pub fn explain<T: FilterObject>(&self, object: &T) -> Option<&Expr> {
+        match self {
+            Expr::And(exprs) => exprs.iter().find_map(|e| e.explain(object)),
+            expr if expr.matches(object) => None,
+            expr => Some(expr),
+        }
+    }
+}
+
+fn compare(value: Option<FieldValue>, op: Op, operand: &Operand) -> bool {
+    match value {
+        None => op == Op::Ne,
+        Some(FieldValue::One(value)) => compare_one(&value, op, operand),
+        Some(FieldValue::Many(values)) => match op {
+            Op::Ne => values.iter().all(|v| !compare_one(v, Op::Eq, operand)),
+            Op::Contains => values.iter().any(|v| compare_one(v, Op::Eq, operand)),
+            op => values.iter().any(|v| compare_one(v, op, operand)),
+        },
+    }
+}
+
+fn compare_one(value: &str, op: Op, operand: &Operand) -> bool {
+    match (op, operand) {
+        (Op::Matches, Operand::Regex(regex)) => regex.is_match(value),
+        (Op::In, Operand::List(values)) => values.iter().any(|v| v == value),
+        (Op::Eq, Operand::Value(other)) => value == other,
+        (Op::Ne, Operand::Value(other)) => value != other,
+        (Op::Contains, Operand::Value(other)) => value.contains(other.as_str()),
+        (Op::StartsWith, Operand::Value(other)) => value.starts_with(other.as_str()),
+        (Op::EndsWith, Operand::Value(other)) => value.ends_with(other.as_str()),
+        (op, Operand::Value(other)) => {
+            let ordering = match (value.parse::<f64>(), other.parse::<f64>()) {
+                (Ok(a), Ok(b)) => a.partial_cmp(&b),
+                _ => Some(value.cmp(other)),
+            };
+            match (op, ordering) {
+                (Op::Lt, Some(o)) => o.is_lt(),
+                (Op::Le, Some(o)) => o.is_le(),
+                (Op::Gt, Some(o)) => o.is_gt(),
+                (Op::Ge, Some(o)) => o.is_ge(),
+                _ => false,
+            }
+        },
+        _ => false,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Word(String),
+    Str(String),
+    Op(&'static str),
+}
+
+impl Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Word(word) => write!(f, "{}", word),
+            Token::Str(s) => write!(f, "{:?}", s),
+            Token::Op(op) => write!(f, "{}", op),
+        }
+    }
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = input.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => { chars.next(); },
+            '"' | '\'' => {
+                chars.next();
+                let mut s = String::new();
+                loop {
+                    match chars.next() {
+                        Some('\\') => s.extend(chars.next()),
+                        Some(q) if q == c => break,
+                        Some(ch) => s.push(ch),
+                        None => return Err(String::from("unterminated string")),
+                    }
+                }
+                tokens.push(Token::Str(s));
+            },
+            '(' | ')' | '[' | ']' | ',' => {
+                chars.next();
+                tokens.push(Token::Op(match c { '(' => "(", ')' => ")", '[' => "[", ']' => "]", _ => "," }));
+            },
+            '=' | '!' | '<' | '>' => {
+                chars.next();
+                let eq = chars.next_if_eq(&'=').is_some();
+                tokens.push(Token::Op(match (c, eq) {
+                    ('=', true) => "==",
+                    ('!', true) => "!=",
+                    ('<', true) => "<=",
+                    ('<', false) => "<",
+                    ('>', true) => ">=",
+                    ('>', false) => ">",
+                    (c, _) => return Err(format!("unexpected {}", c)),
+                }));
+            },
+            c if c.is_alphanumeric() || "_.-:/".contains(c) => {
+                let mut word = String::new();
+                while let Some(ch) = chars.next_if(|ch| ch.is_alphanumeric() || "_.-:/".contains(*ch)) {
+                    word.push(ch);
+                }
+                tokens.push(Token::Word(word));
+            },
+            c => return Err(format!("unexpected {}", c)),
+        }
+    }
+
+    Ok(tokens)
+}
+
+struct Parser<'a> {
+    tokens: &'a [Token],
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn next(&mut self) -> Option<&Token> {
+        let token = self.tokens.get(self.pos);
+        self.pos += 1;
+        token
+    }
+
+    fn peek_word(&self, word: &str) -> bool {
+        matches!(self.tokens.get(self.pos), Some(Token::Word(w)) if w == word)
+    }
+
+    fn expect(&mut self, op: &str) -> Result<(), String> {
+        match self.next() {
+            Some(Token::Op(o)) if *o == op => Ok(()),
+            Some(token) => Err(format!("expected {} instead of {}", op, token)),
+            None => Err(format!("expected {} at the end", op)),
+        }
+    }
+
+    fn or(&mut self) -> Result<Expr, String> {
+        let mut exprs = vec![self.and()?];
+        while self.peek_word("or") {
+            self.pos += 1;
+            exprs.push(self.and()?);
+        }
+        Ok(if exprs.len() == 1 { exprs.remove(0) } else { Expr::Or(exprs) })
+    }
+
+    fn and(&mut self) -> Result<Expr, String> {
+        let mut exprs = vec![self.not()?];
+        while self.peek_word("and") {
+            self.pos += 1;
+            exprs.push(self.not()?);
+        }
+        Ok(if exprs.len() == 1 { exprs.remove(0) } else { Expr::And(exprs) })
+    }
+
+    fn not(&mut self) -> Result<Expr, String> {
+        if self.peek_word("not") {
+            self.pos += 1;
+            return Ok(Expr::Not(Box::new(self.not()?)));
+        }
+        if self.tokens.get(self.pos) == Some(&Token::Op("(")) {
+            self.pos += 1;
+            let expr = self.or()?;
+            self.expect(")")?;
+            return Ok(expr);
+        }
+        self.compare()
+    }
+
+    fn compare(&mut self) -> Result<Expr, String> {
+        let field = match self.next() {
+            Some(Token::Word(field)) => field.clone(),
+            Some(token) => return Err(format!("expected a field instead of {}", token)),
+            None => return Err(String::from("expected a field at the end")),
+        };
+
+        let op = match self.next() {
+            Some(Token::Op("==")) => Op::Eq,
+            Some(Token::Op("!=")) => Op::Ne,
+            Some(Token::Op("<")) => Op::Lt,
+            Some(Token::Op("<=")) => Op::Le,
+            Some(Token::Op(">")) => Op::Gt,
+            Some(Token::Op(">=")) => Op::Ge,
+            Some(Token::Word(w)) if w == "contains" => Op::Contains,
+            Some(Token::Word(w)) if w == "startswith" => Op::StartsWith,
+            Some(Token::Word(w)) if w == "endswith" => Op::EndsWith,
+            Some(Token::Word(w)) if w == "matches" => Op::Matches,
+            Some(Token::Word(w)) if w == "in" => Op::In,
+            Some(token) => return Err(format!("expected an operator after {} instead of {}", field, token)),
+            None => return Err(format!("expected an operator after {}", field)),
+        };
+
+        let operand = match op {
+            Op::In => {
+                self.expect("[")?;
+                let mut values = Vec::new();
+                while self.tokens.get(self.pos) != Some(&Token::Op("]")) {
+                    if !values.is_empty() { self.expect(",")?; }
+                    values.push(self.value()?);
+                }
+                self.pos += 1;
+                Operand::List(values)
+            },
+            Op::Matches => {
+                let pattern = self.value()?;
+                Operand::Regex(Regex::new(&pattern).map_err(|e| format!("invalid regex {:?}: {}", pattern, e))?)
+            },
+            _ => Operand::Value(self.value()?),
+        };
+
+        Ok(Expr::Compare { field, op, operand })
+    }
+
+    fn value(&mut self) -> Result<String, String> {
+        match self.next() {
+            Some(Token::Str(s) | Token::Word(s)) => Ok(s.clone()),
+            Some(token) => Err(format!("expected a value instead of {}", token)),
+            None => Err(String::from("expected a value at the end")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn prefix() -> Prefix {
+        serde_json::from_str::<Prefix>(r#"{
+            "prefix": "10.112.130.0/24",
+            "description": "TEMP lab",
+            "site": { "name": "Zurich", "slug": "zrh" },
+            "vlan": { "vid": 130, "name": "Clients" },
+            "tags": [{ "name": "DHCP", "slug": "dhcp" }, { "name": "Prod", "slug": "prod" }],
+            "custom_fields": { "building": "B1" }
+        }"#).unwrap()
+    }
+
+    fn matches(expr: &str) -> bool {
+        Expr::parse(expr).unwrap().matches(&prefix())
+    }
+
+    #[test]
+    fn it_evaluates_comparisons() {
+        assert!(matches("vlan.vid > 100"));
+        assert!(!matches("vlan.vid < 100"));
+        assert!(matches("vlan.vid >= 130 and vlan.vid <= 130"));
+        assert!(matches("site.slug == zrh"));
+        assert!(matches("site != 'Bern'"));
+        assert!(matches(r#"description startswith "TEMP""#));
+        assert!(matches("description endswith lab and description contains 'P l'"));
+        assert!(matches(r#"cf.building matches "^B[0-9]+$""#));
+        assert!(matches("site.slug in [zrh, ber]"));
+        assert!(!matches("tenant == acme"));
+        assert!(matches("tenant != acme"));
+    }
+
+    #[test]
+    fn it_evaluates_tags() {
+        assert!(matches("tags contains prod"));
+        assert!(matches("tags contains DHCP"));
+        assert!(!matches("tags contains pro"));
+        assert!(matches("tags != lab"));
+        assert!(!matches("tags != prod"));
+        assert!(matches("tags in [lab, prod]"));
+    }
+
+    #[test]
+    fn it_evaluates_boolean_operators() {
+        assert!(matches("vlan.vid < 100 or site.slug == zrh"));
+        assert!(!matches("not site.slug == zrh"));
+        assert!(matches("not (vlan.vid < 100 or site.slug == ber) and tags contains dhcp"));
+    }
+
+    #[test]
+    fn it_explains_the_excluding_clause() {
+        let expr = Expr::parse(r#"vlan.vid > 100 and not description startswith "TEMP" and site.slug == zrh"#).unwrap();
+        assert_eq!(expr.explain(&prefix()).unwrap().to_string(), r#"not description startswith "TEMP""#);
+
+        let expr = Expr::parse("vlan.vid > 100 and (site.slug == ber or tenant == acme)").unwrap();
+        assert_eq!(expr.explain(&prefix()).unwrap().to_string(), r#"site.slug == "ber" or tenant == "acme""#);
+
+        assert!(Expr::parse("vlan.vid > 100").unwrap().explain(&prefix()).is_none());
+    }
+
+    #[test]
+    fn it_lists_the_compared_fields() {
+        let expr = Expr::parse("vlan.vid > 100 and not (site == a or tags contains prod)").unwrap();
+        assert_eq!(expr.fields(), vec!["vlan.vid", "site", "tags"]);
+    }
+
+    #[test]
+    fn it_rejects_invalid_expressions() {
+        assert_eq!(Expr::parse("vlan.vid <").unwrap_err(), "expected a value at the end");
+        assert_eq!(Expr::parse("vlan.vid 100").unwrap_err(), "expected an operator after vlan.vid instead of 100");
+        assert_eq!(Expr::parse("(site == a").unwrap_err(), "expected ) at the end");
+        assert_eq!(Expr::parse("site == a b").unwrap_err(), "unexpected b");
+        assert_eq!(Expr::parse("site == 'a").unwrap_err(), "unterminated string");
+        assert!(Expr::parse("site matches '('").unwrap_err().starts_with("invalid regex"));
+    }
+
+    #[test]
+    fn it_filters_ranges_and_addresses() {
+        let range = serde_json::from_str::<IpRange>(r#"{
+            "start_address": "10.0.0.100/24",
+            "end_address": "10.0.0.200/24",
+            "description": "pool",
+            "role": { "name": "DHCP Pool", "slug": "dhcp-pool" },
+            "custom_fields": { "building": "B1" }
+        }"#).unwrap();
+        assert!(Expr::parse("description == pool and start_address == 10.0.0.100").unwrap().matches(&range));
+        assert!(Expr::parse("role.slug == dhcp-pool and cf.building == B1").unwrap().matches(&range));
+        assert_eq!(range.filter_label(), "10.0.0.100-10.0.0.200");
+
+        let ip = serde_json::from_str::<IpAddress>(r#"{
+            "url": "https://netbox.example.com/api/ipam/ip-addresses/1/",
+            "address": "10.0.0.10/24",
+            "dns_name": "host.example.com",
+            "description": "",
+            "custom_fields": {},
+            "assigned_object": null
+        }"#).unwrap();
+        assert!(Expr::parse("dns_name endswith .example.com").unwrap().matches(&ip));
+        assert_eq!(ip.filter_label(), "10.0.0.10");
+    }
+}
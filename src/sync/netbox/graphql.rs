@@ -0,0 +1,334 @@
+use std::collections::{BTreeMap, HashMap};
+
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::{Map, Value};
+
+use super::address::{IpAddress, IpAddressV6};
+use super::config::{FilterValue, NetboxFilter, SyncNetboxConfig};
+use super::prefix::{Prefix, PrefixV6};
+use super::range::IpRange;
+
+const PREFIX_FIELDS: &str = "id prefix description scope { ... on SiteType { name slug } } vlan { vid name } \
+    tenant { name slug } role { name slug } tags { name slug } custom_fields";
+const RANGE_FIELDS: &str = "id start_address end_address description tenant { name slug } role { name slug } \
+    vrf { name } tags { name slug } custom_fields";
+const ADDRESS_FIELDS: &str = "id address dns_name description tenant { name slug } role vrf { name } \
+    tags { name slug } custom_fields assigned_object { __typename \
+    ... on InterfaceType { id name primary_mac_address { mac_address } device { name } } \
+    ... on VMInterfaceType { id name primary_mac_address { mac_address } virtual_machine { name } } }";
+
+/// Prefix custom fields referencing IP addresses, GraphQL only returns their ids.
+const ADDRESS_CUSTOM_FIELDS: [&str; 2] = ["dhcp_routers", "dhcp_dns_servers"];
+
+#[derive(Debug, Deserialize)]
+pub struct Response {
+    data: Option<Map<String, Value>>,
+    #[serde(default)]
+    errors: Vec<ResponseError>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ResponseError {
+    message: String,
+}
+
+impl Response {
+    pub fn into_data(self) -> Result<Map<String, Value>, String> {
+        if !self.errors.is_empty() {
+            return Err(self.errors.iter()
+                .map(|e| e.message.as_str())
+                .collect::<Vec<&str>>()
+                .join("; "));
+        }
+        self.data.ok_or_else(|| String::from("GraphQL response without data"))
+    }
+}
+
+fn value_literal(value: &str) -> String {
+    match value {
+        "true" | "false" => value.to_string(),
+        v if v.parse::<i64>().is_ok() => value.to_string(),
+        v => Value::from(v).to_string(),
+    }
+}
+
+/// Renders a REST filter as GraphQL input object, numbers and booleans are passed unquoted.
+pub fn filter_literal(filter: &NetboxFilter) -> String {
+    let fields: Vec<String> = filter.iter()
+        .collect::<BTreeMap<&String, &FilterValue>>()
+        .into_iter()
+        .map(|(key, value)| match value {
+            FilterValue::One(value) => format!("{}: {}", key, value_literal(value)),
+            FilterValue::Many(values) => format!("{}: [{}]", key, values.iter()
+                .map(|v| value_literal(v))
+                .collect::<Vec<String>>()
+                .join(", ")),
+        })
+        .collect();
+
+    format!("{{{}}}", fields.join(", "))
+}
+
+/// One query fetching everything the sync reads from Netbox.
+pub fn sync_query(config: &SyncNetboxConfig) -> String {
+    let lists = [
+        ("prefixes", "prefix_list", config.prefix_filter().clone(), PREFIX_FIELDS),
+        ("prefixes6", "prefix_list", config.prefix6_filter().clone(), PREFIX_FIELDS),
+        ("ranges", "ip_range_list", config.range_filter().clone(), RANGE_FIELDS),
+        ("reservations", "ip_address_list", config.reservation_filter(None), ADDRESS_FIELDS),
+        ("routers", "ip_address_list", config.router_filter(None), ADDRESS_FIELDS),
+    ];
+
+    let lists: Vec<String> = lists.iter()
+        .map(|(alias, list, filter, fields)| {
+            let filter = config.graphql_filter(alias).cloned()
+                .unwrap_or_else(|| filter_literal(filter));
+            format!("{}: {}(filters: {}) {{ {} }}", alias, list, filter, fields)
+        })
+        .collect();
+
+    format!("query {{ {} }}", lists.join(" "))
+}
+
+/// Ids of the IP addresses referenced by prefix custom fields.
+pub fn address_ids(data: &Map<String, Value>) -> Vec<u64> {
+    let mut ids: Vec<u64> = ["prefixes", "prefixes6"].iter()
+        .filter_map(|alias| data.get(*alias).and_then(Value::as_array))
+        .flatten()
+        .filter_map(|prefix| prefix.get("custom_fields"))
+        .flat_map(|cf| ADDRESS_CUSTOM_FIELDS.iter().filter_map(|field| cf.get(*field)))
+        .flat_map(|value| match value {
+            Value::Array(values) => values.iter().filter_map(Value::as_u64).collect(),
+            value => value.as_u64().into_iter().collect::<Vec<u64>>(),
+        })
+        .collect();
+    ids.sort_unstable();
+    ids.dedup();
+    ids
+}
+
+pub fn address_query(ids: &[u64]) -> String {
+    let ids: Vec<String> = ids.iter().map(u64::to_string).collect();
+    format!("query {{ addresses: ip_address_list(filters: {{id: [{}]}}) {{ id address }} }}", ids.join(", "))
+}
+
+/// Replaces the address ids in prefix custom fields by the addresses, as returned by the REST API.
+pub fn resolve_addresses(data: &mut Map<String, Value>, addresses: &Map<String, Value>) {
+    let addresses: HashMap<u64, Value> = addresses.get("addresses")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|a| Some((id(a)?.parse().ok()?, a.get("address")?.clone())))
+        .collect();
+    let resolve = |id: &Value| id.as_u64()
+        .and_then(|id| addresses.get(&id))
+        .map(|address| serde_json::json!({ "address": address }));
+
+    for alias in ["prefixes", "prefixes6"] {
+        let Some(prefixes) = data.get_mut(alias).and_then(Value::as_array_mut) else {
+            continue;
+        };
+        for cf in prefixes.iter_mut().filter_map(|p| p.get_mut("custom_fields").and_then(Value::as_object_mut)) {
+            for field in ADDRESS_CUSTOM_FIELDS {
+                let value = match cf.get(field) {
+                    Some(Value::Array(ids)) => Value::Array(ids.iter().filter_map(resolve).collect()),
+                    Some(id @ Value::Number(_)) => Value::Array(resolve(id).into_iter().collect()),
+                    _ => continue,
+                };
+                cf.insert(String::from(field), value);
+            }
+        }
+    }
+}
+
+fn id(object: &Value) -> Option<String> {
+    match object.get("id")? {
+        Value::String(id) => Some(id.clone()),
+        Value::Number(id) => Some(id.to_string()),
+        _ => None,
+    }
+}
+
+/// Adds the REST `url` of an object and flattens the interface MAC address.
+fn rest_shape(mut object: Value, apiurl: &str, endpoint: &str) -> Value {
+    if let Some(id) = id(&object) {
+        object["url"] = Value::from(format!("{}{}{}/", apiurl, endpoint, id));
+    }
+
+    if let Some(assigned) = object.get_mut("assigned_object").filter(|a| a.is_object()) {
+        let endpoint = match assigned.get("__typename").and_then(Value::as_str) {
+            Some("VMInterfaceType") => "virtualization/interfaces/",
+            _ => "dcim/interfaces/",
+        };
+        if let Some(id) = id(assigned) {
+            assigned["url"] = Value::from(format!("{}{}{}/", apiurl, endpoint, id));
+        }
+        let mac = assigned.pointer("/primary_mac_address/mac_address").cloned();
+        assigned["mac_address"] = mac.unwrap_or(Value::Null);
+    }
+
+    object
+}
+
+fn is_ipv6(object: &Value) -> bool {
+    object.get("address").and_then(Value::as_str).is_some_and(|a| a.contains(':'))
+}
+
+fn from_values<T: DeserializeOwned>(values: Vec<Value>) -> Result<Vec<T>, serde_json::Error> {
+    values.into_iter().map(serde_json::from_value).collect()
+}
+
+/// Everything the sync reads from Netbox, fetched with [`sync_query`].
+#[derive(Debug, Default)]
+pub struct GraphqlData {
+    pub prefixes: Vec<Prefix>,
+    pub prefixes6: Vec<PrefixV6>,
+    pub ranges: Vec<IpRange>,
+    pub reservations: Vec<IpAddress>,
+    pub reservations6: Vec<IpAddressV6>,
+    pub routers: Vec<IpAddress>,
+}
+
+impl GraphqlData {
+    pub fn from_data(mut data: Map<String, Value>, apiurl: &str) -> Result<Self, serde_json::Error> {
+        let mut take = |alias: &str, endpoint: &str| -> Vec<Value> {
+            match data.remove(alias) {
+                Some(Value::Array(objects)) => objects.into_iter()
+                    .map(|o| rest_shape(o, apiurl, endpoint))
+                    .collect(),
+                _ => vec![],
+            }
+        };
+
+        let prefixes = take("prefixes", "ipam/prefixes/");
+        let prefixes6 = take("prefixes6", "ipam/prefixes/");
+        let ranges = take("ranges", "ipam/ip-ranges/");
+        let (reservations6, reservations): (Vec<Value>, Vec<Value>) = take("reservations", "ipam/ip-addresses/")
+            .into_iter()
+            .partition(is_ipv6);
+        let routers: Vec<Value> = take("routers", "ipam/ip-addresses/")
+            .into_iter()
+            .filter(|r| !is_ipv6(r))
+            .collect();
+
+        Ok(Self {
+            prefixes: from_values(prefixes)?,
+            prefixes6: from_values(prefixes6)?,
+            ranges: from_values(ranges)?,
+            reservations: from_values(reservations)?,
+            reservations6: from_values(reservations6)?,
+            routers: from_values(routers)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use super::*;
+
+    const APIURL: &str = "https://netbox.example.com/api/";
+
+    fn data(json: &str) -> Map<String, Value> {
+        serde_json::from_str::<Response>(json).unwrap().into_data().unwrap()
+    }
+
+    #[test]
+    fn it_renders_filters() {
+        let filter = HashMap::from([
+            (String::from("tag"), FilterValue::Many(vec![String::from("dhcp"), String::from("prod")])),
+            (String::from("family"), FilterValue::from("4")),
+            (String::from("mark_utilized"), FilterValue::from("false")),
+        ]);
+        assert_eq!(filter_literal(&filter), r#"{family: 4, mark_utilized: false, tag: ["dhcp", "prod"]}"#);
+        assert_eq!(filter_literal(&HashMap::new()), "{}");
+    }
+
+    #[test]
+    fn it_builds_the_sync_query() {
+        let query = sync_query(&SyncNetboxConfig::default());
+        assert!(query.starts_with(r#"query { prefixes: prefix_list(filters: {family: 4, status: "active", tag: "dhcp"}) { id prefix "#));
+        assert!(query.contains(r#"ranges: ip_range_list(filters: {family: 4, role: "dhcp-pool", status: "active"})"#));
+        assert!(query.contains(r#"routers: ip_address_list(filters: {status: "active", tag: "router"})"#));
+    }
+
+    #[test]
+    fn it_returns_errors() {
+        let response = serde_json::from_str::<Response>(r#"{
+            "data": null,
+            "errors": [{ "message": "Unknown argument" }, { "message": "Bad filter" }]
+        }"#).unwrap();
+        assert_eq!(response.into_data().unwrap_err(), "Unknown argument; Bad filter");
+    }
+
+    #[test]
+    fn it_maps_graphql_to_models() {
+        let mut data = data(r#"{ "data": {
+            "prefixes": [{
+                "id": "1", "prefix": "10.0.0.0/24", "description": "office",
+                "scope": { "name": "Berlin", "slug": "ber" }, "vlan": { "vid": 10, "name": "office" },
+                "tenant": null, "role": null,
+                "custom_fields": { "dhcp_lease_duration": 3600, "dhcp_routers": [7], "dhcp_dns_servers": [8, 9] }
+            }],
+            "prefixes6": [],
+            "ranges": [{ "id": "2", "start_address": "10.0.0.100/24", "end_address": "10.0.0.200/24" }],
+            "reservations": [{
+                "id": "3", "address": "10.0.0.10/24", "dns_name": "host", "description": "",
+                "custom_fields": { "dhcp_reservation_mac": null },
+                "assigned_object": {
+                    "__typename": "InterfaceType", "id": "4", "name": "eth0",
+                    "primary_mac_address": { "mac_address": "AA:BB:CC:DD:EE:FF" }, "device": { "name": "host" }
+                }
+            }, {
+                "id": "5", "address": "2001:db8::10/64", "dns_name": "", "description": "",
+                "custom_fields": { "dhcp_reservation_duid": "00:01" }, "assigned_object": null
+            }],
+            "routers": [{
+                "id": "6", "address": "10.0.0.1/24", "dns_name": "", "description": "",
+                "custom_fields": {}, "assigned_object": {
+                    "__typename": "VMInterfaceType", "id": "11", "name": "eth0",
+                    "primary_mac_address": null, "virtual_machine": { "name": "router" }
+                }
+            }]
+        }}"#);
+
+        assert_eq!(address_ids(&data), vec![7, 8, 9]);
+        assert_eq!(address_query(&[7, 8]), "query { addresses: ip_address_list(filters: {id: [7, 8]}) { id address } }");
+
+        resolve_addresses(&mut data, &self::data(r#"{ "data": { "addresses": [
+            { "id": "7", "address": "10.0.0.1/24" },
+            { "id": "8", "address": "10.0.0.2/24" },
+            { "id": "9", "address": "10.0.0.3/24" }
+        ]}}"#));
+
+        let data = GraphqlData::from_data(data, APIURL).unwrap();
+
+        assert_eq!(data.prefixes.len(), 1);
+        let prefix = &data.prefixes[0];
+        assert_eq!(prefix.url(), "https://netbox.example.com/api/ipam/prefixes/1/");
+        assert_eq!(prefix.lease_duration(), Some(3600));
+        assert_eq!(prefix.routers(), Some(vec![Ipv4Addr::new(10, 0, 0, 1)]));
+        assert_eq!(prefix.dns_servers(), Some(vec![Ipv4Addr::new(10, 0, 0, 2), Ipv4Addr::new(10, 0, 0, 3)]));
+        assert_eq!(prefix.template_value("site.slug"), Some(String::from("ber")));
+        assert_eq!(prefix.template_value("vlan.vid"), Some(String::from("10")));
+
+        assert_eq!(data.ranges[0].start_address(), Ipv4Addr::new(10, 0, 0, 100));
+
+        assert_eq!(data.reservations.len(), 1);
+        let reservation = &data.reservations[0];
+        assert_eq!(reservation.url(), "https://netbox.example.com/api/ipam/ip-addresses/3/");
+        assert_eq!(reservation.assigned_object_url().unwrap(), "https://netbox.example.com/api/dcim/interfaces/4/");
+        assert_eq!(reservation.assigned_object_mac().unwrap(), "AA:BB:CC:DD:EE:FF");
+        assert_eq!(reservation.assigned_parent_name().unwrap(), "host");
+
+        assert_eq!(data.reservations6.len(), 1);
+        assert_eq!(data.reservations6[0].reservation_duid().unwrap(), "00:01");
+
+        let router = &data.routers[0];
+        assert_eq!(router.assigned_object_url().unwrap(), "https://netbox.example.com/api/virtualization/interfaces/11/");
+        assert_eq!(router.assigned_object_mac(), None);
+        assert_eq!(router.assigned_parent_name().unwrap(), "router");
+    }
+}
@@ -0,0 +1,142 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Deserializer, Serialize};
+
+/// Prefix of the comments of journal entries written by the sync, used to find them again.
+pub const JOURNAL_MARKER: &str = "netbox-windhcp-sync:";
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum JournalKind {
+    Info,
+    Success,
+    Warning,
+    Danger,
+}
+
+impl JournalKind {
+    fn is_problem(&self) -> bool {
+        matches!(self, JournalKind::Warning | JournalKind::Danger)
+    }
+}
+
+/// The API returns choices as `{"value": ..., "label": ...}` but accepts the plain value.
+fn choice_value<'de, D: Deserializer<'de>>(deserializer: D) -> Result<JournalKind, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Choice {
+        Plain(JournalKind),
+        Object { value: JournalKind },
+    }
+
+    Ok(match Choice::deserialize(deserializer)? {
+        Choice::Plain(kind) | Choice::Object { value: kind } => kind,
+    })
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+pub struct JournalEntry {
+    pub assigned_object_type: String,
+    pub assigned_object_id: u64,
+    #[serde(deserialize_with = "choice_value")]
+    pub kind: JournalKind,
+    pub comments: String,
+}
+
+impl JournalEntry {
+    /// Entry for the Netbox object behind an API URL, `None` for objects without a journal.
+    pub fn for_url(url: &str, kind: JournalKind, message: &str) -> Option<Self> {
+        let (endpoint, id) = url.trim_end_matches('/').rsplit_once('/')?;
+        let object_type = match endpoint.rsplit('/').next()? {
+            "prefixes" => "ipam.prefix",
+            "ip-addresses" => "ipam.ipaddress",
+            _ => return None,
+        };
+
+        Some(Self {
+            assigned_object_type: object_type.to_string(),
+            assigned_object_id: id.parse().ok()?,
+            kind,
+            comments: format!("{} {}", JOURNAL_MARKER, message),
+        })
+    }
+
+    fn object(&self) -> (&str, u64) {
+        (&self.assigned_object_type, self.assigned_object_id)
+    }
+}
+
+/// Journal entries to create for the problems of a run: a problem is only journaled when it differs
+/// from the last entry of the sync on the object, a solved problem gets an info entry.
+/// `existing` are the entries previously written by the sync in chronological order.
+pub fn journal_updates(problems: Vec<JournalEntry>, solved: Vec<JournalEntry>, existing: &[JournalEntry]) -> Vec<JournalEntry> {
+    let latest: HashMap<(&str, u64), &JournalEntry> = existing.iter()
+        .map(|entry| (entry.object(), entry))
+        .collect();
+
+    let problems = problems.into_iter()
+        .filter(|entry| latest.get(&entry.object()) != Some(&entry));
+    let solved = solved.into_iter()
+        .filter(|entry| latest.get(&entry.object()).is_some_and(|last| last.kind.is_problem()));
+
+    problems.chain(solved).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(url: &str, kind: JournalKind, message: &str) -> JournalEntry {
+        JournalEntry::for_url(url, kind, message).unwrap()
+    }
+
+    #[test]
+    fn it_maps_urls_to_objects() {
+        let prefix = entry("https://netbox/api/ipam/prefixes/12/", JournalKind::Warning, "skipped: no range found");
+        assert_eq!(prefix.assigned_object_type, "ipam.prefix");
+        assert_eq!(prefix.assigned_object_id, 12);
+        assert_eq!(prefix.comments, "netbox-windhcp-sync: skipped: no range found");
+
+        assert_eq!(entry("https://netbox/api/ipam/ip-addresses/7/", JournalKind::Info, "ok").assigned_object_type, "ipam.ipaddress");
+        assert_eq!(JournalEntry::for_url("https://netbox/api/ipam/ip-ranges/3/", JournalKind::Info, "ok"), None);
+        assert_eq!(JournalEntry::for_url("", JournalKind::Info, "ok"), None);
+    }
+
+    #[test]
+    fn it_parses_api_entries() {
+        let entry: JournalEntry = serde_json::from_str(r#"{
+            "id": 1, "assigned_object_type": "ipam.prefix", "assigned_object_id": 12,
+            "kind": { "value": "danger", "label": "Danger" },
+            "comments": "netbox-windhcp-sync: failed: Access denied"
+        }"#).unwrap();
+        assert_eq!(entry.kind, JournalKind::Danger);
+        assert_eq!(serde_json::to_value(&entry).unwrap()["kind"], "danger");
+    }
+
+    #[test]
+    fn it_deduplicates_journal_entries() {
+        let existing = vec![
+            entry("https://netbox/api/ipam/ip-addresses/1/", JournalKind::Warning, "skipped: no MAC address found"),
+            entry("https://netbox/api/ipam/ip-addresses/2/", JournalKind::Warning, "skipped: no MAC address found"),
+            entry("https://netbox/api/ipam/ip-addresses/3/", JournalKind::Warning, "skipped: no MAC address found"),
+            entry("https://netbox/api/ipam/ip-addresses/3/", JournalKind::Info, "resolved"),
+            entry("https://netbox/api/ipam/prefixes/4/", JournalKind::Warning, "skipped: no range found"),
+        ];
+        let problems = vec![
+            entry("https://netbox/api/ipam/ip-addresses/1/", JournalKind::Warning, "skipped: no MAC address found"),
+            entry("https://netbox/api/ipam/prefixes/4/", JournalKind::Danger, "failed: Access denied"),
+            entry("https://netbox/api/ipam/prefixes/5/", JournalKind::Warning, "skipped: no range found"),
+        ];
+        let solved = vec![
+            entry("https://netbox/api/ipam/ip-addresses/2/", JournalKind::Info, "resolved"),
+            entry("https://netbox/api/ipam/ip-addresses/3/", JournalKind::Info, "resolved"),
+            entry("https://netbox/api/ipam/ip-addresses/6/", JournalKind::Info, "resolved"),
+        ];
+
+        assert_eq!(journal_updates(problems, solved, &existing), vec![
+            entry("https://netbox/api/ipam/prefixes/4/", JournalKind::Danger, "failed: Access denied"),
+            entry("https://netbox/api/ipam/prefixes/5/", JournalKind::Warning, "skipped: no range found"),
+            entry("https://netbox/api/ipam/ip-addresses/2/", JournalKind::Info, "resolved"),
+        ]);
+    }
+}
@@ -1,19 +1,32 @@
-pub(super) mod model;
+pub mod model;
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::Path;
+use std::sync::OnceLock;
+use std::thread;
+use std::time::{Duration, Instant};
 
-use chrono::NaiveDate;
-use ipnet::Ipv4Net;
-use log::debug;
+use chrono::{DateTime, NaiveDate, Utc};
+use ipnet::{Ipv4Net, Ipv6Net};
+use log::{debug, info, warn};
 use serde::Deserialize;
-use serde_json::json;
-use ureq::http::{HeaderValue, Request};
-use ureq::tls::{RootCerts, TlsConfig};
-use ureq::{Agent, Body, SendBody};
+use serde_json::{json, Map, Value};
+use ureq::http::{HeaderName, HeaderValue, Request};
+use ureq::tls::{parse_pem, Certificate, ClientCert, PemItem, RootCerts, TlsConfig};
+use ureq::{Agent, Body, Proxy, SendBody};
 use ureq::{middleware::MiddlewareNext, http::response::Response, Error};
 
 pub mod config;
-use self::config::SyncNetboxConfig;
+use self::config::{filter_query, list_endpoint, FilterValue, NetboxFilter, NetboxSource, SyncNetboxConfig};
+pub mod change;
+use self::change::{InterfaceAddress, ObjectChange};
+pub mod filter;
+use self::filter::{Expr, FilterObject};
+pub mod journal;
+use self::journal::{JournalEntry, JOURNAL_MARKER};
+pub mod graphql;
+use self::graphql::GraphqlData;
 use self::model::*;
 pub mod prefix;
 use prefix::*;
@@ -22,32 +35,134 @@ use range::*;
 pub mod address;
 use address::*;
 
+/// Status codes of transient errors which are retried.
+const RETRY_STATUS: [u16; 4] = [429, 502, 503, 504];
+
+fn is_transient(error: &Error) -> bool {
+    matches!(error, Error::Timeout(_) | Error::Io(_) | Error::ConnectionFailed | Error::HostNotFound)
+}
+
+/// Parses a `Retry-After` header, either delay seconds or a HTTP date.
+fn parse_retry_after(value: &str, now: DateTime<Utc>) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+
+    let date = DateTime::parse_from_rfc2822(value).ok()?;
+    Some((date.with_timezone(&Utc) - now).to_std().unwrap_or_default())
+}
+
+fn read_pem(path: &Path) -> Result<Vec<PemItem<'static>>, Box<dyn std::error::Error + Send + std::marker::Sync>> {
+    let pem = fs::read(path).map_err(|e| format!("Unable to read {}: {}", path.display(), e))?;
+    parse_pem(&pem)
+        .collect::<Result<Vec<PemItem>, Error>>()
+        .map_err(|e| format!("Unable to parse {}: {}", path.display(), e).into())
+}
+
+/// Platform verifier or the CA bundle on top of the system certificate store, and the optional
+/// client certificate.
+fn tls_config(config: &SyncNetboxConfig) -> Result<TlsConfig, Box<dyn std::error::Error + Send + std::marker::Sync>> {
+    let mut tls = TlsConfig::builder()
+        .root_certs(RootCerts::PlatformVerifier);
+
+    if let Some(path) = config.ca_bundle() {
+        let mut certs: Vec<Certificate> = read_pem(path)?.into_iter()
+            .filter_map(|item| match item {
+                PemItem::Certificate(cert) => Some(cert),
+                _ => None,
+            })
+            .collect();
+        if certs.is_empty() {
+            return Err(format!("No certificates in CA bundle {}", path.display()).into());
+        }
+
+        let system = rustls_native_certs::load_native_certs();
+        for e in system.errors {
+            warn!("Unable to load the system certificates: {}", e);
+        }
+        certs.extend(system.certs.iter().map(|der| Certificate::from_der(der).to_owned()));
+        tls = tls.root_certs(RootCerts::new_with_certs(&certs));
+    }
+
+    if let (Some(cert_path), Some(key_path)) = (config.client_cert(), config.client_key()) {
+        let chain: Vec<Certificate> = read_pem(cert_path)?.into_iter()
+            .filter_map(|item| match item {
+                PemItem::Certificate(cert) => Some(cert),
+                _ => None,
+            })
+            .collect();
+        let key = read_pem(key_path)?.into_iter()
+            .find_map(|item| match item {
+                PemItem::PrivateKey(key) => Some(key),
+                _ => None,
+            })
+            .ok_or_else(|| format!("No private key in {}", key_path.display()))?;
+        if chain.is_empty() {
+            return Err(format!("No certificates in client certificate {}", cert_path.display()).into());
+        }
+        tls = tls.client_cert(Some(ClientCert::new_with_certs(&chain, key)));
+    }
+
+    Ok(tls.build())
+}
+
+fn query_pairs(query: &[(String, String)]) -> Vec<(&str, &str)> {
+    query.iter().map(|(key, val)| (key.as_str(), val.as_str())).collect()
+}
+
 pub struct NetboxApi {
     config: SyncNetboxConfig,
     client: ureq::Agent,
+    graphql: OnceLock<GraphqlData>,
+    expressions: HashMap<String, Expr>,
 }
 
 impl NetboxApi {
-    pub fn new(config: &SyncNetboxConfig) -> Self {
+    pub fn new(
+        config: &SyncNetboxConfig,
+    ) -> Result<Self, Box<dyn std::error::Error + Send + std::marker::Sync>> {
         let config = config.clone();
+        config.validate_fields(&[])?;
 
-        let auth_value = format!("Token {}", config.token());
+        let mut headers = vec![(HeaderName::from_static("authorization"), HeaderValue::from_str(&config.authorization())?)];
+        for (name, value) in config.headers() {
+            let header = HeaderName::from_bytes(name.as_bytes()).ok()
+                .zip(HeaderValue::from_str(value).ok())
+                .ok_or_else(|| format!("Invalid Netbox header {}: {}", name, value))?;
+            headers.push(header);
+        }
+
+        let mut expressions = HashMap::new();
+        for (list, expression) in config.filter_expression() {
+            if list_endpoint(list).is_none() {
+                return Err(format!("Invalid filter_expression for {}: only prefixes, prefixes6, ranges, reservations and routers can be filtered", list).into());
+            }
+            let expr = Expr::parse(expression)
+                .map_err(|e| format!("Invalid filter_expression for {}: {}", list, e))?;
+            expressions.insert(list.clone(), expr);
+        }
+
+        let proxy = match config.proxy() {
+            Some(proxy) => Some(Proxy::new(proxy).map_err(|e| format!("Invalid Netbox proxy {}: {}", proxy, e))?),
+            None => Proxy::try_from_env(),
+        };
 
         let client = Agent::config_builder()
-            .tls_config(
-                TlsConfig::builder()
-                    .root_certs(RootCerts::PlatformVerifier)
-                    .build()
-            )
+            .tls_config(tls_config(&config)?)
+            .proxy(proxy)
             .user_agent(concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION")))
+            .timeout_global(Some(config.timeout()))
             .middleware(move |mut req: Request<SendBody>, next: MiddlewareNext| -> Result<Response<Body>, Error> {
-                req.headers_mut().append("Authorization", HeaderValue::from_str(auth_value.as_str()).unwrap());
+                for (name, value) in headers.iter() {
+                    req.headers_mut().append(name.clone(), value.clone());
+                }
                 next.handle(req)
             })
             .build()
             .into();
 
-        Self { config, client }
+        Ok(Self { config, client, graphql: OnceLock::new(), expressions })
     }
 
     pub fn version(
@@ -61,32 +176,259 @@ impl NetboxApi {
             netbox_version: String,
         }
 
-        let status: NetboxStatus = self.client.get(&url)
-            .call()?
-            .body_mut()
-            .read_json()?;
+        let status: NetboxStatus = match self.get(&url, &[]) {
+            Ok(mut response) => response.body_mut().read_json()?,
+            Err(Error::StatusCode(status @ (401 | 403))) => return Err(self.token_error(status).into()),
+            Err(e) => return Err(e.into()),
+        };
 
         Ok(status.netbox_version)
     }
 
+    /// Fails with a clear error if Netbox rejects the token, run before any other request.
+    pub fn check_token(
+        &self,
+    ) -> Result<(), Box<dyn std::error::Error + Send + std::marker::Sync>> {
+        let url = format!("{}authentication-check/", self.config.apiurl());
+
+        match self.get(&url, &[]) {
+            Ok(_) => Ok(()),
+            Err(Error::StatusCode(status @ (401 | 403))) => Err(self.token_error(status).into()),
+            Err(Error::StatusCode(404)) => {
+                debug!("Netbox has no authentication check, token not verified");
+                Ok(())
+            },
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn token_error(&self, status: u16) -> String {
+        format!(
+            "Netbox rejected the API token (HTTP {}), check netbox.token and netbox.token_type (sent as {})",
+            status, self.config.token_scheme()
+        )
+    }
+
     pub fn get_prefixes(&self) -> Result<Vec<Prefix>, ureq::Error> {
-        self.get_objects("ipam/prefixes/", self.config.prefix_filter())
+        let prefixes = match self.config.source() {
+            NetboxSource::Graphql => self.graphql()?.prefixes.clone(),
+            NetboxSource::Rest => self.get_objects("ipam/prefixes/", self.config.prefix_filter())?,
+        };
+        Ok(self.filter("prefixes", prefixes))
+    }
+
+    pub fn get_prefixes6(&self) -> Result<Vec<PrefixV6>, ureq::Error> {
+        let prefixes = match self.config.source() {
+            NetboxSource::Graphql => self.graphql()?.prefixes6.clone(),
+            NetboxSource::Rest => self.get_objects("ipam/prefixes/", self.config.prefix6_filter())?,
+        };
+        Ok(self.filter("prefixes6", prefixes))
     }
 
     pub fn get_ranges(&self) -> Result<Vec<IpRange>, ureq::Error> {
-        self.get_objects("ipam/ip-ranges/", self.config.range_filter())
+        let ranges = match self.config.source() {
+            NetboxSource::Graphql => self.graphql()?.ranges.clone(),
+            NetboxSource::Rest => self.get_objects("ipam/ip-ranges/", self.config.range_filter())?,
+        };
+        Ok(self.filter("ranges", ranges))
     }
 
     pub fn get_reservations(&self) -> Result<Vec<IpAddress>, ureq::Error> {
-        self.get_objects("ipam/ip-addresses/", &self.config.reservation_filter(None))
+        let reservations = match self.config.source() {
+            NetboxSource::Graphql => self.graphql()?.reservations.clone(),
+            NetboxSource::Rest => self.get_objects("ipam/ip-addresses/", &self.config.reservation_filter(None))?,
+        };
+        Ok(self.filter("reservations", reservations))
     }
 
     pub fn get_reservations_for_subnet(&self, subnet: &Ipv4Net) -> Result<Vec<IpAddress>, ureq::Error> {
-        self.get_objects("ipam/ip-addresses/", &self.config.reservation_filter(Some(subnet)))
+        let reservations = match self.config.source() {
+            NetboxSource::Graphql => self.graphql()?.reservations.iter()
+                .filter(|ip| subnet.contains(&ip.address()))
+                .cloned()
+                .collect(),
+            NetboxSource::Rest => self.get_objects("ipam/ip-addresses/", &self.config.reservation_filter(Some(subnet)))?,
+        };
+        Ok(self.filter("reservations", reservations))
+    }
+
+    pub fn get_reservations_for_subnet6(&self, subnet: &Ipv6Net) -> Result<Vec<IpAddressV6>, ureq::Error> {
+        let reservations = match self.config.source() {
+            NetboxSource::Graphql => self.graphql()?.reservations6.iter()
+                .filter(|ip| subnet.contains(&ip.address()))
+                .cloned()
+                .collect(),
+            NetboxSource::Rest => self.get_objects("ipam/ip-addresses/", &self.config.reservation6_filter(subnet))?,
+        };
+        Ok(self.filter("reservations", reservations))
     }
 
     pub fn get_router_for_subnet(&self, subnet: &Ipv4Net) -> Result<Vec<IpAddress>, ureq::Error> {
-        self.get_objects("ipam/ip-addresses/", &self.config.router_filter(subnet))
+        let routers = match self.config.source() {
+            NetboxSource::Graphql => self.graphql()?.routers.iter()
+                .filter(|ip| subnet.contains(&ip.address()))
+                .cloned()
+                .collect(),
+            NetboxSource::Rest => self.get_objects("ipam/ip-addresses/", &self.config.router_filter(Some(subnet)))?,
+        };
+        Ok(self.filter("routers", routers))
+    }
+
+    /// Id of the newest change log entry, 0 for an empty change log.
+    pub fn get_last_change_id(&self) -> Result<u64, ureq::Error> {
+        #[derive(Debug, Deserialize)]
+        struct ChangeId {
+            id: u64,
+        }
+
+        let url = format!("{}extras/object-changes/", self.config.apiurl());
+        let page: Pageination<ChangeId> = self.get(&url, &[("ordering", "-id"), ("limit", "1"), ("fields", "id")])?
+            .body_mut()
+            .read_json()?;
+
+        Ok(page.results.first().map(|c| c.id).unwrap_or(0))
+    }
+
+    pub fn get_object_changes(&self, after: u64) -> Result<Vec<ObjectChange>, ureq::Error> {
+        let filter = NetboxFilter::from([
+            (String::from("id__gt"), FilterValue::One(after.to_string())),
+            (String::from("ordering"), FilterValue::from("id")),
+        ]);
+        self.get_objects("extras/object-changes/", &filter)
+    }
+
+    /// Addresses assigned to an interface, device or VM, the filter is `interface_id`, `vminterface_id`,
+    /// `device_id` or `virtual_machine_id`.
+    pub fn get_interface_addresses(&self, filter: &str, id: u64) -> Result<Vec<InterfaceAddress>, ureq::Error> {
+        let filter = NetboxFilter::from([(String::from(filter), FilterValue::One(id.to_string()))]);
+        self.get_objects("ipam/ip-addresses/", &filter)
+    }
+
+    /// Updates fields of objects by URL with one bulk PATCH per endpoint and 100 objects.
+    pub fn patch_objects(&self, updates: &[(String, Map<String, Value>)]) -> Result<(), ureq::Error> {
+        let mut endpoints: BTreeMap<&str, Vec<Value>> = BTreeMap::new();
+        for (url, fields) in updates {
+            let Some((endpoint, id)) = url.trim_end_matches('/').rsplit_once('/') else { continue };
+            let Ok(id) = id.parse::<u64>() else { continue };
+            let mut object = fields.clone();
+            object.insert(String::from("id"), json!(id));
+            endpoints.entry(endpoint).or_default().push(Value::Object(object));
+        }
+
+        for (endpoint, objects) in endpoints {
+            let url = format!("{}/", endpoint);
+            for chunk in objects.chunks(100) {
+                debug!("Update {} objects at {:?}", chunk.len(), url);
+                self.retry(&url, || self.client.patch(&url)
+                    .config()
+                    .http_status_as_error(false)
+                    .build()
+                    .send_json(chunk))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Tags of an object by URL.
+    pub fn get_tags(&self, url: &str) -> Result<Vec<NestedTag>, ureq::Error> {
+        #[derive(Deserialize)]
+        struct Tagged {
+            #[serde(default)]
+            tags: Vec<NestedTag>,
+        }
+        Ok(self.get_object::<Tagged>(url)?.tags)
+    }
+
+    /// Journal entries written by the sync on the objects of these entries, oldest first per object.
+    /// The objects are queried by type with 100 ids per request.
+    pub fn get_journal_entries(&self, objects: &[&JournalEntry]) -> Result<Vec<JournalEntry>, ureq::Error> {
+        let mut ids: BTreeMap<&str, Vec<String>> = BTreeMap::new();
+        for entry in objects {
+            ids.entry(entry.assigned_object_type.as_str()).or_default().push(entry.assigned_object_id.to_string());
+        }
+
+        let mut entries = Vec::new();
+        for (object_type, ids) in ids {
+            for chunk in ids.chunks(100) {
+                let filter = NetboxFilter::from([
+                    (String::from("q"), FilterValue::from(JOURNAL_MARKER)),
+                    (String::from("assigned_object_type"), FilterValue::from(object_type)),
+                    (String::from("assigned_object_id"), FilterValue::Many(chunk.to_vec())),
+                    (String::from("ordering"), FilterValue::from("id")),
+                ]);
+                entries.append(&mut self.get_objects("extras/journal-entries/", &filter)?);
+            }
+        }
+
+        Ok(entries)
+    }
+
+    /// Creates journal entries with one bulk POST per 100 entries, not retried to avoid duplicates
+    /// if Netbox created the entries but the response got lost.
+    pub fn create_journal_entries(&self, entries: &[JournalEntry]) -> Result<(), ureq::Error> {
+        let url = format!("{}extras/journal-entries/", self.config.apiurl());
+        for chunk in entries.chunks(100) {
+            debug!("Create {} journal entries at {:?}", chunk.len(), url);
+            self.client.post(&url).send_json(chunk)?;
+        }
+
+        Ok(())
+    }
+
+    /// Drops the objects not matching the filter expression of the list.
+    fn filter<T: FilterObject>(&self, list: &str, objects: Vec<T>) -> Vec<T> {
+        let Some(expr) = self.expressions.get(list) else {
+            return objects;
+        };
+
+        objects.into_iter()
+            .filter(|object| match expr.explain(object) {
+                None => true,
+                Some(clause) => {
+                    match self.config.explain_filter() {
+                        true => info!("Filter {} excluded {}: {}", list, object.filter_label(), clause),
+                        false => debug!("Filter {} excluded {}: {}", list, object.filter_label(), clause),
+                    }
+                    false
+                },
+            })
+            .collect()
+    }
+
+    /// Fetches prefixes, ranges and addresses with one GraphQL query on first use,
+    /// plus one for the addresses referenced by prefix custom fields.
+    fn graphql(&self) -> Result<&GraphqlData, ureq::Error> {
+        if let Some(data) = self.graphql.get() {
+            return Ok(data);
+        }
+
+        let url = self.config.graphql_url();
+        debug!("Fetch prefixes, ranges and addresses from {:?}", url);
+        let mut data = self.query(&url, &graphql::sync_query(&self.config))?;
+
+        let ids = graphql::address_ids(&data);
+        if !ids.is_empty() {
+            let addresses = self.query(&url, &graphql::address_query(&ids))?;
+            graphql::resolve_addresses(&mut data, &addresses);
+        }
+
+        let data = GraphqlData::from_data(data, self.config.apiurl())?;
+        Ok(self.graphql.get_or_init(|| data))
+    }
+
+    fn query(&self, url: &str, query: &str) -> Result<Map<String, Value>, ureq::Error> {
+        let payload = json!({ "query": query });
+
+        let response: graphql::Response = self.retry(url, || self.client.post(url)
+                .config()
+                .http_status_as_error(false)
+                .build()
+                .send_json(&payload))?
+            .body_mut()
+            .read_json()?;
+
+        response.into_data().map_err(|e| Error::Other(format!("GraphQL query failed: {}", e).into()))
     }
 
     pub fn set_ip_last_active(&self, ip: &IpAddress, date: &NaiveDate) -> Result<(), ureq::Error> {
@@ -103,51 +445,182 @@ impl NetboxApi {
         Ok(())
     }
 
-    fn get_objects<T: for<'a> Deserialize<'a>>(
+    fn get_objects<T: for<'a> Deserialize<'a> + Send>(
         &self,
         path: &str,
-        filter: &HashMap<String, String>,
+        filter: &NetboxFilter,
     ) -> Result<Vec<T>, ureq::Error> {
         let url = format!("{}{}", self.config.apiurl(), path);
 
-        let mut query: Vec<(&str, &str)> = vec![];
-        for (key, val) in filter.iter() {
-            query.push((key.as_str(), val.as_str()));
-        }
+        let mut query = filter_query(filter);
+        query.append(&mut self.config.list_options(path));
 
         debug!("Fetch {} from {:?}", std::any::type_name::<T>(), url);
-        let mut page: Pageination<T> = self.client.get(&url)
-            .query_pairs(query)
-            .call()?
+        let mut page: Pageination<T> = self.get(&url, &query_pairs(&query))?
             .body_mut()
             .read_json()?;
 
         let mut objects: Vec<T> = Vec::with_capacity(page.count);
+        let page_size = page.results.len();
+        objects.append(&mut page.results);
 
-        loop {
-            objects.append(&mut page.results);
-
-            match page.next {
-                Some(ref u) => {
-                    debug!("Fetch next page from {:?}", u);
-                    page = self.client.get(u)
-                        .call()?
-                        .body_mut()
-                        .read_json()?;
-                }
-                None => { break; }
+        if page.next.is_some() && page_size > 0 && self.config.parallel_pages() > 1 {
+            let offsets: Vec<usize> = (page_size..page.count).step_by(page_size).collect();
+            for mut results in self.get_pages(&url, &query, page_size, &offsets)? {
+                objects.append(&mut results);
             }
+            return Ok(objects);
+        }
+
+        while let Some(ref u) = page.next {
+            debug!("Fetch next page from {:?}", u);
+            page = self.get(u, &[])?
+                .body_mut()
+                .read_json()?;
+            objects.append(&mut page.results);
         }
 
         Ok(objects)
     }
 
+    /// Fetches the pages at the offsets, `parallel_pages` at a time.
+    fn get_pages<T: for<'a> Deserialize<'a> + Send>(
+        &self,
+        url: &str,
+        query: &[(String, String)],
+        limit: usize,
+        offsets: &[usize],
+    ) -> Result<Vec<Vec<T>>, ureq::Error> {
+        let query: Vec<(String, String)> = query.iter()
+            .filter(|(key, _)| key != "limit" && key != "offset")
+            .cloned()
+            .collect();
+        let mut pages = Vec::with_capacity(offsets.len());
+
+        for chunk in offsets.chunks(self.config.parallel_pages()) {
+            let results = thread::scope(|s| {
+                let handles: Vec<_> = chunk.iter().map(|offset| {
+                    let mut query = query.clone();
+                    query.push((String::from("limit"), limit.to_string()));
+                    query.push((String::from("offset"), offset.to_string()));
+
+                    s.spawn(move || -> Result<Vec<T>, ureq::Error> {
+                        debug!("Fetch page at offset {} from {:?}", query[query.len() - 1].1, url);
+                        let page: Pageination<T> = self.get(url, &query_pairs(&query))?
+                            .body_mut()
+                            .read_json()?;
+                        Ok(page.results)
+                    })
+                }).collect();
+
+                handles.into_iter()
+                    .map(|h| h.join().unwrap_or_else(|e| std::panic::resume_unwind(e)))
+                    .collect::<Result<Vec<Vec<T>>, ureq::Error>>()
+            })?;
+            pages.extend(results);
+        }
+
+        Ok(pages)
+    }
+
     pub fn get_object<T: for<'a> Deserialize<'a>>(&self, url: &str) -> Result<T, ureq::Error> {
         debug!("Fetch {} from {:?}", std::any::type_name::<T>(), url);
-        let object: T = self.client.get(url)
-            .call()?
+        let object: T = self.get(url, &[])?
             .body_mut()
             .read_json()?;
         Ok(object)
     }
+
+    fn get(&self, url: &str, query: &[(&str, &str)]) -> Result<Response<Body>, Error> {
+        self.retry(url, || self.client.get(url)
+            .query_pairs(query.iter().copied())
+            .config()
+            .http_status_as_error(false)
+            .build()
+            .call())
+    }
+
+    /// Sends the request, retried with backoff on transient errors until the total timeout is reached.
+    fn retry(&self, url: &str, send: impl Fn() -> Result<Response<Body>, Error>) -> Result<Response<Body>, Error> {
+        let retry = self.config.retry();
+        let started = Instant::now();
+        let mut attempt = 0;
+
+        loop {
+            let response = send();
+
+            let (error, retry_after) = match response {
+                Ok(response) if response.status().is_success() => return Ok(response),
+                Ok(response) => {
+                    let status = response.status().as_u16();
+                    if !RETRY_STATUS.contains(&status) {
+                        return Err(Error::StatusCode(status));
+                    }
+                    let retry_after = response.headers().get("Retry-After")
+                        .and_then(|v| v.to_str().ok())
+                        .and_then(|v| parse_retry_after(v, Utc::now()));
+                    (Error::StatusCode(status), retry_after)
+                },
+                Err(e) if is_transient(&e) => (e, None),
+                Err(e) => return Err(e),
+            };
+
+            attempt += 1;
+            let delay = retry_after.unwrap_or_else(|| retry.backoff(attempt));
+            if attempt > retry.attempts() || started.elapsed() + delay > self.config.total_timeout() {
+                return Err(error);
+            }
+
+            warn!("Fetch {} failed: {}, retry {}/{} in {:.1}s", url, error, attempt, retry.attempts(), delay.as_secs_f32());
+            thread::sleep(delay);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_parses_retry_after() {
+        let now = DateTime::parse_from_rfc3339("2015-10-21T07:27:00Z").unwrap().with_timezone(&Utc);
+        assert_eq!(parse_retry_after("120", now), Some(Duration::from_secs(120)));
+        assert_eq!(parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT", now), Some(Duration::from_secs(60)));
+        assert_eq!(parse_retry_after("Wed, 21 Oct 2015 07:26:00 GMT", now), Some(Duration::ZERO));
+        assert_eq!(parse_retry_after("soon", now), None);
+    }
+
+    #[test]
+    fn it_rejects_invalid_tls_files() {
+        let path = std::env::temp_dir().join(format!("netbox-windhcp-ca-{}.pem", std::process::id()));
+        fs::write(&path, "not a certificate").unwrap();
+
+        let config = |key: &str| serde_yaml_ng::from_str::<SyncNetboxConfig>(&format!("{}: {}", key, path.display())).unwrap();
+        let ca_err = tls_config(&config("ca_bundle")).unwrap_err().to_string();
+        let cert_err = tls_config(&config("client_cert")).unwrap_err().to_string();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(ca_err, format!("No certificates in CA bundle {}", path.display()));
+        assert_eq!(cert_err, format!("No private key in {}", path.display()));
+        assert!(tls_config(&SyncNetboxConfig::default()).is_ok());
+    }
+
+    #[test]
+    fn it_rejects_invalid_headers() {
+        let config = serde_yaml_ng::from_str::<SyncNetboxConfig>("headers: { \"X Bad\": value }").unwrap();
+        assert_eq!(NetboxApi::new(&config).err().unwrap().to_string(), "Invalid Netbox header X Bad: value");
+    }
+
+    #[test]
+    fn it_rejects_unknown_filter_expression_lists() {
+        let config = serde_yaml_ng::from_str::<SyncNetboxConfig>("filter_expression: { prefix: \"vlan.vid < 100\" }").unwrap();
+        assert_eq!(NetboxApi::new(&config).err().unwrap().to_string(),
+            "Invalid filter_expression for prefix: only prefixes, prefixes6, ranges, reservations and routers can be filtered");
+    }
+
+    #[test]
+    fn it_retries_transient_errors() {
+        assert!(is_transient(&Error::ConnectionFailed));
+        assert!(!is_transient(&Error::StatusCode(404)));
+    }
 }
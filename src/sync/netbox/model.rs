@@ -1,4 +1,8 @@
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+use ipnet::{Ipv4Net, Ipv6Net};
 use serde::Deserialize;
+use serde_json::Value;
 
 #[derive(Debug, Deserialize)]
 pub struct Pageination<T> {
@@ -6,3 +10,78 @@ pub struct Pageination<T> {
     pub next: Option<String>,
     pub results: Vec<T>,
 }
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct NestedObject {
+    pub name: Option<String>,
+    pub slug: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct NestedVlan {
+    pub vid: u16,
+    pub name: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct NestedTag {
+    pub name: String,
+    pub slug: String,
+}
+
+/// Choice field, REST returns value and label, GraphQL only the value.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum NestedChoice {
+    Full { value: String, label: String },
+    Value(String),
+}
+
+impl NestedChoice {
+    pub fn value(&self) -> &str {
+        match self {
+            NestedChoice::Full { value, .. } | NestedChoice::Value(value) => value,
+        }
+    }
+
+    pub fn label(&self) -> &str {
+        match self {
+            NestedChoice::Full { label, .. } => label,
+            NestedChoice::Value(value) => value,
+        }
+    }
+}
+
+pub fn custom_field_to_string(value: &Value) -> Option<String> {
+    match value {
+        Value::Null => None,
+        Value::String(s) => Some(s.clone()),
+        Value::Array(a) => Some(a.iter()
+            .filter_map(custom_field_to_string)
+            .collect::<Vec<String>>()
+            .join(", ")),
+        Value::Object(o) => o.get("display")
+            .or_else(|| o.get("name"))
+            .and_then(custom_field_to_string),
+        v => Some(v.to_string()),
+    }
+}
+
+pub trait NetAddr {
+    type Addr;
+    fn net_addr(&self) -> Self::Addr;
+}
+
+impl NetAddr for Ipv4Net {
+    type Addr = Ipv4Addr;
+    fn net_addr(&self) -> Ipv4Addr {
+        self.addr()
+    }
+}
+
+impl NetAddr for Ipv6Net {
+    type Addr = Ipv6Addr;
+    fn net_addr(&self) -> Ipv6Addr {
+        self.addr()
+    }
+}
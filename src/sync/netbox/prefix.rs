@@ -1,32 +1,60 @@
+use std::fmt::Display;
 use std::net::Ipv4Addr;
 
-use ipnet::Ipv4Net;
+use ipnet::{Ipv4Net, Ipv6Net};
 use serde::Deserialize;
+use serde_json::{Map, Value};
 
-#[derive(Debug, Deserialize)]
-pub struct Prefix {
-    prefix: Ipv4Net,
+use super::model::{custom_field_to_string, NestedObject, NestedTag, NestedVlan, NetAddr};
+
+pub type PrefixV6 = Prefix<Ipv6Net>;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Prefix<N = Ipv4Net> {
+    #[serde(default)]
+    url: String,
+    prefix: N,
     description: String,
-    custom_fields: PrefixCustomField,
+    site: Option<NestedObject>,
+    scope: Option<NestedObject>,
+    vlan: Option<NestedVlan>,
+    tenant: Option<NestedObject>,
+    role: Option<NestedObject>,
+    tags: Option<Vec<NestedTag>>,
+    custom_fields: PrefixCustomField<N>,
 }
 
-impl Prefix {
-    pub fn prefix(&self) -> Ipv4Net {
-        self.prefix
+impl<N: NetAddr + Copy + Display> Prefix<N> {
+    pub fn url(&self) -> &str {
+        self.url.as_ref()
     }
 
-    pub fn addr(&self) -> Ipv4Addr {
-        self.prefix.addr()
+    pub fn prefix(&self) -> N {
+        self.prefix
     }
 
-    pub fn netmask(&self) -> Ipv4Addr {
-        self.prefix.netmask()
+    pub fn addr(&self) -> N::Addr {
+        self.prefix.net_addr()
     }
 
     pub fn description(&self) -> &str {
         self.description.as_ref()
     }
 
+    pub fn tags(&self) -> &[NestedTag] {
+        self.tags.as_deref().unwrap_or_default()
+    }
+
+    /// The tags, `None` if the list left them out.
+    pub fn fetched_tags(&self) -> Option<&[NestedTag]> {
+        self.tags.as_deref()
+    }
+
+    /// Raw value of a custom field not used by the sync itself.
+    pub fn custom_field(&self, name: &str) -> Option<&Value> {
+        self.custom_fields.other.get(name)
+    }
+
     pub fn lease_duration(&self) -> Option<u32> {
         self.custom_fields.dhcp_lease_duration
     }
@@ -35,44 +63,105 @@ impl Prefix {
         self.custom_fields.dhcp_dns_flags.as_ref()
     }
 
-    pub fn routers(&self) -> Option<Vec<Ipv4Addr>> {
+    pub fn routers(&self) -> Option<Vec<N::Addr>> {
         self.custom_fields.dhcp_routers.as_ref()
-            .map(|routers| routers.iter().map(|n| n.address.addr())
-            .collect::<Vec<Ipv4Addr>>())
+            .map(|routers| routers.iter().map(|n| n.address.net_addr())
+            .collect::<Vec<N::Addr>>())
     }
 
     pub fn dns_domain(&self) -> Option<&String> {
         self.custom_fields.dhcp_dns_domain.as_ref()
     }
 
-    pub fn dns_servers(&self) -> Option<Vec<Ipv4Addr>> {
+    pub fn dns_servers(&self) -> Option<Vec<N::Addr>> {
         self.custom_fields.dhcp_dns_servers.as_ref()
-            .map(|dns| dns.iter().map(|n| n.address.addr())
-            .collect::<Vec<Ipv4Addr>>())
+            .map(|dns| dns.iter().map(|n| n.address.net_addr())
+            .collect::<Vec<N::Addr>>())
     }
 
     pub fn failover_relation(&self) -> Option<&String> {
         self.custom_fields.dhcp_failover_relation.as_ref()
     }
+
+    pub fn template_value(&self, key: &str) -> Option<String> {
+        let site = self.site.as_ref().or(self.scope.as_ref());
+        match key {
+            "prefix" => Some(self.prefix.to_string()),
+            "description" => Some(self.description.clone()),
+            "site" => site.and_then(|s| s.name.clone()),
+            "site.slug" => site.and_then(|s| s.slug.clone()),
+            "vlan.vid" => self.vlan.as_ref().map(|v| v.vid.to_string()),
+            "vlan.name" => self.vlan.as_ref().map(|v| v.name.clone()),
+            "tenant" => self.tenant.as_ref().and_then(|t| t.name.clone()),
+            "tenant.slug" => self.tenant.as_ref().and_then(|t| t.slug.clone()),
+            "role" => self.role.as_ref().and_then(|r| r.name.clone()),
+            "role.slug" => self.role.as_ref().and_then(|r| r.slug.clone()),
+            key => key.strip_prefix("cf.")
+                .and_then(|cf| self.custom_fields.other.get(cf))
+                .and_then(custom_field_to_string),
+        }
+    }
 }
 
-#[derive(Debug, Deserialize)]
-struct PrefixCustomField {
+impl Prefix<Ipv4Net> {
+    pub fn netmask(&self) -> Ipv4Addr {
+        self.prefix.netmask()
+    }
+}
+
+impl Prefix<Ipv6Net> {
+    pub fn prefix_len(&self) -> u8 {
+        self.prefix.prefix_len()
+    }
+
+    pub fn preference(&self) -> Option<u16> {
+        self.custom_fields.dhcp_preference
+    }
+
+    pub fn preferred_lifetime(&self) -> Option<u32> {
+        self.custom_fields.dhcp_preferred_lifetime
+    }
+
+    pub fn valid_lifetime(&self) -> Option<u32> {
+        self.custom_fields.dhcp_valid_lifetime
+    }
+
+    pub fn domain_search(&self) -> Option<Vec<String>> {
+        match self.custom_fields.dhcp_domain_search.as_ref() {
+            Some(search) => Some(search.split([',', ' '])
+                .filter(|d| !d.is_empty())
+                .map(String::from)
+                .collect()),
+            None => self.dns_domain().map(|d| vec![d.clone()]),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct PrefixCustomField<N> {
     dhcp_lease_duration: Option<u32>,
     dhcp_dns_flags: Option<Vec<String>>,
-    dhcp_routers: Option<Vec<PrefixCustomFieldIp>>,
+    dhcp_routers: Option<Vec<PrefixCustomFieldIp<N>>>,
     dhcp_dns_domain: Option<String>,
-    dhcp_dns_servers: Option<Vec<PrefixCustomFieldIp>>,
+    dhcp_dns_servers: Option<Vec<PrefixCustomFieldIp<N>>>,
     dhcp_failover_relation: Option<String>,
+    dhcp_preference: Option<u16>,
+    dhcp_preferred_lifetime: Option<u32>,
+    dhcp_valid_lifetime: Option<u32>,
+    dhcp_domain_search: Option<String>,
+    #[serde(flatten)]
+    other: Map<String, Value>,
 }
 
-#[derive(Debug, Deserialize)]
-struct PrefixCustomFieldIp {
-    address: Ipv4Net,
+#[derive(Debug, Clone, Deserialize)]
+struct PrefixCustomFieldIp<N> {
+    address: N,
 }
 
 #[cfg(test)]
 mod tests {
+    use std::net::Ipv6Addr;
+
     use super::*;
 
     #[test]
@@ -121,4 +210,85 @@ mod tests {
         assert_eq!(prefix.dns_domain(), Some(&String::from("example.com")));
         assert_eq!(prefix.dns_servers(), Some(vec!("10.112.130.2".parse().unwrap(), "10.112.130.3".parse().unwrap())));
     }
+
+    #[test]
+    fn it_returns_template_values() {
+        let prefix = serde_json::from_str::<Prefix>(r#"{
+            "prefix": "10.112.130.0/24",
+            "description": "foo",
+            "site": { "name": "Zurich", "slug": "zrh" },
+            "vlan": { "vid": 130, "name": "Clients" },
+            "tenant": null,
+            "role": { "name": "Access", "slug": "access" },
+            "custom_fields": {
+                "dhcp_lease_duration": 86400,
+                "building": "B1",
+                "contacts": [{ "display": "Alice" }, { "display": "Bob" }]
+            }
+        }"#).unwrap();
+
+        assert_eq!(prefix.template_value("prefix"), Some(String::from("10.112.130.0/24")));
+        assert_eq!(prefix.template_value("site"), Some(String::from("Zurich")));
+        assert_eq!(prefix.template_value("site.slug"), Some(String::from("zrh")));
+        assert_eq!(prefix.template_value("vlan.vid"), Some(String::from("130")));
+        assert_eq!(prefix.template_value("vlan.name"), Some(String::from("Clients")));
+        assert_eq!(prefix.template_value("tenant"), None);
+        assert_eq!(prefix.template_value("role"), Some(String::from("Access")));
+        assert_eq!(prefix.template_value("cf.building"), Some(String::from("B1")));
+        assert_eq!(prefix.template_value("cf.contacts"), Some(String::from("Alice, Bob")));
+        assert_eq!(prefix.template_value("cf.missing"), None);
+    }
+
+    #[test]
+    fn it_returns_the_scope_as_site() {
+        let prefix = serde_json::from_str::<Prefix>(r#"{
+            "prefix": "10.112.130.0/24",
+            "description": "foo",
+            "scope": { "name": "Zurich", "slug": "zrh" },
+            "custom_fields": {}
+        }"#).unwrap();
+
+        assert_eq!(prefix.template_value("site"), Some(String::from("Zurich")));
+    }
+
+    #[test]
+    fn it_parses_netbox_ipv6_prefix() {
+        let prefix = serde_json::from_str::<PrefixV6>(r#"{
+            "prefix": "2001:db8:130::/64",
+            "description": "foo",
+            "custom_fields": {
+                "dhcp_preference": 10,
+                "dhcp_preferred_lifetime": 3600,
+                "dhcp_valid_lifetime": 7200,
+                "dhcp_dns_servers": [
+                    { "address": "2001:db8:130::53/64" }
+                ],
+                "dhcp_domain_search": "example.com, example.org"
+            }
+        }"#);
+        dbg!(&prefix);
+        assert!(prefix.is_ok());
+        let prefix = prefix.unwrap();
+
+        assert_eq!(prefix.addr(), "2001:db8:130::".parse::<Ipv6Addr>().unwrap());
+        assert_eq!(prefix.prefix_len(), 64);
+        assert_eq!(prefix.preference(), Some(10));
+        assert_eq!(prefix.preferred_lifetime(), Some(3600));
+        assert_eq!(prefix.valid_lifetime(), Some(7200));
+        assert_eq!(prefix.dns_servers(), Some(vec!("2001:db8:130::53".parse().unwrap())));
+        assert_eq!(prefix.domain_search(), Some(vec!(String::from("example.com"), String::from("example.org"))));
+    }
+
+    #[test]
+    fn it_uses_the_dns_domain_as_domain_search() {
+        let prefix = serde_json::from_str::<PrefixV6>(r#"{
+            "prefix": "2001:db8:130::/64",
+            "description": "foo",
+            "custom_fields": {
+                "dhcp_dns_domain": "example.com"
+            }
+        }"#).unwrap();
+
+        assert_eq!(prefix.domain_search(), Some(vec!(String::from("example.com"))));
+    }
 }
@@ -2,16 +2,32 @@ use std::net::Ipv4Addr;
 
 use ipnet::Ipv4Net;
 use serde::Deserialize;
+use serde_json::{Map, Value};
 
+use super::model::{custom_field_to_string, NestedObject, NestedTag};
 use super::prefix::Prefix;
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 pub struct IpRange {
+    #[serde(default)]
+    url: String,
     start_address: Ipv4Net,
     end_address: Ipv4Net,
+    #[serde(default)]
+    description: String,
+    tenant: Option<NestedObject>,
+    role: Option<NestedObject>,
+    vrf: Option<NestedObject>,
+    tags: Option<Vec<NestedTag>>,
+    #[serde(default)]
+    custom_fields: Map<String, Value>,
 }
 
 impl IpRange {
+    pub fn url(&self) -> &str {
+        self.url.as_ref()
+    }
+
     pub fn start_address(&self) -> Ipv4Addr {
         self.start_address.addr()
     }
@@ -20,6 +36,40 @@ impl IpRange {
         self.end_address.addr()
     }
 
+    pub fn description(&self) -> &str {
+        self.description.as_ref()
+    }
+
+    pub fn tags(&self) -> &[NestedTag] {
+        self.tags.as_deref().unwrap_or_default()
+    }
+
+    /// The tags, `None` if the list left them out.
+    pub fn fetched_tags(&self) -> Option<&[NestedTag]> {
+        self.tags.as_deref()
+    }
+
+    pub fn custom_field(&self, name: &str) -> Option<&Value> {
+        self.custom_fields.get(name)
+    }
+
+    /// Ranges have no site or VLAN in Netbox, only tenant, role and VRF.
+    pub fn template_value(&self, key: &str) -> Option<String> {
+        match key {
+            "start_address" => Some(self.start_address().to_string()),
+            "end_address" => Some(self.end_address().to_string()),
+            "description" => Some(self.description.clone()),
+            "tenant" => self.tenant.as_ref().and_then(|t| t.name.clone()),
+            "tenant.slug" => self.tenant.as_ref().and_then(|t| t.slug.clone()),
+            "role" => self.role.as_ref().and_then(|r| r.name.clone()),
+            "role.slug" => self.role.as_ref().and_then(|r| r.slug.clone()),
+            "vrf" => self.vrf.as_ref().and_then(|v| v.name.clone()),
+            key => key.strip_prefix("cf.")
+                .and_then(|cf| self.custom_fields.get(cf))
+                .and_then(custom_field_to_string),
+        }
+    }
+
     pub fn is_contained(&self, prefix: &Prefix) -> bool {
         prefix.prefix().contains(&self.start_address) && prefix.prefix().contains(&self.end_address)
     }
@@ -72,4 +122,25 @@ mod tests {
 
         assert!(!range.is_contained(&prefix));
     }
+
+    #[test]
+    fn it_returns_template_values() {
+        let range = serde_json::from_str::<IpRange>(r#"{
+            "start_address": "10.112.130.100/24",
+            "end_address": "10.112.130.149/24",
+            "tenant": { "name": "Acme", "slug": "acme" },
+            "role": { "name": "DHCP Pool", "slug": "dhcp-pool" },
+            "vrf": { "name": "Office" },
+            "custom_fields": { "pool_owner": "neteng", "unset": null }
+        }"#).unwrap();
+
+        assert_eq!(range.template_value("start_address"), Some(String::from("10.112.130.100")));
+        assert_eq!(range.template_value("tenant.slug"), Some(String::from("acme")));
+        assert_eq!(range.template_value("role"), Some(String::from("DHCP Pool")));
+        assert_eq!(range.template_value("role.slug"), Some(String::from("dhcp-pool")));
+        assert_eq!(range.template_value("vrf"), Some(String::from("Office")));
+        assert_eq!(range.template_value("cf.pool_owner"), Some(String::from("neteng")));
+        assert_eq!(range.template_value("cf.unset"), None);
+        assert_eq!(range.template_value("site"), None);
+    }
 }
@@ -0,0 +1,137 @@
+use serde_json::{Map, Value};
+
+use super::stats::StatsConfig;
+use super::status::{STATUS_FIELD, SYNCED_AT_FIELD};
+
+/// Custom field written by the log parser of `netbox-windhcp-log`.
+const LAST_ACTIVE_FIELD: &str = "dhcp_reservation_last_active";
+
+/// Fields of the last modification time, they change with every update.
+const IGNORED_FIELDS: [&str; 1] = ["last_updated"];
+
+/// Custom fields and tags the sync writes to Netbox, changes of only these don't need a sync.
+#[derive(Debug, Clone)]
+pub struct SyncOwned {
+    custom_fields: Vec<String>,
+    tags: Vec<String>,
+}
+
+impl SyncOwned {
+    pub fn new(stats: &StatsConfig) -> Self {
+        let mut custom_fields: Vec<String> = [STATUS_FIELD, SYNCED_AT_FIELD, LAST_ACTIVE_FIELD].into_iter()
+            .map(String::from)
+            .collect();
+        let mut tags = Vec::new();
+        if stats.enabled() {
+            custom_fields.extend(stats.custom_fields().cloned());
+            tags.extend(stats.full_tag().cloned());
+        }
+
+        Self { custom_fields, tags }
+    }
+
+    /// True if the snapshots before and after an update differ only in fields written by the sync,
+    /// created and deleted objects always need a sync.
+    pub fn only_owned_changed(&self, prechange: Option<&Map<String, Value>>, postchange: Option<&Map<String, Value>>) -> bool {
+        match (prechange, postchange) {
+            (Some(prechange), Some(postchange)) => self.strip(prechange) == self.strip(postchange),
+            _ => false,
+        }
+    }
+
+    /// The object without the fields written by the sync.
+    fn strip(&self, object: &Map<String, Value>) -> Map<String, Value> {
+        object.iter()
+            .filter(|(key, _)| !IGNORED_FIELDS.contains(&key.as_str()))
+            .map(|(key, value)| {
+                let value = match (key.as_str(), value) {
+                    ("custom_fields", Value::Object(fields)) => Value::Object(fields.iter()
+                        .filter(|(field, _)| !self.custom_fields.contains(field))
+                        .map(|(field, value)| (field.clone(), value.clone()))
+                        .collect()),
+                    ("tags", Value::Array(tags)) => Value::Array(tags.iter()
+                        .filter(|tag| !self.is_owned_tag(tag))
+                        .cloned()
+                        .collect()),
+                    (_, value) => value.clone(),
+                };
+                (key.clone(), value)
+            })
+            .collect()
+    }
+
+    /// Snapshots list tags by name, the REST data as nested tag with slug.
+    fn is_owned_tag(&self, tag: &Value) -> bool {
+        let names = match tag {
+            Value::String(name) => vec![name.as_str()],
+            tag => ["name", "slug"].iter().filter_map(|f| tag.get(*f).and_then(Value::as_str)).collect(),
+        };
+        names.into_iter().any(|name| self.tags.iter().any(|owned| *owned == name || *owned == slugify(name)))
+    }
+}
+
+/// Slug Netbox derives from a tag name.
+fn slugify(name: &str) -> String {
+    name.to_lowercase()
+        .split(|c: char| !c.is_alphanumeric() && c != '_')
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<&str>>()
+        .join("-")
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn owned() -> SyncOwned {
+        SyncOwned { custom_fields: vec![String::from(STATUS_FIELD), String::from(SYNCED_AT_FIELD)], tags: vec![String::from("dhcp-pool-full")] }
+    }
+
+    fn object(value: Value) -> Map<String, Value> {
+        value.as_object().unwrap().clone()
+    }
+
+    #[test]
+    fn it_ignores_changes_of_owned_fields() {
+        let prechange = object(json!({
+            "prefix": "10.0.0.0/24", "last_updated": "2024-05-01T10:00:00Z", "tags": ["DHCP"],
+            "custom_fields": { "dhcp_sync_status": null, "dhcp_synced_at": null, "building": "B1" }
+        }));
+        let postchange = object(json!({
+            "prefix": "10.0.0.0/24", "last_updated": "2024-05-01T10:05:00Z", "tags": ["DHCP", "DHCP Pool Full"],
+            "custom_fields": { "dhcp_sync_status": "ok", "dhcp_synced_at": "2024-05-01T10:05:00Z", "building": "B1" }
+        }));
+        assert!(owned().only_owned_changed(Some(&prechange), Some(&postchange)));
+
+        let mut renamed = postchange.clone();
+        renamed.insert(String::from("description"), json!("Clients"));
+        assert!(!owned().only_owned_changed(Some(&prechange), Some(&renamed)));
+
+        let mut moved = postchange.clone();
+        moved.insert(String::from("custom_fields"), json!({ "dhcp_sync_status": "ok", "building": "B2" }));
+        assert!(!owned().only_owned_changed(Some(&prechange), Some(&moved)));
+
+        assert!(!owned().only_owned_changed(None, Some(&postchange)));
+        assert!(!owned().only_owned_changed(Some(&prechange), None));
+    }
+
+    #[test]
+    fn it_owns_the_statistics_only_if_enabled() {
+        let prechange = object(json!({ "custom_fields": { "dhcp_addresses_in_use": 10 }, "tags": [] }));
+        let postchange = object(json!({ "custom_fields": { "dhcp_addresses_in_use": 95 }, "tags": ["dhcp-pool-full"] }));
+
+        let stats = serde_yaml_ng::from_str::<StatsConfig>("enabled: true").unwrap();
+        assert!(SyncOwned::new(&stats).only_owned_changed(Some(&prechange), Some(&postchange)));
+        assert!(!SyncOwned::new(&StatsConfig::default()).only_owned_changed(Some(&prechange), Some(&postchange)));
+    }
+
+    #[test]
+    fn it_recognizes_owned_tags_by_name_and_slug() {
+        assert!(owned().is_owned_tag(&json!("dhcp-pool-full")));
+        assert!(owned().is_owned_tag(&json!("DHCP Pool Full")));
+        assert!(owned().is_owned_tag(&json!({ "name": "Full", "slug": "dhcp-pool-full" })));
+        assert!(!owned().is_owned_tag(&json!("DHCP")));
+    }
+}
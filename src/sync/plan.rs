@@ -0,0 +1,516 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use super::mac::MacAddr;
+use super::state::{FailoverMode, FailoverRelation, ReservationV4, ReservationV6, ScopeV4, ScopeV6};
+use super::state::DnsFlags;
+
+pub type Plan = Vec<Change>;
+
+/// NetBox object URL of the Prefix or IP-Address a scope or reservation was built from.
+pub type Sources = HashMap<IpAddr, String>;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Change {
+    V4(Ipv4Addr, ScopeV4Change),
+    V6(Ipv6Addr, ScopeV6Change),
+    Failover(String, FailoverChange),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScopeV4Change {
+    Create { mask: Ipv4Addr },
+    Remove,
+    Mask { old: Ipv4Addr, new: Ipv4Addr },
+    Name { old: String, new: String },
+    Comment { old: String, new: String },
+    Range { old: (Ipv4Addr, Ipv4Addr), new: (Ipv4Addr, Ipv4Addr) },
+    LeaseDuration { old: Option<u32>, new: Option<u32> },
+    DnsFlags { old: Option<DnsFlags>, new: Option<DnsFlags> },
+    Routers { old: Vec<Ipv4Addr>, new: Vec<Ipv4Addr> },
+    DnsDomain { old: Option<String>, new: Option<String> },
+    DnsServers { old: Vec<Ipv4Addr>, new: Vec<Ipv4Addr> },
+    Failover { old: Option<String>, new: Option<String> },
+    AddExclusion((Ipv4Addr, Ipv4Addr)),
+    RemoveExclusion((Ipv4Addr, Ipv4Addr)),
+    AddReservation(ReservationV4),
+    RemoveReservation(ReservationV4),
+    ReservationName { address: Ipv4Addr, old: String, new: String },
+    ReservationComment { address: Ipv4Addr, old: String, new: String },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScopeV6Change {
+    Create { prefix_len: u8 },
+    Remove,
+    Name { old: String, new: String },
+    Comment { old: String, new: String },
+    Preference { old: u16, new: u16 },
+    Lifetimes { old: (u32, u32), new: (u32, u32) },
+    DnsServers { old: Vec<Ipv6Addr>, new: Vec<Ipv6Addr> },
+    DomainSearch { old: Vec<String>, new: Vec<String> },
+    AddReservation(ReservationV6),
+    RemoveReservation(ReservationV6),
+    ReservationName { address: Ipv6Addr, old: String, new: String },
+    ReservationComment { address: Ipv6Addr, old: String, new: String },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FailoverChange {
+    Create { relation: FailoverRelation, shared_secret: Option<String> },
+    Mode { old: FailoverMode, new: FailoverMode },
+    Mclt { old: u32, new: u32 },
+    SafePeriod { old: u32, new: u32 },
+    Percentage { old: u8, new: u8 },
+}
+
+impl Change {
+    pub fn is_failover(&self) -> bool {
+        matches!(self, Change::V4(_, ScopeV4Change::Failover { .. }) | Change::Failover(..))
+    }
+
+    /// Address of the scope or reservation the change applies to.
+    pub fn object_address(&self) -> Option<IpAddr> {
+        match self {
+            Change::V4(_, ScopeV4Change::AddReservation(r) | ScopeV4Change::RemoveReservation(r)) => Some(IpAddr::V4(r.address)),
+            Change::V4(_, ScopeV4Change::ReservationName { address, .. } | ScopeV4Change::ReservationComment { address, .. }) => Some(IpAddr::V4(*address)),
+            Change::V4(subnet, _) => Some(IpAddr::V4(*subnet)),
+            Change::V6(_, ScopeV6Change::AddReservation(r) | ScopeV6Change::RemoveReservation(r)) => Some(IpAddr::V6(r.address)),
+            Change::V6(_, ScopeV6Change::ReservationName { address, .. } | ScopeV6Change::ReservationComment { address, .. }) => Some(IpAddr::V6(*address)),
+            Change::V6(prefix, _) => Some(IpAddr::V6(*prefix)),
+            Change::Failover(..) => None,
+        }
+    }
+
+    /// Address of the scope the change belongs to.
+    pub fn scope_address(&self) -> Option<IpAddr> {
+        match self {
+            Change::V4(subnet, _) => Some(IpAddr::V4(*subnet)),
+            Change::V6(prefix, _) => Some(IpAddr::V6(*prefix)),
+            Change::Failover(..) => None,
+        }
+    }
+
+    /// NetBox object which caused the change, the Prefix for removed reservations.
+    pub fn source<'a>(&self, sources: &'a Sources) -> Option<&'a String> {
+        self.object_address().and_then(|a| sources.get(&a))
+            .or_else(|| self.scope_address().and_then(|a| sources.get(&a)))
+    }
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Change::V4(subnet, change) => match change {
+                ScopeV4Change::Create { mask } => write!(f, "Subnet {}: Created with netmask {}", subnet, mask),
+                ScopeV4Change::Remove => write!(f, "Subnet {}: Removed", subnet),
+                ScopeV4Change::Mask { new, .. } => write!(f, "Subnet {}: Updated netmask to {}", subnet, new),
+                ScopeV4Change::Name { new, .. } => write!(f, "Subnet {}: Updated name to {}", subnet, new),
+                ScopeV4Change::Comment { new, .. } => write!(f, "Subnet {}: Updated comment to {}", subnet, new),
+                ScopeV4Change::Range { new, .. } => write!(f, "Subnet {}: Updated range to {}-{}", subnet, new.0, new.1),
+                ScopeV4Change::LeaseDuration { new, .. } => write!(f, "Subnet {}: Updated lease duration to {}", subnet, new.unwrap_or_default()),
+                ScopeV4Change::DnsFlags { new, .. } => write!(f, "Subnet {}: Updated dns flags to {:?}", subnet, new),
+                ScopeV4Change::Routers { new, .. } => write!(f, "Subnet {}: Updated routers to {:?}", subnet, new),
+                ScopeV4Change::DnsDomain { new, .. } => write!(f, "Subnet {}: Updated dns domain to {:?}", subnet, new),
+                ScopeV4Change::DnsServers { new, .. } => write!(f, "Subnet {}: Updated dns to {:?}", subnet, new),
+                ScopeV4Change::Failover { old: None, new } => write!(f, "Subnet {}: Add to Failover Relation: {:?}", subnet, new),
+                ScopeV4Change::Failover { old, new: None } => write!(f, "Subnet {}: Remove from Failover Relation: {:?}", subnet, old),
+                ScopeV4Change::Failover { old, new } => write!(f, "Subnet {}: Move from Failover Relation {:?} to {:?}", subnet, old, new),
+                ScopeV4Change::AddExclusion(range) => write!(f, "Subnet {}: Add exclusion range {}-{}", subnet, range.0, range.1),
+                ScopeV4Change::RemoveExclusion(range) => write!(f, "Subnet {}: Remove exclusion range {}-{}", subnet, range.0, range.1),
+                ScopeV4Change::AddReservation(r) => write!(f, "Reservation {}: Create Reservation {}", r.address, r.mac.as_mac()),
+                ScopeV4Change::RemoveReservation(r) => write!(f, "Reservation {}: Remove Reservation {}", r.address, r.mac.as_mac()),
+                ScopeV4Change::ReservationName { address, new, .. } => write!(f, "Reservation {}: Set client name to {}", address, new),
+                ScopeV4Change::ReservationComment { address, new, .. } => write!(f, "Reservation {}: Set client comment to {}", address, new),
+            },
+            Change::V6(prefix, change) => match change {
+                ScopeV6Change::Create { prefix_len } => write!(f, "Subnet {}: Created with prefix length {}", prefix, prefix_len),
+                ScopeV6Change::Remove => write!(f, "Subnet {}: Removed", prefix),
+                ScopeV6Change::Name { new, .. } => write!(f, "Subnet {}: Updated name to {}", prefix, new),
+                ScopeV6Change::Comment { new, .. } => write!(f, "Subnet {}: Updated comment to {}", prefix, new),
+                ScopeV6Change::Preference { new, .. } => write!(f, "Subnet {}: Updated preference to {}", prefix, new),
+                ScopeV6Change::Lifetimes { new, .. } => write!(f, "Subnet {}: Updated lifetimes to preferred {}s valid {}s", prefix, new.0, new.1),
+                ScopeV6Change::DnsServers { new, .. } => write!(f, "Subnet {}: Updated dns to {:?}", prefix, new),
+                ScopeV6Change::DomainSearch { new, .. } => write!(f, "Subnet {}: Updated domain search list to {:?}", prefix, new),
+                ScopeV6Change::AddReservation(r) => write!(f, "Reservation {}: Create Reservation {} IAID {}", r.address, r.duid.as_mac(), r.iaid),
+                ScopeV6Change::RemoveReservation(r) => write!(f, "Reservation {}: Remove Reservation {} IAID {}", r.address, r.duid.as_mac(), r.iaid),
+                ScopeV6Change::ReservationName { address, new, .. } => write!(f, "Reservation {}: Set client name to {}", address, new),
+                ScopeV6Change::ReservationComment { address, new, .. } => write!(f, "Reservation {}: Set client comment to {}", address, new),
+            },
+            Change::Failover(name, change) => match change {
+                FailoverChange::Create { relation, .. } => write!(f, "Failover {}: Created {:?} relationship with partner {}", name, relation.mode, relation.partner()),
+                FailoverChange::Mode { old, new } => write!(f, "Failover {}: Updated mode from {:?} to {:?}", name, old, new),
+                FailoverChange::Mclt { old, new } => write!(f, "Failover {}: Updated MCLT from {} to {}", name, old, new),
+                FailoverChange::SafePeriod { old, new } => write!(f, "Failover {}: Updated state switchover interval from {} to {}", name, old, new),
+                FailoverChange::Percentage { old, new } => write!(f, "Failover {}: Updated percentage from {} to {}", name, old, new),
+            },
+        }
+    }
+}
+
+macro_rules! diff_field {
+    ($changes:ident, $change:ident, $variant:ident, $desired:expr, $actual:expr) => {
+        if $desired != $actual {
+            $changes.push($change::$variant { old: $actual.clone(), new: $desired.clone() });
+        }
+    };
+}
+
+pub fn diff_scope_v4(desired: &ScopeV4, actual: Option<&ScopeV4>) -> Plan {
+    let subnet = desired.subnet;
+    let mut changes = Vec::new();
+
+    let empty = ScopeV4::new(desired.subnet, desired.mask);
+    let actual = match actual {
+        Some(actual) => actual,
+        None => {
+            changes.push(ScopeV4Change::Create { mask: desired.mask });
+            &empty
+        }
+    };
+
+    diff_field!(changes, ScopeV4Change, Mask, desired.mask, actual.mask);
+    diff_field!(changes, ScopeV4Change, Name, desired.name, actual.name);
+    diff_field!(changes, ScopeV4Change, Comment, desired.comment, actual.comment);
+    diff_field!(changes, ScopeV4Change, Range, desired.range, actual.range);
+    diff_field!(changes, ScopeV4Change, LeaseDuration, desired.lease_duration, actual.lease_duration);
+    diff_field!(changes, ScopeV4Change, DnsFlags, desired.dns_flags, actual.dns_flags);
+    diff_field!(changes, ScopeV4Change, Routers, desired.routers, actual.routers);
+    diff_field!(changes, ScopeV4Change, DnsDomain, desired.dns_domain, actual.dns_domain);
+    diff_field!(changes, ScopeV4Change, DnsServers, desired.dns_servers, actual.dns_servers);
+    diff_field!(changes, ScopeV4Change, Failover, desired.failover, actual.failover);
+
+    // The client type can only be set when the reservation is created.
+    for (address, reservation) in desired.reservations.iter() {
+        let current = match actual.reservations.get(address) {
+            Some(current) if current.mac == reservation.mac && current.client_type == reservation.client_type => current.clone(),
+            Some(current) => {
+                changes.push(ScopeV4Change::RemoveReservation(current.clone()));
+                changes.push(ScopeV4Change::AddReservation(reservation.clone()));
+                ReservationV4 { name: String::new(), comment: String::new(), ..reservation.clone() }
+            },
+            None => {
+                changes.push(ScopeV4Change::AddReservation(reservation.clone()));
+                ReservationV4 { name: String::new(), comment: String::new(), ..reservation.clone() }
+            },
+        };
+
+        if reservation.name != current.name {
+            changes.push(ScopeV4Change::ReservationName { address: *address, old: current.name, new: reservation.name.clone() });
+        }
+        if reservation.comment != current.comment {
+            changes.push(ScopeV4Change::ReservationComment { address: *address, old: current.comment, new: reservation.comment.clone() });
+        }
+    }
+
+    for (address, reservation) in actual.reservations.iter() {
+        if !desired.reservations.contains_key(address) {
+            changes.push(ScopeV4Change::RemoveReservation(reservation.clone()));
+        }
+    }
+
+    changes.into_iter().map(|c| Change::V4(subnet, c)).collect()
+}
+
+pub fn remove_scope_v4(actual: &ScopeV4) -> Plan {
+    let mut changes = Vec::new();
+
+    if actual.failover.is_some() {
+        changes.push(Change::V4(actual.subnet, ScopeV4Change::Failover { old: actual.failover.clone(), new: None }));
+    }
+    changes.push(Change::V4(actual.subnet, ScopeV4Change::Remove));
+
+    changes
+}
+
+pub fn diff_scope_v6(desired: &ScopeV6, actual: Option<&ScopeV6>) -> Plan {
+    let prefix = desired.prefix;
+    let mut changes = Vec::new();
+
+    let empty = ScopeV6::new(desired.prefix, desired.prefix_len);
+    let actual = match actual {
+        Some(actual) => actual,
+        None => {
+            changes.push(ScopeV6Change::Create { prefix_len: desired.prefix_len });
+            &empty
+        }
+    };
+
+    diff_field!(changes, ScopeV6Change, Name, desired.name, actual.name);
+    diff_field!(changes, ScopeV6Change, Comment, desired.comment, actual.comment);
+    diff_field!(changes, ScopeV6Change, Preference, desired.preference, actual.preference);
+    diff_field!(changes, ScopeV6Change, Lifetimes,
+        (desired.preferred_lifetime, desired.valid_lifetime),
+        (actual.preferred_lifetime, actual.valid_lifetime));
+    diff_field!(changes, ScopeV6Change, DnsServers, desired.dns_servers, actual.dns_servers);
+    diff_field!(changes, ScopeV6Change, DomainSearch, desired.domain_search, actual.domain_search);
+
+    for (address, reservation) in desired.reservations.iter() {
+        let current = match actual.reservations.get(address) {
+            Some(current) if current.duid == reservation.duid && current.iaid == reservation.iaid => current.clone(),
+            Some(current) => {
+                changes.push(ScopeV6Change::RemoveReservation(current.clone()));
+                changes.push(ScopeV6Change::AddReservation(reservation.clone()));
+                ReservationV6 { name: String::new(), comment: String::new(), ..reservation.clone() }
+            },
+            None => {
+                changes.push(ScopeV6Change::AddReservation(reservation.clone()));
+                ReservationV6 { name: String::new(), comment: String::new(), ..reservation.clone() }
+            },
+        };
+
+        if reservation.name != current.name {
+            changes.push(ScopeV6Change::ReservationName { address: *address, old: current.name, new: reservation.name.clone() });
+        }
+        if reservation.comment != current.comment {
+            changes.push(ScopeV6Change::ReservationComment { address: *address, old: current.comment, new: reservation.comment.clone() });
+        }
+    }
+
+    for (address, reservation) in actual.reservations.iter() {
+        if !desired.reservations.contains_key(address) {
+            changes.push(ScopeV6Change::RemoveReservation(reservation.clone()));
+        }
+    }
+
+    changes.into_iter().map(|c| Change::V6(prefix, c)).collect()
+}
+
+pub fn remove_scope_v6(actual: &ScopeV6) -> Plan {
+    vec![Change::V6(actual.prefix, ScopeV6Change::Remove)]
+}
+
+/// Plan to bring a scope back to a snapshotted state, `None` meaning the scope did not exist.
+#[cfg_attr(not(target_os = "windows"), allow(dead_code))]
+pub fn restore_scope_v4(snapshot: Option<&ScopeV4>, actual: Option<&ScopeV4>) -> Plan {
+    match (snapshot, actual) {
+        (Some(snapshot), actual) => diff_scope_v4(snapshot, actual),
+        (None, Some(actual)) => remove_scope_v4(actual),
+        (None, None) => Vec::new(),
+    }
+}
+
+/// Exclusion ranges to add and remove to get from `actual` back to `snapshot`.
+#[cfg_attr(not(target_os = "windows"), allow(dead_code))]
+pub fn restore_exclusions(subnet: Ipv4Addr, snapshot: &[(Ipv4Addr, Ipv4Addr)], actual: &[(Ipv4Addr, Ipv4Addr)]) -> Plan {
+    let remove = actual.iter()
+        .filter(|range| !snapshot.contains(range))
+        .map(|range| ScopeV4Change::RemoveExclusion(*range));
+    let add = snapshot.iter()
+        .filter(|range| !actual.contains(range))
+        .map(|range| ScopeV4Change::AddExclusion(*range));
+
+    remove.chain(add).map(|c| Change::V4(subnet, c)).collect()
+}
+
+#[cfg_attr(not(target_os = "windows"), allow(dead_code))]
+pub fn restore_scope_v6(snapshot: Option<&ScopeV6>, actual: Option<&ScopeV6>) -> Plan {
+    match (snapshot, actual) {
+        (Some(snapshot), actual) => diff_scope_v6(snapshot, actual),
+        (None, Some(actual)) => remove_scope_v6(actual),
+        (None, None) => Vec::new(),
+    }
+}
+
+pub fn diff_failover(desired: &FailoverRelation, actual: Option<&FailoverRelation>, shared_secret: Option<&String>) -> Plan {
+    let actual = match actual {
+        Some(actual) => actual,
+        None => return vec![Change::Failover(desired.name.clone(), FailoverChange::Create {
+            relation: desired.clone(),
+            shared_secret: shared_secret.cloned(),
+        })],
+    };
+
+    let mut changes = Vec::new();
+
+    diff_field!(changes, FailoverChange, Mode, desired.mode, actual.mode);
+    diff_field!(changes, FailoverChange, Mclt, desired.mclt, actual.mclt);
+    diff_field!(changes, FailoverChange, SafePeriod, desired.safe_period, actual.safe_period);
+    diff_field!(changes, FailoverChange, Percentage, desired.percentage, actual.percentage);
+
+    changes.into_iter().map(|c| Change::Failover(desired.name.clone(), c)).collect()
+}
+
+/// Differences of a failover relationship which can not be changed on an existing relationship.
+pub fn failover_conflicts(desired: &FailoverRelation, actual: &FailoverRelation) -> Vec<String> {
+    let mut conflicts = Vec::new();
+
+    if desired.primary_server != actual.primary_server {
+        conflicts.push(format!("primary server is {} instead of {}", actual.primary_server, desired.primary_server));
+    }
+    if desired.secondary_server != actual.secondary_server {
+        conflicts.push(format!("secondary server is {} instead of {}", actual.secondary_server, desired.secondary_server));
+    }
+    if desired.server_type != actual.server_type {
+        conflicts.push(format!("server role is {:?} instead of {:?}", actual.server_type, desired.server_type));
+    }
+
+    conflicts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sync::state::ReservationClientTypes;
+
+    fn scope() -> ScopeV4 {
+        let mut scope = ScopeV4::new("10.0.0.0".parse().unwrap(), "255.255.255.0".parse().unwrap());
+        scope.name = String::from("Clients");
+        scope.range = ("10.0.0.100".parse().unwrap(), "10.0.0.200".parse().unwrap());
+        scope.lease_duration = Some(3600);
+        scope
+    }
+
+    fn reservation(address: &str, mac: u8) -> ReservationV4 {
+        ReservationV4 {
+            address: address.parse().unwrap(),
+            mac: vec![0, 0, 0, 0, 0, mac],
+            client_type: ReservationClientTypes::Both,
+            name: String::from("host"),
+            comment: String::new(),
+        }
+    }
+
+    #[test]
+    fn it_plans_nothing_for_equal_scopes() {
+        assert_eq!(diff_scope_v4(&scope(), Some(&scope())), vec![]);
+    }
+
+    #[test]
+    fn it_plans_a_new_scope() {
+        let plan = diff_scope_v4(&scope(), None);
+        let subnet = scope().subnet;
+        assert_eq!(plan, vec![
+            Change::V4(subnet, ScopeV4Change::Create { mask: scope().mask }),
+            Change::V4(subnet, ScopeV4Change::Name { old: String::new(), new: String::from("Clients") }),
+            Change::V4(subnet, ScopeV4Change::Range { old: (Ipv4Addr::from(0), Ipv4Addr::from(0)), new: scope().range }),
+            Change::V4(subnet, ScopeV4Change::LeaseDuration { old: None, new: Some(3600) }),
+        ]);
+    }
+
+    #[test]
+    fn it_plans_field_updates() {
+        let mut actual = scope();
+        actual.name = String::from("Old");
+        actual.failover = Some(String::from("Failover"));
+        let plan = diff_scope_v4(&scope(), Some(&actual));
+        assert_eq!(plan, vec![
+            Change::V4(actual.subnet, ScopeV4Change::Name { old: String::from("Old"), new: String::from("Clients") }),
+            Change::V4(actual.subnet, ScopeV4Change::Failover { old: Some(String::from("Failover")), new: None }),
+        ]);
+    }
+
+    #[test]
+    fn it_plans_reservations() {
+        let mut desired = scope();
+        desired.reservations.insert("10.0.0.10".parse().unwrap(), reservation("10.0.0.10", 1));
+        desired.reservations.insert("10.0.0.11".parse().unwrap(), reservation("10.0.0.11", 2));
+        let mut actual = scope();
+        actual.reservations.insert("10.0.0.11".parse().unwrap(), reservation("10.0.0.11", 3));
+        actual.reservations.insert("10.0.0.12".parse().unwrap(), reservation("10.0.0.12", 4));
+
+        let plan = diff_scope_v4(&desired, Some(&actual));
+        let subnet = desired.subnet;
+        assert_eq!(plan, vec![
+            Change::V4(subnet, ScopeV4Change::AddReservation(reservation("10.0.0.10", 1))),
+            Change::V4(subnet, ScopeV4Change::ReservationName { address: "10.0.0.10".parse().unwrap(), old: String::new(), new: String::from("host") }),
+            Change::V4(subnet, ScopeV4Change::RemoveReservation(reservation("10.0.0.11", 3))),
+            Change::V4(subnet, ScopeV4Change::AddReservation(reservation("10.0.0.11", 2))),
+            Change::V4(subnet, ScopeV4Change::ReservationName { address: "10.0.0.11".parse().unwrap(), old: String::new(), new: String::from("host") }),
+            Change::V4(subnet, ScopeV4Change::RemoveReservation(reservation("10.0.0.12", 4))),
+        ]);
+    }
+
+    #[test]
+    fn it_plans_scope_removal() {
+        let mut actual = scope();
+        actual.failover = Some(String::from("Failover"));
+        assert_eq!(remove_scope_v4(&actual), vec![
+            Change::V4(actual.subnet, ScopeV4Change::Failover { old: Some(String::from("Failover")), new: None }),
+            Change::V4(actual.subnet, ScopeV4Change::Remove),
+        ]);
+    }
+
+    #[test]
+    fn it_plans_scope_restore() {
+        let mut snapshot = scope();
+        snapshot.reservations.insert("10.0.0.10".parse().unwrap(), reservation("10.0.0.10", 1));
+        let subnet = snapshot.subnet;
+
+        assert_eq!(restore_scope_v4(Some(&snapshot), Some(&scope())), vec![
+            Change::V4(subnet, ScopeV4Change::AddReservation(reservation("10.0.0.10", 1))),
+            Change::V4(subnet, ScopeV4Change::ReservationName { address: "10.0.0.10".parse().unwrap(), old: String::new(), new: String::from("host") }),
+        ]);
+        let mut actual = snapshot.clone();
+        actual.reservations.values_mut().for_each(|r| r.client_type = ReservationClientTypes::Dhcp);
+        let changed = ReservationV4 { client_type: ReservationClientTypes::Dhcp, ..reservation("10.0.0.10", 1) };
+        assert_eq!(restore_scope_v4(Some(&snapshot), Some(&actual)), vec![
+            Change::V4(subnet, ScopeV4Change::RemoveReservation(changed)),
+            Change::V4(subnet, ScopeV4Change::AddReservation(reservation("10.0.0.10", 1))),
+            Change::V4(subnet, ScopeV4Change::ReservationName { address: "10.0.0.10".parse().unwrap(), old: String::new(), new: String::from("host") }),
+        ]);
+
+        assert_eq!(restore_scope_v4(None, Some(&scope())), vec![Change::V4(subnet, ScopeV4Change::Remove)]);
+        assert_eq!(restore_scope_v4(None, None), vec![]);
+    }
+
+    #[test]
+    fn it_plans_a_new_v6_scope() {
+        let mut desired = ScopeV6::new("2001:db8::".parse().unwrap(), 64);
+        desired.preferred_lifetime = 3600;
+        desired.valid_lifetime = 7200;
+        desired.reservations.insert("2001:db8::10".parse().unwrap(), ReservationV6 {
+            address: "2001:db8::10".parse().unwrap(),
+            duid: vec![0, 1, 2, 3],
+            iaid: 42,
+            name: String::new(),
+            comment: String::new(),
+        });
+
+        let plan = diff_scope_v6(&desired, None);
+        assert_eq!(plan, vec![
+            Change::V6(desired.prefix, ScopeV6Change::Create { prefix_len: 64 }),
+            Change::V6(desired.prefix, ScopeV6Change::Lifetimes { old: (0, 0), new: (3600, 7200) }),
+            Change::V6(desired.prefix, ScopeV6Change::AddReservation(desired.reservations.values().next().unwrap().clone())),
+        ]);
+    }
+
+    fn relation() -> FailoverRelation {
+        FailoverRelation {
+            name: String::from("DHCP-Failover"),
+            primary_server: Ipv4Addr::new(10, 0, 0, 1),
+            secondary_server: Ipv4Addr::new(10, 0, 0, 2),
+            mode: FailoverMode::LoadBalance,
+            server_type: crate::sync::state::FailoverServerType::Primary,
+            mclt: 3600,
+            safe_period: 0,
+            percentage: 50,
+        }
+    }
+
+    #[test]
+    fn it_plans_a_new_failover_relation() {
+        let secret = String::from("secret");
+        assert_eq!(diff_failover(&relation(), None, Some(&secret)), vec![
+            Change::Failover(String::from("DHCP-Failover"), FailoverChange::Create { relation: relation(), shared_secret: Some(secret) }),
+        ]);
+    }
+
+    #[test]
+    fn it_plans_failover_relation_updates() {
+        let mut actual = relation();
+        actual.mclt = 60;
+        actual.secondary_server = Ipv4Addr::new(10, 0, 0, 3);
+        assert_eq!(diff_failover(&relation(), Some(&actual), None), vec![
+            Change::Failover(String::from("DHCP-Failover"), FailoverChange::Mclt { old: 60, new: 3600 }),
+        ]);
+        assert_eq!(failover_conflicts(&relation(), &actual), vec![String::from("secondary server is 10.0.0.3 instead of 10.0.0.2")]);
+    }
+
+    #[test]
+    fn it_displays_changes() {
+        let change = Change::V4("10.0.0.0".parse().unwrap(), ScopeV4Change::Name { old: String::new(), new: String::from("Clients") });
+        assert_eq!(change.to_string(), "Subnet 10.0.0.0: Updated name to Clients");
+    }
+}
@@ -0,0 +1,230 @@
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter};
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+
+use super::plan::{restore_exclusions, restore_scope_v4, restore_scope_v6, Plan};
+use super::state::{ScopeV4, ScopeV6};
+
+const PREFIX: &str = "snapshot-";
+const EXTENSION: &str = ".json";
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct SnapshotConfig {
+    dir: Option<PathBuf>,
+    keep: usize,
+}
+
+impl Default for SnapshotConfig {
+    fn default() -> Self {
+        Self { dir: None, keep: 10 }
+    }
+}
+
+impl SnapshotConfig {
+    pub fn dir(&self) -> Option<&PathBuf> {
+        self.dir.as_ref()
+    }
+
+    pub fn keep(&self) -> usize {
+        self.keep
+    }
+}
+
+/// State of every scope a sync run is about to change, `None` if the scope does not exist yet.
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct Snapshot {
+    pub run_id: String,
+    pub timestamp: DateTime<Local>,
+    pub server: String,
+    #[serde(default)]
+    pub scopes_v4: BTreeMap<Ipv4Addr, Option<ScopeV4>>,
+    #[serde(default)]
+    pub scopes_v6: BTreeMap<Ipv6Addr, Option<ScopeV6>>,
+    /// Exclusion ranges of the existing IPv4 scopes, missing in older snapshots.
+    #[serde(default)]
+    pub exclusions_v4: BTreeMap<Ipv4Addr, Vec<(Ipv4Addr, Ipv4Addr)>>,
+}
+
+impl Snapshot {
+    pub fn new(run_id: &str, server: &str) -> Self {
+        Self {
+            run_id: run_id.to_string(),
+            timestamp: Local::now(),
+            server: server.to_string(),
+            scopes_v4: BTreeMap::new(),
+            scopes_v6: BTreeMap::new(),
+            exclusions_v4: BTreeMap::new(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.scopes_v4.is_empty() && self.scopes_v6.is_empty()
+    }
+
+    pub fn load(path: &Path) -> Result<Self, Box<dyn std::error::Error + Send + std::marker::Sync>> {
+        let file = File::open(path)
+            .map_err(|e| format!("Unable to open snapshot {}: {}", path.display(), e))?;
+        Ok(serde_json::from_reader(BufReader::new(file))
+            .map_err(|e| format!("Unable to read snapshot {}: {}", path.display(), e))?)
+    }
+
+    /// Writes the snapshot into the configured directory and removes the oldest snapshots beyond `keep`.
+    pub fn save(&self, config: &SnapshotConfig) -> io::Result<Option<PathBuf>> {
+        let dir = match config.dir() {
+            Some(dir) => dir,
+            None => return Ok(None),
+        };
+
+        fs::create_dir_all(dir)?;
+        let path = dir.join(format!("{}{}{}", PREFIX, self.run_id, EXTENSION));
+        serde_json::to_writer_pretty(BufWriter::new(File::create(&path)?), self)?;
+
+        prune(dir, config.keep())?;
+
+        Ok(Some(path))
+    }
+
+    /// Plan to bring the scopes back to the snapshotted state, given their current state and exclusion ranges.
+    pub fn restore_plan(
+        &self,
+        current_v4: &BTreeMap<Ipv4Addr, Option<ScopeV4>>,
+        current_exclusions: &BTreeMap<Ipv4Addr, Vec<(Ipv4Addr, Ipv4Addr)>>,
+        current_v6: &BTreeMap<Ipv6Addr, Option<ScopeV6>>,
+    ) -> Plan {
+        let mut plan = Vec::new();
+
+        for (subnet, scope) in self.scopes_v4.iter() {
+            plan.append(&mut restore_scope_v4(scope.as_ref(), current_v4.get(subnet).and_then(|s| s.as_ref())));
+            if let (Some(_), Some(exclusions)) = (scope, self.exclusions_v4.get(subnet)) {
+                let current = current_exclusions.get(subnet).map(Vec::as_slice).unwrap_or_default();
+                plan.append(&mut restore_exclusions(*subnet, exclusions, current));
+            }
+        }
+        for (prefix, scope) in self.scopes_v6.iter() {
+            plan.append(&mut restore_scope_v6(scope.as_ref(), current_v6.get(prefix).and_then(|s| s.as_ref())));
+        }
+
+        plan
+    }
+}
+
+fn prune(dir: &Path, keep: usize) -> io::Result<()> {
+    let mut snapshots = fs::read_dir(dir)?
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| p.file_name()
+            .and_then(|n| n.to_str())
+            .is_some_and(|n| n.starts_with(PREFIX) && n.ends_with(EXTENSION)))
+        .collect::<Vec<PathBuf>>();
+
+    // run ids start with the timestamp, so the name order is the creation order
+    snapshots.sort();
+
+    let remove = snapshots.len().saturating_sub(keep);
+    for path in snapshots.into_iter().take(remove) {
+        fs::remove_file(path)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sync::plan::{Change, ScopeV4Change};
+    use crate::sync::state::ReservationV4;
+    use crate::sync::state::ReservationClientTypes;
+
+    fn scope() -> ScopeV4 {
+        let mut scope = ScopeV4::new("10.0.0.0".parse().unwrap(), "255.255.255.0".parse().unwrap());
+        scope.name = String::from("Clients");
+        scope.failover = Some(String::from("Failover"));
+        scope.reservations.insert("10.0.0.10".parse().unwrap(), ReservationV4 {
+            address: "10.0.0.10".parse().unwrap(),
+            mac: vec![0, 0x11, 0x22, 0x33, 0x44, 0x55],
+            client_type: ReservationClientTypes::Both,
+            name: String::from("host"),
+            comment: String::from("comment"),
+        });
+        scope
+    }
+
+    #[test]
+    fn it_serializes_snapshots() {
+        let mut snapshot = Snapshot::new("run", "dhcp.example.com");
+        snapshot.scopes_v4.insert("10.0.0.0".parse().unwrap(), Some(scope()));
+        snapshot.scopes_v4.insert("10.0.1.0".parse().unwrap(), None);
+        snapshot.exclusions_v4.insert("10.0.0.0".parse().unwrap(), vec![("10.0.0.150".parse().unwrap(), "10.0.0.160".parse().unwrap())]);
+
+        let json = serde_json::to_string(&snapshot).unwrap();
+        assert!(json.contains(r#""mac":"00:11:22:33:44:55""#));
+        assert!(json.contains(r#""10.0.1.0":null"#));
+        assert_eq!(serde_json::from_str::<Snapshot>(&json).unwrap(), snapshot);
+    }
+
+    #[test]
+    fn it_plans_the_restore() {
+        let mut snapshot = Snapshot::new("run", "dhcp.example.com");
+        snapshot.scopes_v4.insert("10.0.0.0".parse().unwrap(), Some(scope()));
+        snapshot.scopes_v4.insert("10.0.1.0".parse().unwrap(), None);
+
+        let mut renamed = scope();
+        renamed.name = String::from("Renamed");
+        let current = BTreeMap::from([
+            ("10.0.0.0".parse().unwrap(), Some(renamed)),
+            ("10.0.1.0".parse().unwrap(), Some(ScopeV4::new("10.0.1.0".parse().unwrap(), "255.255.255.0".parse().unwrap()))),
+        ]);
+
+        assert_eq!(snapshot.restore_plan(&current, &BTreeMap::new(), &BTreeMap::new()), vec![
+            Change::V4("10.0.0.0".parse().unwrap(), ScopeV4Change::Name { old: String::from("Renamed"), new: String::from("Clients") }),
+            Change::V4("10.0.1.0".parse().unwrap(), ScopeV4Change::Remove),
+        ]);
+    }
+
+    #[test]
+    fn it_restores_removed_scopes_with_exclusions_and_client_types() {
+        let subnet: Ipv4Addr = "10.0.0.0".parse().unwrap();
+        let mut scope = scope();
+        scope.reservations.values_mut().for_each(|r| r.client_type = ReservationClientTypes::Dhcp);
+        let exclusion = ("10.0.0.150".parse().unwrap(), "10.0.0.160".parse().unwrap());
+
+        let mut snapshot = Snapshot::new("run", "dhcp.example.com");
+        snapshot.scopes_v4.insert(subnet, Some(scope.clone()));
+        snapshot.exclusions_v4.insert(subnet, vec![exclusion]);
+
+        let plan = snapshot.restore_plan(&BTreeMap::from([(subnet, None)]), &BTreeMap::new(), &BTreeMap::new());
+        assert!(plan.contains(&Change::V4(subnet, ScopeV4Change::AddReservation(scope.reservations.values().next().unwrap().clone()))));
+        assert_eq!(plan.last(), Some(&Change::V4(subnet, ScopeV4Change::AddExclusion(exclusion))));
+
+        let other = ("10.0.0.170".parse().unwrap(), "10.0.0.180".parse().unwrap());
+        let plan = snapshot.restore_plan(&BTreeMap::from([(subnet, Some(scope))]), &BTreeMap::from([(subnet, vec![other])]), &BTreeMap::new());
+        assert_eq!(plan, vec![
+            Change::V4(subnet, ScopeV4Change::RemoveExclusion(other)),
+            Change::V4(subnet, ScopeV4Change::AddExclusion(exclusion)),
+        ]);
+    }
+
+    #[test]
+    fn it_keeps_the_last_snapshots() {
+        let dir = std::env::temp_dir().join(format!("netbox-windhcp-snapshot-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let config = SnapshotConfig { dir: Some(dir.clone()), keep: 2 };
+
+        for run_id in ["20240101T000000-1", "20240102T000000-1", "20240103T000000-1"] {
+            Snapshot::new(run_id, "dhcp.example.com").save(&config).unwrap();
+        }
+
+        assert!(!dir.join("snapshot-20240101T000000-1.json").exists());
+        assert!(dir.join("snapshot-20240102T000000-1.json").exists());
+        let path = dir.join("snapshot-20240103T000000-1.json");
+        assert_eq!(Snapshot::load(&path).unwrap().run_id, "20240103T000000-1");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,232 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+use serde::{Deserialize, Serialize};
+
+use super::mac::serde_mac;
+
+/// Length limits of the DHCP server for names and comments.
+pub const SUBNET_NAME_MAX_LENGTH: usize = 128;
+pub const SUBNET_COMMENT_MAX_LENGTH: usize = 255;
+pub const CLIENT_COMMENT_MAX_LENGTH: usize = 255;
+
+/// Read access to the scopes of a DHCP server, either live or from an export file.
+pub trait DhcpState {
+    fn subnets_v4(&self) -> Result<Vec<Ipv4Addr>, Box<dyn std::error::Error + Send + std::marker::Sync>>;
+    fn subnets_v6(&self) -> Result<Vec<Ipv6Addr>, Box<dyn std::error::Error + Send + std::marker::Sync>>;
+    fn scope_v4(&self, subnet: &Ipv4Addr) -> Result<Option<ScopeV4>, Box<dyn std::error::Error + Send + std::marker::Sync>>;
+    fn scope_v6(&self, prefix: &Ipv6Addr) -> Result<Option<ScopeV6>, Box<dyn std::error::Error + Send + std::marker::Sync>>;
+    fn failover_relation(&self, name: &str) -> Result<Option<FailoverRelation>, Box<dyn std::error::Error + Send + std::marker::Sync>>;
+}
+
+#[derive(Debug, Default, Deserialize, Serialize, Clone, PartialEq, Eq)]
+pub struct DnsFlags {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default)]
+    pub update_downlevel: bool,
+    #[serde(default)]
+    pub cleanup_expired: bool,
+    #[serde(default)]
+    pub update_both_always: bool,
+    #[serde(default)]
+    pub update_dhcid: bool,
+    #[serde(default)]
+    pub disable_ptr_update: bool,
+}
+
+impl fmt::Display for DnsFlags {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.enabled { write!(f, "Enabled")? } else { write!(f, "Disabled")? }
+        if self.update_downlevel { write!(f, ", Update Downlevel")? }
+        if self.cleanup_expired { write!(f, ", Cleanup Expired")? }
+        if self.update_both_always { write!(f, ", Update Both always")? }
+        if self.update_dhcid { write!(f, ", Update DHCID")? }
+        if self.disable_ptr_update { write!(f, ", Disable PTR update")? }
+        Ok(())
+    }
+}
+
+impl From<&Vec<String>> for DnsFlags {
+    fn from(flags: &Vec<String>) -> Self {
+        Self {
+            enabled: flags.contains(&String::from("enabled")),
+            update_downlevel: flags.contains(&String::from("update_downlevel")),
+            cleanup_expired: flags.contains(&String::from("cleanup_expired")),
+            update_both_always: flags.contains(&String::from("update_both_always")),
+            update_dhcid: flags.contains(&String::from("update_dhcid")),
+            disable_ptr_update: flags.contains(&String::from("disable_ptr_update")),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[repr(u8)]
+pub enum ReservationClientTypes {
+    Dhcp = 1,
+    Bootp = 2,
+    Both = 3
+}
+
+impl From<u8> for ReservationClientTypes {
+    fn from(value: u8) -> Self {
+        match value {
+            1 => ReservationClientTypes::Dhcp,
+            2 => ReservationClientTypes::Bootp,
+            _n => ReservationClientTypes::Both,
+        }
+    }
+}
+
+impl From<ReservationClientTypes> for u8 {
+    fn from(value: ReservationClientTypes) -> Self {
+        match value {
+            ReservationClientTypes::Dhcp => 1,
+            ReservationClientTypes::Bootp => 2,
+            ReservationClientTypes::Both => 3,
+        }
+    }
+}
+
+/// Address usage of an IPv4 scope as reported by the DHCP server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScopeStats {
+    pub in_use: u32,
+    pub free: u32,
+    pub pending_offers: u32,
+}
+
+impl ScopeStats {
+    /// Addresses in use in percent of the pool, rounded to one decimal.
+    pub fn utilisation(&self) -> f64 {
+        match self.in_use + self.free {
+            0 => 0.0,
+            total => (self.in_use as f64 * 1000.0 / total as f64).round() / 10.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScopeV4 {
+    pub subnet: Ipv4Addr,
+    pub mask: Ipv4Addr,
+    pub name: String,
+    pub comment: String,
+    pub range: (Ipv4Addr, Ipv4Addr),
+    pub lease_duration: Option<u32>,
+    pub dns_flags: Option<DnsFlags>,
+    pub routers: Vec<Ipv4Addr>,
+    pub dns_domain: Option<String>,
+    pub dns_servers: Vec<Ipv4Addr>,
+    pub failover: Option<String>,
+    pub reservations: BTreeMap<Ipv4Addr, ReservationV4>,
+}
+
+impl ScopeV4 {
+    pub fn new(subnet: Ipv4Addr, mask: Ipv4Addr) -> Self {
+        Self {
+            subnet,
+            mask,
+            name: String::new(),
+            comment: String::new(),
+            range: (Ipv4Addr::from(0), Ipv4Addr::from(0)),
+            lease_duration: None,
+            dns_flags: None,
+            routers: Vec::new(),
+            dns_domain: None,
+            dns_servers: Vec::new(),
+            failover: None,
+            reservations: BTreeMap::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReservationV4 {
+    pub address: Ipv4Addr,
+    #[serde(with = "serde_mac")]
+    pub mac: Vec<u8>,
+    pub client_type: ReservationClientTypes,
+    pub name: String,
+    pub comment: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScopeV6 {
+    pub prefix: Ipv6Addr,
+    pub prefix_len: u8,
+    pub name: String,
+    pub comment: String,
+    pub preference: u16,
+    pub preferred_lifetime: u32,
+    pub valid_lifetime: u32,
+    pub dns_servers: Vec<Ipv6Addr>,
+    pub domain_search: Vec<String>,
+    pub reservations: BTreeMap<Ipv6Addr, ReservationV6>,
+}
+
+impl ScopeV6 {
+    pub fn new(prefix: Ipv6Addr, prefix_len: u8) -> Self {
+        Self {
+            prefix,
+            prefix_len,
+            name: String::new(),
+            comment: String::new(),
+            preference: 0,
+            preferred_lifetime: 0,
+            valid_lifetime: 0,
+            dns_servers: Vec::new(),
+            domain_search: Vec::new(),
+            reservations: BTreeMap::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReservationV6 {
+    pub address: Ipv6Addr,
+    #[serde(with = "serde_mac")]
+    pub duid: Vec<u8>,
+    pub iaid: u32,
+    pub name: String,
+    pub comment: String,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum FailoverMode {
+    #[default]
+    LoadBalance,
+    HotStandby,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum FailoverServerType {
+    #[default]
+    Primary,
+    Secondary,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FailoverRelation {
+    pub name: String,
+    pub primary_server: Ipv4Addr,
+    pub secondary_server: Ipv4Addr,
+    pub mode: FailoverMode,
+    pub server_type: FailoverServerType,
+    pub mclt: u32,
+    pub safe_period: u32,
+    pub percentage: u8,
+}
+
+impl FailoverRelation {
+    pub fn partner(&self) -> Ipv4Addr {
+        match self.server_type {
+            FailoverServerType::Primary => self.secondary_server,
+            FailoverServerType::Secondary => self.primary_server,
+        }
+    }
+}
@@ -0,0 +1,155 @@
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+
+use super::netbox::model::NestedTag;
+use super::state::ScopeStats;
+
+#[derive(Debug, Default, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum StatsTarget {
+    #[default]
+    Prefix,
+    Range,
+    Both,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct StatsConfig {
+    enabled: bool,
+    target: StatsTarget,
+    in_use_field: Option<String>,
+    free_field: Option<String>,
+    pending_offers_field: Option<String>,
+    utilisation_field: Option<String>,
+    full_tag: Option<String>,
+    full_threshold: f64,
+}
+
+impl Default for StatsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            target: StatsTarget::Prefix,
+            in_use_field: Some(String::from("dhcp_addresses_in_use")),
+            free_field: Some(String::from("dhcp_addresses_free")),
+            pending_offers_field: Some(String::from("dhcp_pending_offers")),
+            utilisation_field: Some(String::from("dhcp_utilisation")),
+            full_tag: Some(String::from("dhcp-pool-full")),
+            full_threshold: 90.0,
+        }
+    }
+}
+
+impl StatsConfig {
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn prefix(&self) -> bool {
+        self.target != StatsTarget::Range
+    }
+
+    pub fn range(&self) -> bool {
+        self.target != StatsTarget::Prefix
+    }
+
+    /// Custom fields written with the statistics.
+    pub fn custom_fields(&self) -> impl Iterator<Item = &String> {
+        [&self.in_use_field, &self.free_field, &self.pending_offers_field, &self.utilisation_field].into_iter()
+            .flatten()
+    }
+
+    pub fn full_tag(&self) -> Option<&String> {
+        self.full_tag.as_ref()
+    }
+
+    /// Fields to PATCH on a prefix or range with the given custom field values and tags,
+    /// `None` if they are up to date. The PATCH replaces all tags, without the current tags
+    /// they are left alone.
+    pub fn update<'a>(
+        &self,
+        stats: &ScopeStats,
+        custom_field: impl Fn(&str) -> Option<&'a Value>,
+        tags: Option<&[NestedTag]>,
+    ) -> Option<Map<String, Value>> {
+        let utilisation = stats.utilisation();
+        let values = [
+            (&self.in_use_field, json!(stats.in_use)),
+            (&self.free_field, json!(stats.free)),
+            (&self.pending_offers_field, json!(stats.pending_offers)),
+            (&self.utilisation_field, json!(utilisation)),
+        ];
+
+        let custom_fields: Map<String, Value> = values.into_iter()
+            .filter_map(|(field, value)| field.as_ref().map(|field| (field, value)))
+            .filter(|(field, value)| custom_field(field).and_then(Value::as_f64) != value.as_f64())
+            .map(|(field, value)| (field.clone(), value))
+            .collect();
+
+        let mut update = Map::new();
+        if !custom_fields.is_empty() {
+            update.insert(String::from("custom_fields"), Value::Object(custom_fields));
+        }
+
+        if let (Some(full_tag), Some(tags)) = (&self.full_tag, tags) {
+            let full = utilisation >= self.full_threshold;
+            if full != tags.iter().any(|t| &t.slug == full_tag) {
+                let tags: Vec<Value> = tags.iter()
+                    .map(|t| t.slug.as_str())
+                    .filter(|slug| slug != full_tag)
+                    .chain(full.then_some(full_tag.as_str()))
+                    .map(|slug| json!({ "slug": slug }))
+                    .collect();
+                update.insert(String::from("tags"), Value::Array(tags));
+            }
+        }
+
+        (!update.is_empty()).then_some(update)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tag(slug: &str) -> NestedTag {
+        NestedTag { name: slug.to_string(), slug: slug.to_string() }
+    }
+
+    #[test]
+    fn it_calculates_the_utilisation() {
+        assert_eq!(ScopeStats { in_use: 1, free: 2, pending_offers: 0 }.utilisation(), 33.3);
+        assert_eq!(ScopeStats { in_use: 0, free: 0, pending_offers: 0 }.utilisation(), 0.0);
+    }
+
+    #[test]
+    fn it_updates_changed_fields_and_the_full_tag() {
+        let config = serde_yaml_ng::from_str::<StatsConfig>("enabled: true\npending_offers_field: null").unwrap();
+        let stats = ScopeStats { in_use: 95, free: 5, pending_offers: 1 };
+        let current = json!({ "dhcp_addresses_in_use": 95, "dhcp_addresses_free": 10 });
+
+        let update = config.update(&stats, |f| current.get(f), Some(&[tag("dhcp")])).unwrap();
+        assert_eq!(Value::Object(update), json!({
+            "custom_fields": { "dhcp_addresses_free": 5, "dhcp_utilisation": 95.0 },
+            "tags": [{ "slug": "dhcp" }, { "slug": "dhcp-pool-full" }],
+        }));
+
+        let current = json!({ "dhcp_addresses_in_use": 95, "dhcp_addresses_free": 5, "dhcp_utilisation": 95.0 });
+        assert_eq!(config.update(&stats, |f| current.get(f), Some(&[tag("dhcp"), tag("dhcp-pool-full")])), None);
+
+        let stats = ScopeStats { in_use: 50, free: 50, pending_offers: 0 };
+        let update = config.update(&stats, |f| current.get(f), Some(&[tag("dhcp"), tag("dhcp-pool-full")])).unwrap();
+        assert_eq!(update["tags"], json!([{ "slug": "dhcp" }]));
+    }
+
+    #[test]
+    fn it_leaves_tags_alone_without_the_current_tags() {
+        let config = serde_yaml_ng::from_str::<StatsConfig>("enabled: true").unwrap();
+        let stats = ScopeStats { in_use: 95, free: 5, pending_offers: 0 };
+        let current = json!({ "dhcp_addresses_in_use": 95, "dhcp_addresses_free": 5, "dhcp_pending_offers": 0 });
+
+        let update = config.update(&stats, |f| current.get(f), None).unwrap();
+        assert_eq!(Value::Object(update), json!({ "custom_fields": { "dhcp_utilisation": 95.0 } }));
+    }
+}
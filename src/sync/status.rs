@@ -0,0 +1,150 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use chrono::{DateTime, Utc};
+use serde_json::{json, Map, Value};
+
+use super::netbox::journal::{JournalEntry, JournalKind};
+use super::netbox::prefix::Prefix;
+use super::netbox::range::IpRange;
+
+pub const STATUS_FIELD: &str = "dhcp_sync_status";
+pub const SYNCED_AT_FIELD: &str = "dhcp_synced_at";
+
+/// Outcome of the sync for a Netbox prefix or IP address.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ObjectStatus {
+    Ok,
+    Skipped(String),
+    Failed(String),
+}
+
+impl fmt::Display for ObjectStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ObjectStatus::Ok => write!(f, "ok"),
+            ObjectStatus::Skipped(reason) => write!(f, "skipped: {}", reason),
+            ObjectStatus::Failed(error) => write!(f, "failed: {}", error),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct StatusEntry {
+    status: ObjectStatus,
+    current: Option<String>,
+    changed: bool,
+}
+
+/// Status of the synced objects by URL, written back to the `dhcp_sync_status` custom field.
+#[derive(Debug, Default)]
+pub struct StatusReport {
+    objects: BTreeMap<String, StatusEntry>,
+    scopes: Vec<(Prefix, IpRange)>,
+}
+
+impl StatusReport {
+    /// Remembers the prefix and range of a synced IPv4 scope.
+    pub fn add_scope(&mut self, prefix: &Prefix, range: &IpRange) {
+        self.scopes.push((prefix.clone(), range.clone()));
+    }
+
+    pub fn scopes(&self) -> &[(Prefix, IpRange)] {
+        &self.scopes
+    }
+
+    /// Sets the status of an object, `current` is the value of its status field in Netbox.
+    pub fn set(&mut self, url: &str, current: Option<String>, status: ObjectStatus) {
+        if url.is_empty() { return; }
+        self.objects.insert(url.to_string(), StatusEntry { status, current, changed: false });
+    }
+
+    pub fn fail(&mut self, url: &str, error: &str) {
+        if let Some(entry) = self.objects.get_mut(url) {
+            entry.status = ObjectStatus::Failed(error.to_string());
+        }
+    }
+
+    /// Marks an object whose changes were applied to the DHCP server.
+    pub fn changed(&mut self, url: &str) {
+        if let Some(entry) = self.objects.get_mut(url) {
+            entry.changed = true;
+        }
+    }
+
+    /// Status field updates of the objects whose status differs from Netbox or which were changed,
+    /// unchanged objects are not written to keep the Netbox change log quiet.
+    pub fn updates(&self, now: DateTime<Utc>) -> Vec<(String, Map<String, Value>)> {
+        self.objects.iter()
+            .filter(|(_, entry)| entry.changed || entry.current.as_deref() != Some(entry.status.to_string().as_str()))
+            .map(|(url, entry)| {
+                let mut fields = Map::new();
+                fields.insert(String::from(STATUS_FIELD), json!(entry.status.to_string()));
+                if entry.status == ObjectStatus::Ok {
+                    fields.insert(String::from(SYNCED_AT_FIELD), json!(now.to_rfc3339_opts(chrono::SecondsFormat::Secs, true)));
+                }
+                (url.clone(), Map::from_iter([(String::from("custom_fields"), Value::Object(fields))]))
+            })
+            .collect()
+    }
+
+    /// Journal entries for the skipped and failed objects and the entries marking the other objects as resolved.
+    pub fn journal(&self) -> (Vec<JournalEntry>, Vec<JournalEntry>) {
+        let (problems, solved): (Vec<_>, Vec<_>) = self.objects.iter()
+            .partition(|(_, entry)| entry.status != ObjectStatus::Ok);
+
+        let problems = problems.into_iter()
+            .filter_map(|(url, entry)| {
+                let kind = match entry.status {
+                    ObjectStatus::Failed(_) => JournalKind::Danger,
+                    _ => JournalKind::Warning,
+                };
+                JournalEntry::for_url(url, kind, &entry.status.to_string())
+            })
+            .collect();
+        let solved = solved.into_iter()
+            .filter_map(|(url, _)| JournalEntry::for_url(url, JournalKind::Info, "resolved"))
+            .collect();
+
+        (problems, solved)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_writes_changed_statuses() {
+        let now = DateTime::parse_from_rfc3339("2024-05-01T10:00:00Z").unwrap().with_timezone(&Utc);
+        let mut report = StatusReport::default();
+        report.set("https://netbox/api/ipam/prefixes/1/", Some(String::from("ok")), ObjectStatus::Ok);
+        report.set("https://netbox/api/ipam/prefixes/2/", Some(String::from("ok")), ObjectStatus::Ok);
+        report.set("https://netbox/api/ipam/ip-addresses/3/", None, ObjectStatus::Skipped(String::from("no MAC address found")));
+        report.set("https://netbox/api/ipam/ip-addresses/4/", Some(String::from("ok")), ObjectStatus::Ok);
+        report.set("", None, ObjectStatus::Ok);
+        report.changed("https://netbox/api/ipam/prefixes/2/");
+        report.fail("https://netbox/api/ipam/ip-addresses/4/", "Access denied");
+
+        let updates = report.updates(now);
+        assert_eq!(updates, vec![
+            (String::from("https://netbox/api/ipam/ip-addresses/3/"), json!({ "custom_fields": {
+                "dhcp_sync_status": "skipped: no MAC address found",
+            }}).as_object().unwrap().clone()),
+            (String::from("https://netbox/api/ipam/ip-addresses/4/"), json!({ "custom_fields": {
+                "dhcp_sync_status": "failed: Access denied",
+            }}).as_object().unwrap().clone()),
+            (String::from("https://netbox/api/ipam/prefixes/2/"), json!({ "custom_fields": {
+                "dhcp_sync_status": "ok",
+                "dhcp_synced_at": "2024-05-01T10:00:00Z",
+            }}).as_object().unwrap().clone()),
+        ]);
+
+        let (problems, solved) = report.journal();
+        assert_eq!(problems.iter().map(|e| (e.kind, e.comments.as_str())).collect::<Vec<_>>(), vec![
+            (JournalKind::Warning, "netbox-windhcp-sync: skipped: no MAC address found"),
+            (JournalKind::Danger, "netbox-windhcp-sync: failed: Access denied"),
+        ]);
+        assert_eq!(solved.iter().map(|e| e.assigned_object_id).collect::<Vec<_>>(), vec![1, 2]);
+    }
+}
@@ -0,0 +1,144 @@
+/// Renders the placeholders of the template. Whitespace of the template is collapsed and trimmed,
+/// so empty placeholders leave no gaps, the values are inserted unchanged.
+pub fn render<F: Fn(&str) -> Option<String>>(template: &str, lookup: F) -> String {
+    let mut out = Output::default();
+    let mut chars = template.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                out.push_literal('{');
+            },
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                out.push_literal('}');
+            },
+            '{' => {
+                let mut key = String::new();
+                let mut closed = false;
+                for k in chars.by_ref() {
+                    if k == '}' {
+                        closed = true;
+                        break;
+                    }
+                    key.push(k);
+                }
+                match closed {
+                    true => out.push_value(&lookup(key.trim()).unwrap_or_default()),
+                    false => {
+                        out.push_literal('{');
+                        key.chars().for_each(|k| out.push_literal(k));
+                    },
+                }
+            },
+            c => out.push_literal(c),
+        }
+    }
+
+    out.text
+}
+
+/// Keys of the placeholders of the template.
+pub fn placeholders(template: &str) -> Vec<String> {
+    let keys = std::cell::RefCell::new(Vec::new());
+    render(template, |key| {
+        keys.borrow_mut().push(key.to_string());
+        None
+    });
+    keys.into_inner()
+}
+
+#[derive(Default)]
+struct Output {
+    text: String,
+    space: bool,
+}
+
+impl Output {
+    fn push_literal(&mut self, c: char) {
+        match c.is_whitespace() {
+            true => self.space = true,
+            false => {
+                self.separate();
+                self.text.push(c);
+            },
+        }
+    }
+
+    fn push_value(&mut self, value: &str) {
+        if !value.is_empty() {
+            self.separate();
+            self.text.push_str(value);
+        }
+    }
+
+    /// Adds the pending template whitespace as a single space, unless at the start or after whitespace.
+    fn separate(&mut self) {
+        if std::mem::take(&mut self.space) && !self.text.is_empty() && !self.text.ends_with(char::is_whitespace) {
+            self.text.push(' ');
+        }
+    }
+}
+
+/// Cuts the value to `max_length` characters, only a cut value is trimmed.
+pub fn truncate(value: &str, max_length: usize) -> String {
+    match value.chars().count() > max_length {
+        true => value.chars().take(max_length).collect::<String>().trim_end().to_string(),
+        false => value.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lookup(key: &str) -> Option<String> {
+        match key {
+            "site" => Some(String::from("ZRH")),
+            "vlan.vid" => Some(String::from("100")),
+            "description" => Some(String::from("Clients")),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn it_renders_placeholders() {
+        assert_eq!(render("{site} {vlan.vid} {description}", lookup), "ZRH 100 Clients");
+    }
+
+    #[test]
+    fn it_renders_unknown_placeholders_as_empty() {
+        assert_eq!(render("{tenant} {description}", lookup), "Clients");
+    }
+
+    #[test]
+    fn it_keeps_the_whitespace_of_values() {
+        let lookup = |key: &str| (key == "description").then(|| String::from("Clients\n  Floor 2 "));
+        assert_eq!(render("{description}", lookup), "Clients\n  Floor 2 ");
+        assert_eq!(render("  {site}  {description}\n", lookup), "Clients\n  Floor 2 ");
+    }
+
+    #[test]
+    fn it_renders_escaped_braces() {
+        assert_eq!(render("{{{site}}}", lookup), "{ZRH}");
+    }
+
+    #[test]
+    fn it_renders_unterminated_placeholders_literally() {
+        assert_eq!(render("{site", lookup), "{site");
+    }
+
+    #[test]
+    fn it_lists_placeholders() {
+        assert_eq!(placeholders("{{x}} { site } {vlan.vid} {open"), vec!["site", "vlan.vid"]);
+    }
+
+    #[test]
+    fn it_truncates() {
+        assert_eq!(truncate("Clients Zürich", 9), "Clients Z");
+        assert_eq!(truncate("Clients Zürich", 8), "Clients");
+        assert_eq!(truncate("Clients", 255), "Clients");
+        assert_eq!(truncate("Clients ", 255), "Clients ");
+    }
+}
@@ -0,0 +1,154 @@
+use log::trace;
+use std::{net::Ipv4Addr, ptr};
+#[cfg(feature = "rpc_free")]
+use std::os::raw::c_void;
+use windows::{
+    core::{HSTRING, PWSTR},
+    Win32::NetworkManagement::Dhcp::*,
+};
+
+use crate::sync::state::{FailoverMode, FailoverRelation, FailoverServerType};
+
+use super::{WinDhcp, WinDhcpError, WinDhcpResult};
+
+impl From<&DHCP_FAILOVER_RELATIONSHIP> for FailoverRelation {
+    fn from(value: &DHCP_FAILOVER_RELATIONSHIP) -> Self {
+        Self {
+            name: match value.RelationshipName.is_null() {
+                true => String::new(),
+                false => unsafe { value.RelationshipName.to_string() }.unwrap_or_default(),
+            },
+            primary_server: Ipv4Addr::from(value.PrimaryServer),
+            secondary_server: Ipv4Addr::from(value.SecondaryServer),
+            mode: match value.Mode == HotStandby {
+                true => FailoverMode::HotStandby,
+                false => FailoverMode::LoadBalance,
+            },
+            server_type: match value.ServerType == SecondaryServer {
+                true => FailoverServerType::Secondary,
+                false => FailoverServerType::Primary,
+            },
+            mclt: value.Mclt,
+            safe_period: value.SafePeriod,
+            percentage: value.Percentage,
+        }
+    }
+}
+
+fn set_failover_relation(
+    serveripaddress: &HSTRING,
+    relation: &FailoverRelation,
+    server_type: FailoverServerType,
+    shared_secret: Option<&str>,
+    flags: Option<u32>,
+) -> u32 {
+    let mut name = relation.name.encode_utf16().chain([0u16]).collect::<Vec<u16>>();
+    let mut secret = shared_secret.map(|s| s.encode_utf16().chain([0u16]).collect::<Vec<u16>>());
+
+    let prelationship = DHCP_FAILOVER_RELATIONSHIP {
+        PrimaryServer: u32::from(relation.primary_server),
+        SecondaryServer: u32::from(relation.secondary_server),
+        Mode: match relation.mode {
+            FailoverMode::LoadBalance => LoadBalance,
+            FailoverMode::HotStandby => HotStandby,
+        },
+        ServerType: match server_type {
+            FailoverServerType::Primary => PrimaryServer,
+            FailoverServerType::Secondary => SecondaryServer,
+        },
+        State: FSM_STATE(0),
+        PrevState: FSM_STATE(0),
+        Mclt: relation.mclt,
+        SafePeriod: relation.safe_period,
+        RelationshipName: PWSTR(name.as_mut_ptr()),
+        PrimaryServerName: PWSTR::null(),
+        SecondaryServerName: PWSTR::null(),
+        pScopes: &mut DHCP_IP_ARRAY {
+            NumElements: 0,
+            Elements: ptr::null_mut(),
+        },
+        Percentage: relation.percentage,
+        SharedSecret: match secret.as_mut() {
+            Some(secret) => PWSTR(secret.as_mut_ptr()),
+            None => PWSTR::null(),
+        },
+    };
+
+    unsafe {
+        match flags {
+            Some(flags) => {
+                trace!("Call DhcpV4FailoverSetRelationship({}, {}, {})", serveripaddress, flags, relation.name);
+                DhcpV4FailoverSetRelationship(serveripaddress, flags, &prelationship)
+            },
+            None => {
+                trace!("Call DhcpV4FailoverCreateRelationship({}, {})", serveripaddress, relation.name);
+                DhcpV4FailoverCreateRelationship(serveripaddress, &prelationship)
+            },
+        }
+    }
+}
+
+/// Role of the partner server in the relationship.
+fn partner_type(relation: &FailoverRelation) -> FailoverServerType {
+    match relation.server_type {
+        FailoverServerType::Primary => FailoverServerType::Secondary,
+        FailoverServerType::Secondary => FailoverServerType::Primary,
+    }
+}
+
+impl WinDhcp {
+    pub fn get_failover_relation(&self, name: &str) -> WinDhcpResult<Option<FailoverRelation>> {
+        let mut prelationship: *mut DHCP_FAILOVER_RELATIONSHIP = ptr::null_mut();
+
+        let ret = match unsafe {
+            trace!("Call DhcpV4FailoverGetRelationship({}, {}, ptr)", &self.serveripaddress, name);
+            DhcpV4FailoverGetRelationship(&self.serveripaddress, &HSTRING::from(name), &mut prelationship)
+        } {
+            0 => Ok(Some(FailoverRelation::from(unsafe { &*prelationship }))),
+            ERROR_DHCP_FO_RELATIONSHIP_DOES_NOT_EXIST => return Ok(None),
+            e => return Err(WinDhcpError::new("getting failover relationship", e)),
+        };
+
+        #[cfg(feature = "rpc_free")]
+        unsafe {
+            let relationship = &*prelationship;
+            DhcpRpcFreeMemory(relationship.RelationshipName.0 as *mut c_void);
+            DhcpRpcFreeMemory(relationship.PrimaryServerName.0 as *mut c_void);
+            DhcpRpcFreeMemory(relationship.SecondaryServerName.0 as *mut c_void);
+            DhcpRpcFreeMemory(relationship.SharedSecret.0 as *mut c_void);
+            if !relationship.pScopes.is_null() {
+                DhcpRpcFreeMemory((*relationship.pScopes).Elements as *mut c_void);
+                DhcpRpcFreeMemory(relationship.pScopes as *mut c_void);
+            }
+            DhcpRpcFreeMemory(prelationship as *mut c_void);
+        };
+
+        ret
+    }
+
+    /// Creates the relationship on this server and on the partner server.
+    pub fn create_failover_relation(&self, relation: &FailoverRelation, shared_secret: Option<&str>) -> WinDhcpResult<()> {
+        match set_failover_relation(&self.serveripaddress, relation, relation.server_type, shared_secret, None) {
+            0 => (),
+            e => return Err(WinDhcpError::new("creating failover relationship", e)),
+        }
+
+        match set_failover_relation(&HSTRING::from(relation.partner().to_string()), relation, partner_type(relation), shared_secret, None) {
+            0 | ERROR_DHCP_FO_RELATIONSHIP_EXISTS => Ok(()),
+            e => Err(WinDhcpError::new("creating failover relationship on partner", e)),
+        }
+    }
+
+    /// Updates the relationship on this server and on the partner server, so both keep the same settings.
+    pub fn set_failover_relation(&self, relation: &FailoverRelation, flags: u32) -> WinDhcpResult<()> {
+        match set_failover_relation(&self.serveripaddress, relation, relation.server_type, None, Some(flags)) {
+            0 => (),
+            e => return Err(WinDhcpError::new("updating failover relationship", e)),
+        }
+
+        match set_failover_relation(&HSTRING::from(relation.partner().to_string()), relation, partner_type(relation), None, Some(flags)) {
+            0 => Ok(()),
+            e => Err(WinDhcpError::new("updating failover relationship on partner", e)),
+        }
+    }
+}
@@ -1,5 +1,6 @@
 use log::debug;
-use std::net::Ipv4Addr;
+use std::collections::HashMap;
+use std::net::{Ipv4Addr, Ipv6Addr};
 #[cfg(feature = "rpc_free")]
 use std::os::raw::c_void;
 use std::ptr;
@@ -7,10 +8,16 @@ use std::sync::atomic::{AtomicUsize, Ordering};
 use windows::core::{HSTRING, PWSTR};
 use windows::Win32::NetworkManagement::Dhcp::*;
 
+use crate::sync::state::ScopeStats;
+
 static GLOBAL_DHCP_INIT_COUNT: AtomicUsize = AtomicUsize::new(0);
 
 pub mod subnet;
 pub use subnet::*;
+pub mod subnetv6;
+pub use subnetv6::*;
+mod failover;
+mod state;
 pub mod error;
 pub use error::*;
 
@@ -86,6 +93,37 @@ impl WinDhcp {
         Ok(subnets)
     }
 
+    /// Address usage of all IPv4 scopes.
+    pub fn get_scope_stats(&self) -> WinDhcpResult<HashMap<Ipv4Addr, ScopeStats>> {
+        let mut mibinfo: *mut DHCP_MIB_INFO_V5 = ptr::null_mut();
+
+        match unsafe { DhcpGetMibInfoV5(&self.serveripaddress, &mut mibinfo) } {
+            0 => (),
+            e => return Err(WinDhcpError::new("getting scope statistics", e)),
+        }
+
+        let data: DHCP_MIB_INFO_V5 = unsafe { *mibinfo };
+
+        let mut stats = HashMap::with_capacity(data.Scopes.try_into().unwrap());
+
+        for idx in 0..data.Scopes {
+            let scope = unsafe { *data.ScopeInfo.offset(idx.try_into().unwrap()) };
+            stats.insert(Ipv4Addr::from(scope.Subnet), ScopeStats {
+                in_use: scope.NumAddressesInuse,
+                free: scope.NumAddressesFree,
+                pending_offers: scope.NumPendingOffers,
+            });
+        }
+
+        #[cfg(feature = "rpc_free")]
+        unsafe {
+            DhcpRpcFreeMemory((*mibinfo).ScopeInfo as *mut c_void);
+            DhcpRpcFreeMemory(mibinfo as *mut c_void);
+        };
+
+        Ok(stats)
+    }
+
     pub fn get_or_create_subnet(
         &self,
         subnetaddress: &Ipv4Addr,
@@ -117,7 +155,7 @@ impl WinDhcp {
         }
     }
 
-    pub fn get_client_name(&self, clientip: Ipv4Addr) -> Result<String, u32> {
+    pub fn get_client_info(&self, clientip: Ipv4Addr) -> Result<(String, String), u32> {
         let mut clientinfo: *mut DHCP_CLIENT_INFO_V4 = ptr::null_mut();
 
         let searchinfo = DHCP_SEARCH_INFO {
@@ -137,6 +175,10 @@ impl WinDhcp {
             true => String::from(""),
             false => unsafe { info.ClientName.to_string() }.unwrap_or_default(),
         };
+        let comment = match info.ClientComment.is_null() {
+            true => String::from(""),
+            false => unsafe { info.ClientComment.to_string() }.unwrap_or_default(),
+        };
 
         #[cfg(feature = "rpc_free")]
         unsafe {
@@ -148,7 +190,7 @@ impl WinDhcp {
             DhcpRpcFreeMemory(clientinfo as *mut c_void);
         };
 
-        Ok(name)
+        Ok((name, comment))
     }
 
     pub fn set_client_name(&self, clientip: Ipv4Addr, name: &str) -> WinDhcpResult<()> {
@@ -186,7 +228,7 @@ impl WinDhcp {
         ret
     }
 
-    pub fn get_client_comment(&self, clientip: Ipv4Addr) -> Result<String, u32> {
+    pub fn set_client_comment(&self, clientip: Ipv4Addr, comment: &str) -> WinDhcpResult<()> {
         let mut clientinfo: *mut DHCP_CLIENT_INFO_V4 = ptr::null_mut();
 
         let searchinfo = DHCP_SEARCH_INFO {
@@ -194,6 +236,100 @@ impl WinDhcp {
             SearchInfo: DHCP_SEARCH_INFO_0 { ClientIpAddress: u32::from(clientip) },
         };
         match unsafe { DhcpGetClientInfoV4(&self.serveripaddress, &searchinfo, &mut clientinfo) } {
+            0 => (),
+            e => return Err(WinDhcpError::new("setting client name", e)),
+        }
+
+        let mut info = unsafe { *clientinfo };
+
+        let mut wcomment = comment.encode_utf16().chain([0u16]).collect::<Vec<u16>>();
+        info.ClientComment = PWSTR(wcomment.as_mut_ptr());
+
+        let ret = match unsafe { DhcpSetClientInfoV4(&self.serveripaddress, &info) } {
+            0 => Ok(()),
+            e => Err(WinDhcpError::new("setting client name", e)),
+        };
+
+        #[cfg(feature = "rpc_free")]
+        unsafe {
+            DhcpRpcFreeMemory((*clientinfo).ClientName.as_ptr() as *mut c_void);
+            DhcpRpcFreeMemory((*clientinfo).ClientComment.as_ptr() as *mut c_void);
+            DhcpRpcFreeMemory((*clientinfo).ClientHardwareAddress.Data as *mut c_void);
+            DhcpRpcFreeMemory((*clientinfo).OwnerHost.HostName.as_ptr() as *mut c_void);
+            DhcpRpcFreeMemory((*clientinfo).OwnerHost.NetBiosName.as_ptr() as *mut c_void);
+            DhcpRpcFreeMemory(clientinfo as *mut c_void);
+        };
+
+        ret
+    }
+
+    pub fn get_subnets_v6(&self) -> WinDhcpResult<Vec<Ipv6Addr>> {
+        let mut resumehandle: u32 = 0;
+        let mut elementsread: u32 = 0;
+        let mut elementstotal: u32 = 0;
+
+        let mut enuminfo: *mut DHCPV6_IP_ARRAY = ptr::null_mut();
+
+        match unsafe {
+            DhcpEnumSubnetsV6(
+                &self.serveripaddress,
+                &mut resumehandle,
+                0xFFFFFFFF,
+                &mut enuminfo,
+                &mut elementsread,
+                &mut elementstotal,
+            )
+        } {
+            0 => (),
+            //ERROR_NO_MORE_ITEMS
+            259 => {
+                return Ok(Vec::new());
+            }
+            e => {
+                return Err(WinDhcpError::new("listing v6 subnets", e));
+            }
+        }
+
+        let data: DHCPV6_IP_ARRAY = unsafe { *enuminfo };
+
+        let mut subnets = Vec::with_capacity(data.NumElements.try_into().unwrap());
+
+        for idx in 0..data.NumElements {
+            subnets.push(from_dhcp_ipv6(unsafe { &*data.Elements.offset(idx.try_into().unwrap()) }));
+        }
+
+        #[cfg(feature = "rpc_free")]
+        unsafe {
+            DhcpRpcFreeMemory((*enuminfo).Elements as *mut c_void);
+            DhcpRpcFreeMemory(enuminfo as *mut c_void);
+        };
+
+        Ok(subnets)
+    }
+
+    pub fn get_or_create_subnet_v6(&self, prefix: &Ipv6Addr, prefix_len: u8) -> Result<SubnetV6, u32> {
+        match SubnetV6::get(&self.serveripaddress, prefix)? {
+            Some(subnet) => Ok(subnet),
+            None => SubnetV6::create(&self.serveripaddress, prefix, prefix_len),
+        }
+    }
+
+    pub fn get_subnet_v6(&self, prefix: &Ipv6Addr) -> Result<Option<SubnetV6>, u32> {
+        SubnetV6::get(&self.serveripaddress, prefix)
+    }
+
+    pub fn remove_subnet_v6(&self, prefix: Ipv6Addr) -> WinDhcpResult<()> {
+        SubnetV6::remove(&self.serveripaddress, prefix)
+    }
+
+    pub fn get_client_info_v6(&self, clientip: Ipv6Addr) -> Result<(String, String), u32> {
+        let mut clientinfo: *mut DHCP_CLIENT_INFO_V6 = ptr::null_mut();
+
+        let searchinfo = DHCP_SEARCH_INFO_V6 {
+            SearchType: Dhcpv6ClientIpAddress,
+            SearchInfo: DHCP_SEARCH_INFO_V6_0 { ClientIpAddress: to_dhcp_ipv6(&clientip) },
+        };
+        match unsafe { DhcpGetClientInfoV6(&self.serveripaddress, &searchinfo, &mut clientinfo) } {
             0 => (),
             n => {
                 return Err(n);
@@ -202,7 +338,11 @@ impl WinDhcp {
 
         let info = unsafe { *clientinfo };
 
-        let name = match info.ClientComment.is_null() {
+        let name = match info.ClientName.is_null() {
+            true => String::from(""),
+            false => unsafe { info.ClientName.to_string() }.unwrap_or_default(),
+        };
+        let comment = match info.ClientComment.is_null() {
             true => String::from(""),
             false => unsafe { info.ClientComment.to_string() }.unwrap_or_default(),
         };
@@ -211,42 +351,48 @@ impl WinDhcp {
         unsafe {
             DhcpRpcFreeMemory((*clientinfo).ClientName.as_ptr() as *mut c_void);
             DhcpRpcFreeMemory((*clientinfo).ClientComment.as_ptr() as *mut c_void);
-            DhcpRpcFreeMemory((*clientinfo).ClientHardwareAddress.Data as *mut c_void);
+            DhcpRpcFreeMemory((*clientinfo).ClientDUID.Data as *mut c_void);
             DhcpRpcFreeMemory((*clientinfo).OwnerHost.HostName.as_ptr() as *mut c_void);
             DhcpRpcFreeMemory((*clientinfo).OwnerHost.NetBiosName.as_ptr() as *mut c_void);
             DhcpRpcFreeMemory(clientinfo as *mut c_void);
         };
 
-        Ok(name)
+        Ok((name, comment))
     }
 
-    pub fn set_client_comment(&self, clientip: Ipv4Addr, comment: &str) -> WinDhcpResult<()> {
-        let mut clientinfo: *mut DHCP_CLIENT_INFO_V4 = ptr::null_mut();
+    pub fn set_client_info_v6(&self, clientip: Ipv6Addr, name: Option<&str>, comment: Option<&str>) -> WinDhcpResult<()> {
+        let mut clientinfo: *mut DHCP_CLIENT_INFO_V6 = ptr::null_mut();
 
-        let searchinfo = DHCP_SEARCH_INFO {
-            SearchType: DHCP_SEARCH_INFO_TYPE(0),
-            SearchInfo: DHCP_SEARCH_INFO_0 { ClientIpAddress: u32::from(clientip) },
+        let searchinfo = DHCP_SEARCH_INFO_V6 {
+            SearchType: Dhcpv6ClientIpAddress,
+            SearchInfo: DHCP_SEARCH_INFO_V6_0 { ClientIpAddress: to_dhcp_ipv6(&clientip) },
         };
-        match unsafe { DhcpGetClientInfoV4(&self.serveripaddress, &searchinfo, &mut clientinfo) } {
+        match unsafe { DhcpGetClientInfoV6(&self.serveripaddress, &searchinfo, &mut clientinfo) } {
             0 => (),
-            e => return Err(WinDhcpError::new("setting client name", e)),
+            e => return Err(WinDhcpError::new("setting v6 client info", e)),
         }
 
         let mut info = unsafe { *clientinfo };
 
-        let mut wcomment = comment.encode_utf16().chain([0u16]).collect::<Vec<u16>>();
-        info.ClientComment = PWSTR(wcomment.as_mut_ptr());
+        let mut wname = name.map(|n| n.encode_utf16().chain([0u16]).collect::<Vec<u16>>());
+        if let Some(wname) = wname.as_mut() {
+            info.ClientName = PWSTR(wname.as_mut_ptr());
+        }
+        let mut wcomment = comment.map(|c| c.encode_utf16().chain([0u16]).collect::<Vec<u16>>());
+        if let Some(wcomment) = wcomment.as_mut() {
+            info.ClientComment = PWSTR(wcomment.as_mut_ptr());
+        }
 
-        let ret = match unsafe { DhcpSetClientInfoV4(&self.serveripaddress, &info) } {
+        let ret = match unsafe { DhcpSetClientInfoV6(&self.serveripaddress, &info) } {
             0 => Ok(()),
-            e => Err(WinDhcpError::new("setting client name", e)),
+            e => Err(WinDhcpError::new("setting v6 client info", e)),
         };
 
         #[cfg(feature = "rpc_free")]
         unsafe {
             DhcpRpcFreeMemory((*clientinfo).ClientName.as_ptr() as *mut c_void);
             DhcpRpcFreeMemory((*clientinfo).ClientComment.as_ptr() as *mut c_void);
-            DhcpRpcFreeMemory((*clientinfo).ClientHardwareAddress.Data as *mut c_void);
+            DhcpRpcFreeMemory((*clientinfo).ClientDUID.Data as *mut c_void);
             DhcpRpcFreeMemory((*clientinfo).OwnerHost.HostName.as_ptr() as *mut c_void);
             DhcpRpcFreeMemory((*clientinfo).OwnerHost.NetBiosName.as_ptr() as *mut c_void);
             DhcpRpcFreeMemory(clientinfo as *mut c_void);
@@ -0,0 +1,206 @@
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+use crate::sync::plan::{Change, FailoverChange, ScopeV4Change, ScopeV6Change};
+use crate::sync::state::{DhcpState, FailoverRelation, ReservationV4, ReservationV6, ScopeV4, ScopeV6};
+
+use windows::Win32::NetworkManagement::Dhcp::{ERROR_DHCP_FO_RELATIONSHIP_DOES_NOT_EXIST, ERROR_DHCP_SUBNET_NOT_PRESENT, MCLT, MODE, PERCENTAGE, SAFEPERIOD};
+
+use super::{Subnet, SubnetV6, WinDhcp, WinDhcpError, WinDhcpResult};
+
+impl WinDhcp {
+    pub fn get_scope_v4(&self, subnetaddress: &Ipv4Addr) -> WinDhcpResult<Option<ScopeV4>> {
+        let subnet = match self.get_subnet(subnetaddress)
+            .map_err(|e| WinDhcpError::new("getting subnet", e))? {
+            Some(subnet) => subnet,
+            None => return Ok(None),
+        };
+
+        let mut scope = ScopeV4::new(*subnetaddress, subnet.subnet_mask);
+        scope.name = subnet.subnet_name.clone();
+        scope.comment = subnet.subnet_comment.clone();
+        scope.range = subnet.get_subnet_range()?;
+        scope.lease_duration = subnet.get_lease_duration()?;
+        scope.dns_flags = subnet.get_dns_flags()?;
+        scope.routers = subnet.get_routers()?;
+        scope.dns_domain = subnet.get_dns_domain()?;
+        scope.dns_servers = subnet.get_dns_servers()?;
+        scope.failover = subnet.get_failover_relationship()
+            .map_err(|e| WinDhcpError::new("getting failover relationship", e))?;
+
+        let reservations = subnet.get_reservations()
+            .map_err(|e| WinDhcpError::new("getting reservations", e))?;
+        for (address, reservation) in reservations {
+            let (name, comment) = self.get_client_info(address)
+                .map_err(|e| WinDhcpError::new("getting client info", e))?;
+            scope.reservations.insert(address, ReservationV4 {
+                address,
+                mac: reservation.for_client,
+                client_type: reservation.allowed_client_types,
+                name,
+                comment,
+            });
+        }
+
+        Ok(Some(scope))
+    }
+
+    pub fn get_scope_v6(&self, prefix: &Ipv6Addr) -> WinDhcpResult<Option<ScopeV6>> {
+        let subnet = match self.get_subnet_v6(prefix)
+            .map_err(|e| WinDhcpError::new("getting v6 subnet", e))? {
+            Some(subnet) => subnet,
+            None => return Ok(None),
+        };
+
+        let mut scope = ScopeV6::new(*prefix, subnet.prefix_len);
+        scope.name = subnet.subnet_name.clone();
+        scope.comment = subnet.subnet_comment.clone();
+        scope.preference = subnet.preference;
+        (scope.preferred_lifetime, scope.valid_lifetime) = subnet.get_lifetimes()?;
+        scope.dns_servers = subnet.get_dns_servers()?;
+        scope.domain_search = subnet.get_domain_search()?;
+
+        for reservation in subnet.get_reservations()? {
+            let (name, comment) = self.get_client_info_v6(reservation.ip_address)
+                .map_err(|e| WinDhcpError::new("getting v6 client info", e))?;
+            scope.reservations.insert(reservation.ip_address, ReservationV6 {
+                address: reservation.ip_address,
+                duid: reservation.duid,
+                iaid: reservation.iaid,
+                name,
+                comment,
+            });
+        }
+
+        Ok(Some(scope))
+    }
+
+    pub fn get_exclusions(&self, subnetaddress: &Ipv4Addr) -> WinDhcpResult<Vec<(Ipv4Addr, Ipv4Addr)>> {
+        self.existing_subnet(subnetaddress)?.get_exclusions()
+    }
+
+    fn existing_subnet(&self, subnetaddress: &Ipv4Addr) -> WinDhcpResult<Subnet> {
+        match self.get_subnet(subnetaddress) {
+            Ok(Some(subnet)) => Ok(subnet),
+            Ok(None) => Err(WinDhcpError::new("getting subnet", ERROR_DHCP_SUBNET_NOT_PRESENT)),
+            Err(e) => Err(WinDhcpError::new("getting subnet", e)),
+        }
+    }
+
+    fn existing_subnet_v6(&self, prefix: &Ipv6Addr) -> WinDhcpResult<SubnetV6> {
+        match self.get_subnet_v6(prefix) {
+            Ok(Some(subnet)) => Ok(subnet),
+            Ok(None) => Err(WinDhcpError::new("getting v6 subnet", ERROR_DHCP_SUBNET_NOT_PRESENT)),
+            Err(e) => Err(WinDhcpError::new("getting v6 subnet", e)),
+        }
+    }
+
+    pub fn apply(&self, change: &Change) -> WinDhcpResult<()> {
+        match change {
+            Change::V4(subnetaddress, change) => self.apply_v4(subnetaddress, change),
+            Change::V6(prefix, change) => self.apply_v6(prefix, change),
+            Change::Failover(name, change) => self.apply_failover(name, change),
+        }
+    }
+
+    fn apply_failover(&self, name: &str, change: &FailoverChange) -> WinDhcpResult<()> {
+        if let FailoverChange::Create { relation, shared_secret } = change {
+            return self.create_failover_relation(relation, shared_secret.as_deref());
+        }
+
+        let mut relation = match self.get_failover_relation(name)? {
+            Some(relation) => relation,
+            None => return Err(WinDhcpError::new("updating failover relationship", ERROR_DHCP_FO_RELATIONSHIP_DOES_NOT_EXIST)),
+        };
+
+        let flags = match change {
+            FailoverChange::Create { .. } => 0,
+            FailoverChange::Mode { new, .. } => { relation.mode = *new; MODE },
+            FailoverChange::Mclt { new, .. } => { relation.mclt = *new; MCLT },
+            FailoverChange::SafePeriod { new, .. } => { relation.safe_period = *new; SAFEPERIOD },
+            FailoverChange::Percentage { new, .. } => { relation.percentage = *new; PERCENTAGE },
+        };
+
+        self.set_failover_relation(&relation, flags)
+    }
+
+    fn apply_v4(&self, subnetaddress: &Ipv4Addr, change: &ScopeV4Change) -> WinDhcpResult<()> {
+        match change {
+            ScopeV4Change::Create { mask } => self.get_or_create_subnet(subnetaddress, mask)
+                .map(|_| ())
+                .map_err(|e| WinDhcpError::new("creating subnet", e)),
+            ScopeV4Change::Remove => self.remove_subnet(*subnetaddress),
+            ScopeV4Change::Mask { new, .. } => self.existing_subnet(subnetaddress)?.set_mask(*new),
+            ScopeV4Change::Name { new, .. } => self.existing_subnet(subnetaddress)?.set_name(new),
+            ScopeV4Change::Comment { new, .. } => self.existing_subnet(subnetaddress)?.set_comment(new),
+            ScopeV4Change::Range { new, .. } => self.existing_subnet(subnetaddress)?.set_subnet_range(new.0, new.1),
+            ScopeV4Change::LeaseDuration { new, .. } => self.existing_subnet(subnetaddress)?.set_lease_duration(*new),
+            ScopeV4Change::DnsFlags { new, .. } => self.existing_subnet(subnetaddress)?.set_dns_flags(new.as_ref()),
+            ScopeV4Change::Routers { new, .. } => self.existing_subnet(subnetaddress)?.set_routers(new),
+            ScopeV4Change::DnsDomain { new, .. } => self.existing_subnet(subnetaddress)?.set_dns_domain(new.as_ref()),
+            ScopeV4Change::DnsServers { new, .. } => self.existing_subnet(subnetaddress)?.set_dns_servers(new),
+            ScopeV4Change::Failover { old, new } => {
+                let subnet = self.existing_subnet(subnetaddress)?;
+                if let Some(old) = old {
+                    subnet.remove_failover_relationship(old)
+                        .map_err(|e| WinDhcpError::new("removing failover relationship", e))?;
+                }
+                if let Some(new) = new {
+                    subnet.add_failover_relationship(new)
+                        .map_err(|e| WinDhcpError::new("adding failover relationship", e))?;
+                }
+                Ok(())
+            },
+            ScopeV4Change::AddExclusion(range) => self.existing_subnet(subnetaddress)?.add_exclusion(range.0, range.1),
+            ScopeV4Change::RemoveExclusion(range) => self.existing_subnet(subnetaddress)?.remove_exclusion(range.0, range.1),
+            ScopeV4Change::AddReservation(reservation) => self.existing_subnet(subnetaddress)?
+                .add_reservation(reservation.address, &reservation.mac, reservation.client_type),
+            ScopeV4Change::RemoveReservation(reservation) => self.existing_subnet(subnetaddress)?
+                .remove_reservation(reservation.address, &reservation.mac),
+            ScopeV4Change::ReservationName { address, new, .. } => self.set_client_name(*address, new),
+            ScopeV4Change::ReservationComment { address, new, .. } => self.set_client_comment(*address, new),
+        }
+    }
+
+    fn apply_v6(&self, prefix: &Ipv6Addr, change: &ScopeV6Change) -> WinDhcpResult<()> {
+        match change {
+            ScopeV6Change::Create { prefix_len } => self.get_or_create_subnet_v6(prefix, *prefix_len)
+                .map(|_| ())
+                .map_err(|e| WinDhcpError::new("creating v6 subnet", e)),
+            ScopeV6Change::Remove => self.remove_subnet_v6(*prefix),
+            ScopeV6Change::Name { new, .. } => self.existing_subnet_v6(prefix)?.set_name(new),
+            ScopeV6Change::Comment { new, .. } => self.existing_subnet_v6(prefix)?.set_comment(new),
+            ScopeV6Change::Preference { new, .. } => self.existing_subnet_v6(prefix)?.set_preference(*new),
+            ScopeV6Change::Lifetimes { new, .. } => self.existing_subnet_v6(prefix)?.set_lifetimes(new.0, new.1),
+            ScopeV6Change::DnsServers { new, .. } => self.existing_subnet_v6(prefix)?.set_dns_servers(new),
+            ScopeV6Change::DomainSearch { new, .. } => self.existing_subnet_v6(prefix)?.set_domain_search(new),
+            ScopeV6Change::AddReservation(reservation) => self.existing_subnet_v6(prefix)?
+                .add_reservation(reservation.address, &reservation.duid, reservation.iaid),
+            ScopeV6Change::RemoveReservation(reservation) => self.existing_subnet_v6(prefix)?
+                .remove_reservation(reservation.address, &reservation.duid, reservation.iaid),
+            ScopeV6Change::ReservationName { address, new, .. } => self.set_client_info_v6(*address, Some(new), None),
+            ScopeV6Change::ReservationComment { address, new, .. } => self.set_client_info_v6(*address, None, Some(new)),
+        }
+    }
+}
+
+impl DhcpState for WinDhcp {
+    fn subnets_v4(&self) -> Result<Vec<Ipv4Addr>, Box<dyn std::error::Error + Send + std::marker::Sync>> {
+        Ok(self.get_subnets()?)
+    }
+
+    fn subnets_v6(&self) -> Result<Vec<Ipv6Addr>, Box<dyn std::error::Error + Send + std::marker::Sync>> {
+        Ok(self.get_subnets_v6()?)
+    }
+
+    fn scope_v4(&self, subnet: &Ipv4Addr) -> Result<Option<ScopeV4>, Box<dyn std::error::Error + Send + std::marker::Sync>> {
+        Ok(self.get_scope_v4(subnet)?)
+    }
+
+    fn scope_v6(&self, prefix: &Ipv6Addr) -> Result<Option<ScopeV6>, Box<dyn std::error::Error + Send + std::marker::Sync>> {
+        Ok(self.get_scope_v6(prefix)?)
+    }
+
+    fn failover_relation(&self, name: &str) -> Result<Option<FailoverRelation>, Box<dyn std::error::Error + Send + std::marker::Sync>> {
+        Ok(self.get_failover_relation(name)?)
+    }
+}
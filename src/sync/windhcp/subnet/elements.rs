@@ -1,6 +1,6 @@
 use std::{os::raw::c_void, ptr};
 
-use windows::Win32::NetworkManagement::Dhcp::{DhcpRpcFreeMemory, DHCP_BOOTP_IP_RANGE, DHCP_SUBNET_ELEMENT_INFO_ARRAY_V5, DhcpEnumSubnetElementsV5, DhcpIpRangesDhcpBootp, DHCP_SUBNET_ELEMENT_DATA_V5, DHCP_SUBNET_ELEMENT_DATA_V5_0, DhcpAddSubnetElementV5, DhcpReservedIps, DHCP_IP_RESERVATION_V4, DHCP_BINARY_DATA, DhcpRemoveSubnetElementV5, DhcpFullForce};
+use windows::Win32::NetworkManagement::Dhcp::{DhcpRpcFreeMemory, DHCP_BOOTP_IP_RANGE, DHCP_SUBNET_ELEMENT_INFO_ARRAY_V5, DhcpEnumSubnetElementsV5, DhcpIpRangesDhcpBootp, DHCP_SUBNET_ELEMENT_DATA_V5, DHCP_SUBNET_ELEMENT_DATA_V5_0, DhcpAddSubnetElementV5, DhcpReservedIps, DHCP_IP_RESERVATION_V4, DHCP_BINARY_DATA, DhcpRemoveSubnetElementV5, DhcpFullForce, DhcpExcludedIpRanges, DHCP_IP_RANGE};
 
 use super::Subnet;
 use super::reservation::Reservation;
@@ -76,6 +76,79 @@ impl SubnetElements<DHCP_BOOTP_IP_RANGE> for Subnet {
     }
 }
 
+impl SubnetElements<DHCP_IP_RANGE> for Subnet {
+    fn get_elements(&self) -> Result<Vec<DHCP_IP_RANGE>, u32> {
+        let mut resumehandle: u32 = 0;
+        let mut elementsread: u32 = 0;
+        let mut elementstotal: u32 = 0;
+
+        let mut enumelementinfo: *mut DHCP_SUBNET_ELEMENT_INFO_ARRAY_V5 = ptr::null_mut();
+
+        match unsafe {
+            DhcpEnumSubnetElementsV5(
+                &self.serveripaddress,
+                self.subnetaddress,
+                DhcpExcludedIpRanges,
+                &mut resumehandle,
+                0xFFFFFFFF,
+                &mut enumelementinfo,
+                &mut elementsread,
+                &mut elementstotal,
+            )
+        } {
+            0 => {
+                let mut elements = Vec::new();
+
+                unsafe {
+                    for idx in 0usize..(*enumelementinfo).NumElements.try_into().unwrap() {
+                        let range = (*(*enumelementinfo).Elements.offset(idx.try_into().unwrap())).Element.ExcludeIpRange;
+                        elements.push(DHCP_IP_RANGE { ..*range });
+                        DhcpRpcFreeMemory(range as *mut c_void);
+                    }
+                    DhcpRpcFreeMemory((*enumelementinfo).Elements as *mut c_void);
+                    DhcpRpcFreeMemory(enumelementinfo as *mut c_void);
+                }
+
+                Ok(elements)
+            },
+            //ERROR_NO_MORE_ITEMS
+            259 => {
+                Ok(vec![])
+            }
+            n => {
+                Err(n)
+            }
+        }
+    }
+
+    fn add_element(&self, element: &mut DHCP_IP_RANGE) -> Result<(), u32> {
+        let addelementinfo = DHCP_SUBNET_ELEMENT_DATA_V5 {
+            ElementType: DhcpExcludedIpRanges,
+            Element: DHCP_SUBNET_ELEMENT_DATA_V5_0 {
+                ExcludeIpRange: element,
+            },
+        };
+
+        match unsafe { DhcpAddSubnetElementV5(&self.serveripaddress, self.subnetaddress, &addelementinfo) } {
+            0 => Ok(()),
+            n => Err(n),
+        }
+    }
+
+    fn remove_element(&self, element: &mut DHCP_IP_RANGE) -> Result<(), u32> {
+        let removeelementinfo = DHCP_SUBNET_ELEMENT_DATA_V5 {
+            ElementType: DhcpExcludedIpRanges,
+            Element: DHCP_SUBNET_ELEMENT_DATA_V5_0 {
+                ExcludeIpRange: element,
+            },
+        };
+
+        match unsafe { DhcpRemoveSubnetElementV5(&self.serveripaddress, self.subnetaddress, &removeelementinfo, DhcpFullForce) } {
+            0 => Ok(()),
+            n => Err(n),
+        }
+    }
+}
 
 impl SubnetElements<Reservation> for Subnet {
     fn get_elements(&self) -> Result<Vec<Reservation>, u32> {
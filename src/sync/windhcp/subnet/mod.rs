@@ -1,13 +1,13 @@
 use ipnet::Ipv4Net;
 use log::{info, trace};
-use serde::Deserialize;
-use std::{collections::HashMap, fmt, net::Ipv4Addr, ptr};
+use std::{collections::HashMap, net::Ipv4Addr, ptr};
 use windows::{
     core::{HSTRING, PCWSTR, PWSTR},
     Win32::NetworkManagement::Dhcp::*,
 };
 
 use super::{WinDhcpError, WinDhcpResult};
+pub use crate::sync::state::DnsFlags;
 
 mod options;
 use self::options::*;
@@ -207,6 +207,31 @@ impl Subnet {
         Ok(())
     }
 
+    pub fn get_exclusions(&self) -> WinDhcpResult<Vec<(Ipv4Addr, Ipv4Addr)>> {
+        match SubnetElements::<DHCP_IP_RANGE>::get_elements(self) {
+            Ok(ranges) => Ok(ranges.iter()
+                .map(|r| (Ipv4Addr::from(r.StartAddress), Ipv4Addr::from(r.EndAddress)))
+                .collect()),
+            Err(e) => Err(WinDhcpError::new("getting exclusion ranges", e)),
+        }
+    }
+
+    pub fn add_exclusion(&self, start_address: Ipv4Addr, end_address: Ipv4Addr) -> WinDhcpResult<()> {
+        let mut range = DHCP_IP_RANGE { StartAddress: start_address.into(), EndAddress: end_address.into() };
+        match SubnetElements::<DHCP_IP_RANGE>::add_element(self, &mut range) {
+            Ok(_) => Ok(()),
+            Err(e) => Err(WinDhcpError::new("adding exclusion range", e)),
+        }
+    }
+
+    pub fn remove_exclusion(&self, start_address: Ipv4Addr, end_address: Ipv4Addr) -> WinDhcpResult<()> {
+        let mut range = DHCP_IP_RANGE { StartAddress: start_address.into(), EndAddress: end_address.into() };
+        match SubnetElements::<DHCP_IP_RANGE>::remove_element(self, &mut range) {
+            Ok(_) => Ok(()),
+            Err(e) => Err(WinDhcpError::new("removing exclusion range", e)),
+        }
+    }
+
     pub fn get_lease_duration(&self) -> WinDhcpResult<Option<u32>> {
         self.get_option(OPTION_LEASE_TIME)
     }
@@ -266,11 +291,12 @@ impl Subnet {
         &self,
         reservationaddress: Ipv4Addr,
         macaddress: &[u8],
+        client_type: ReservationClientTypes,
     ) -> WinDhcpResult<()> {
         let mut reservation = Reservation {
             ip_address: reservationaddress,
             for_client: macaddress.to_owned(),
-            allowed_client_types: ReservationClientTypes::Both,
+            allowed_client_types: client_type,
         };
         match self.add_element(&mut reservation) {
             Ok(_) => Ok(()),
@@ -391,47 +417,6 @@ impl Subnet {
     }
 }
 
-#[derive(Debug, Default, Deserialize, Clone, PartialEq, Eq)]
-pub struct DnsFlags {
-    #[serde(default)]
-    pub enabled: bool,
-    #[serde(default)]
-    pub update_downlevel: bool,
-    #[serde(default)]
-    pub cleanup_expired: bool,
-    #[serde(default)]
-    pub update_both_always: bool,
-    #[serde(default)]
-    pub update_dhcid: bool,
-    #[serde(default)]
-    pub disable_ptr_update: bool,
-}
-
-impl fmt::Display for DnsFlags {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        if self.enabled { write!(f, "Enabled")? } else { write!(f, "Disabled")? }
-        if self.update_downlevel { write!(f, ", Update Downlevel")? }
-        if self.cleanup_expired { write!(f, ", Cleanup Expired")? }
-        if self.update_both_always { write!(f, ", Update Both always")? }
-        if self.update_dhcid { write!(f, ", Update DHCID")? }
-        if self.disable_ptr_update { write!(f, ", Disable PTR update")? }
-        Ok(())
-    }
-}
-
-impl From<&Vec<String>> for DnsFlags {
-    fn from(flags: &Vec<String>) -> Self {
-        Self {
-            enabled: flags.contains(&String::from("enabled")),
-            update_downlevel: flags.contains(&String::from("update_downlevel")),
-            cleanup_expired: flags.contains(&String::from("cleanup_expired")),
-            update_both_always: flags.contains(&String::from("update_both_always")),
-            update_dhcid: flags.contains(&String::from("update_dhcid")),
-            disable_ptr_update: flags.contains(&String::from("disable_ptr_update")),
-        }
-    }
-}
-
 impl From<u32> for DnsFlags {
     fn from(flags: u32) -> Self {
         Self {
@@ -2,33 +2,7 @@ use std::{net::Ipv4Addr};
 
 use windows::Win32::NetworkManagement::Dhcp::DHCP_IP_RESERVATION_V4;
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
-#[repr(u8)]
-pub enum ReservationClientTypes {
-    Dhcp = 1,
-    Bootp = 2,
-    Both = 3
-}
-
-impl From<u8> for ReservationClientTypes {
-    fn from(value: u8) -> Self {
-        match value {
-            1 => ReservationClientTypes::Dhcp,
-            2 => ReservationClientTypes::Bootp,
-            _n => ReservationClientTypes::Both,
-        }
-    }
-}
-
-impl From<ReservationClientTypes> for u8 {
-    fn from(value: ReservationClientTypes) -> Self {
-        match value {
-            ReservationClientTypes::Dhcp => 1,
-            ReservationClientTypes::Bootp => 2,
-            ReservationClientTypes::Both => 3,
-        }
-    }
-}
+pub use crate::sync::state::ReservationClientTypes;
 
 #[derive(Debug, PartialEq, Eq)]
 pub struct Reservation {
@@ -0,0 +1,422 @@
+use log::trace;
+use std::{net::Ipv6Addr, ptr};
+#[cfg(feature = "rpc_free")]
+use std::os::raw::c_void;
+use windows::{
+    core::{HSTRING, PCWSTR, PWSTR},
+    Win32::NetworkManagement::Dhcp::*,
+};
+
+use super::{WinDhcpError, WinDhcpResult};
+
+pub const OPTION_V6_DNS_SERVERS: u32 = 23;
+pub const OPTION_V6_DOMAIN_LIST: u32 = 24;
+
+pub fn to_dhcp_ipv6(address: &Ipv6Addr) -> DHCP_IPV6_ADDRESS {
+    let bits = u128::from(*address);
+    DHCP_IPV6_ADDRESS {
+        HighOrderBits: (bits >> 64) as u64,
+        LowOrderBits: bits as u64,
+    }
+}
+
+pub fn from_dhcp_ipv6(address: &DHCP_IPV6_ADDRESS) -> Ipv6Addr {
+    Ipv6Addr::from(((address.HighOrderBits as u128) << 64) | address.LowOrderBits as u128)
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct ReservationV6Info {
+    pub ip_address: Ipv6Addr,
+    pub duid: Vec<u8>,
+    pub iaid: u32,
+}
+
+#[derive(Debug)]
+pub struct SubnetV6 {
+    serveripaddress: HSTRING,
+    pub prefix: Ipv6Addr,
+    pub prefix_len: u8,
+    pub preference: u16,
+    pub subnet_name: String,
+    pub subnet_comment: String,
+}
+
+impl SubnetV6 {
+    fn subnetaddress(&self) -> DHCP_IPV6_ADDRESS {
+        to_dhcp_ipv6(&self.prefix)
+    }
+
+    fn scopeinfo(&self) -> DHCP_OPTION_SCOPE_INFO6 {
+        DHCP_OPTION_SCOPE_INFO6 {
+            ScopeType: DhcpScopeOptions6,
+            ScopeInfo: DHCP_OPTION_SCOPE_INFO6_0 { SubnetScopeInfo: self.subnetaddress() },
+        }
+    }
+
+    fn get_subnet_info(&self) -> Result<DHCP_SUBNET_INFO_V6, u32> {
+        let mut subnetinfo: *mut DHCP_SUBNET_INFO_V6 = ptr::null_mut();
+
+        match unsafe {
+            trace!("Call DhcpGetSubnetInfoV6({}, {}, ptr)", &self.serveripaddress, self.prefix);
+            DhcpGetSubnetInfoV6(&self.serveripaddress, self.subnetaddress(), &mut subnetinfo)
+        } {
+            0 => Ok(unsafe { *subnetinfo }),
+            n => Err(n),
+        }
+    }
+
+    fn set_subnet_info(&self, mut subnetinfo: DHCP_SUBNET_INFO_V6) -> Result<(), u32> {
+        match unsafe { DhcpSetSubnetInfoV6(&self.serveripaddress, self.subnetaddress(), &mut subnetinfo) } {
+            0 => Ok(()),
+            n => Err(n),
+        }
+    }
+
+    pub fn get(serveripaddress: &HSTRING, prefix: &Ipv6Addr) -> Result<Option<Self>, u32> {
+        let mut subnetinfo: *mut DHCP_SUBNET_INFO_V6 = ptr::null_mut();
+
+        let ret = match unsafe { DhcpGetSubnetInfoV6(serveripaddress, to_dhcp_ipv6(prefix), &mut subnetinfo) } {
+            0 => {
+                let info = unsafe { *subnetinfo };
+                let subnet_name = match info.SubnetName.is_null() {
+                    true => String::default(),
+                    false => unsafe { info.SubnetName.to_string() }.unwrap_or_default(),
+                };
+                let subnet_comment = match info.SubnetComment.is_null() {
+                    true => String::default(),
+                    false => unsafe { info.SubnetComment.to_string() }.unwrap_or_default(),
+                };
+
+                Ok(Some(Self {
+                    serveripaddress: serveripaddress.clone(),
+                    prefix: *prefix,
+                    prefix_len: info.Prefix as u8,
+                    preference: info.Preference,
+                    subnet_name,
+                    subnet_comment,
+                }))
+            },
+            ERROR_DHCP_SUBNET_NOT_PRESENT => Ok(None),
+            n => Err(n),
+        };
+
+        #[cfg(feature = "rpc_free")]
+        unsafe { DhcpRpcFreeMemory(subnetinfo as *mut c_void) };
+
+        ret
+    }
+
+    pub fn create(serveripaddress: &HSTRING, prefix: &Ipv6Addr, prefix_len: u8) -> Result<Self, u32> {
+        let mut subnetinfo = DHCP_SUBNET_INFO_V6 {
+            SubnetAddress: to_dhcp_ipv6(prefix),
+            Prefix: prefix_len.into(),
+            Preference: 0,
+            SubnetName: PWSTR::null(),
+            SubnetComment: PWSTR::null(),
+            State: 0,
+            ScopeId: 0,
+        };
+
+        match unsafe { DhcpCreateSubnetV6(serveripaddress, to_dhcp_ipv6(prefix), &mut subnetinfo) } {
+            0 => match Self::get(serveripaddress, prefix)? {
+                Some(subnet) => Ok(subnet),
+                None => Err(0),
+            },
+            n => Err(n),
+        }
+    }
+
+    pub fn remove(serveripaddress: &HSTRING, prefix: Ipv6Addr) -> WinDhcpResult<()> {
+        match unsafe { DhcpDeleteSubnetV6(serveripaddress, to_dhcp_ipv6(&prefix), DhcpFullForce) } {
+            0 => Ok(()),
+            e => Err(WinDhcpError::new("removing subnet", e)),
+        }
+    }
+
+    pub fn set_name(&self, name: &str) -> WinDhcpResult<()> {
+        let mut subnetinfo = self.get_subnet_info()
+            .map_err(|e| WinDhcpError::new("setting subnet name", e))?;
+
+        let mut wname = name.encode_utf16().chain([0u16]).collect::<Vec<u16>>();
+        subnetinfo.SubnetName = PWSTR(wname.as_mut_ptr());
+
+        self.set_subnet_info(subnetinfo)
+            .map_err(|e| WinDhcpError::new("setting subnet name", e))
+    }
+
+    pub fn set_comment(&self, comment: &str) -> WinDhcpResult<()> {
+        let mut subnetinfo = self.get_subnet_info()
+            .map_err(|e| WinDhcpError::new("setting subnet comment", e))?;
+
+        let mut wcomment = comment.encode_utf16().chain([0u16]).collect::<Vec<u16>>();
+        subnetinfo.SubnetComment = PWSTR(wcomment.as_mut_ptr());
+
+        self.set_subnet_info(subnetinfo)
+            .map_err(|e| WinDhcpError::new("setting subnet comment", e))
+    }
+
+    pub fn set_preference(&self, preference: u16) -> WinDhcpResult<()> {
+        let mut subnetinfo = self.get_subnet_info()
+            .map_err(|e| WinDhcpError::new("setting subnet preference", e))?;
+
+        subnetinfo.Preference = preference;
+
+        self.set_subnet_info(subnetinfo)
+            .map_err(|e| WinDhcpError::new("setting subnet preference", e))
+    }
+
+    pub fn get_lifetimes(&self) -> WinDhcpResult<(u32, u32)> {
+        let mut scopeinfo = self.scopeinfo();
+        let mut configinfo: *mut DHCP_SERVER_CONFIG_INFO_V6 = ptr::null_mut();
+
+        match unsafe { DhcpServerGetConfigV6(&self.serveripaddress, &mut scopeinfo, &mut configinfo) } {
+            0 => (),
+            e => return Err(WinDhcpError::new("getting lifetimes", e)),
+        }
+
+        let lifetimes = unsafe { ((*configinfo).PreferredLifetime, (*configinfo).ValidLifetime) };
+
+        #[cfg(feature = "rpc_free")]
+        unsafe { DhcpRpcFreeMemory(configinfo as *mut c_void) };
+
+        Ok(lifetimes)
+    }
+
+    pub fn set_lifetimes(&self, preferred_lifetime: u32, valid_lifetime: u32) -> WinDhcpResult<()> {
+        let mut scopeinfo = self.scopeinfo();
+        let mut configinfo = DHCP_SERVER_CONFIG_INFO_V6 {
+            PreferredLifetime: preferred_lifetime,
+            ValidLifetime: valid_lifetime,
+            ..Default::default()
+        };
+
+        match unsafe {
+            DhcpServerSetConfigV6(
+                &self.serveripaddress,
+                &mut scopeinfo,
+                Set_PreferredLifetime | Set_ValidLifetime,
+                &mut configinfo,
+            )
+        } {
+            0 => Ok(()),
+            e => Err(WinDhcpError::new("setting lifetimes", e)),
+        }
+    }
+
+    fn get_option_strings(&self, optionid: u32, ipv6: bool) -> WinDhcpResult<Vec<String>> {
+        let mut scopeinfo = self.scopeinfo();
+        let mut optionvalue: *mut DHCP_OPTION_VALUE = ptr::null_mut();
+
+        match unsafe {
+            DhcpGetOptionValueV6(
+                &self.serveripaddress,
+                0x00,
+                optionid,
+                PCWSTR::null(),
+                PCWSTR::null(),
+                &mut scopeinfo,
+                &mut optionvalue,
+            )
+        } {
+            0 => (),
+            2 => return Ok(Vec::new()),
+            e => return Err(WinDhcpError::new("getting option", e)),
+        }
+
+        let len = unsafe { (*optionvalue).Value.NumElements };
+
+        let mut strings = Vec::with_capacity(len as usize);
+
+        for idx in 0..len {
+            let element = unsafe { (*optionvalue).Value.Elements.offset(idx.try_into().unwrap()) };
+            let value = match ipv6 {
+                true => unsafe { (*element).Element.Ipv6AddressDataOption.to_string().unwrap_or_default() },
+                false => unsafe { (*element).Element.StringDataOption.to_string().unwrap_or_default() },
+            };
+            strings.push(value);
+        }
+
+        #[cfg(feature = "rpc_free")]
+        unsafe { DhcpRpcFreeMemory(optionvalue as *mut c_void) };
+
+        Ok(strings)
+    }
+
+    fn set_option_strings(&self, optionid: u32, set_values: &[String], ipv6: bool) -> WinDhcpResult<()> {
+        let mut scopeinfo = self.scopeinfo();
+
+        if set_values.is_empty() {
+            return match unsafe {
+                DhcpRemoveOptionValueV6(
+                    &self.serveripaddress,
+                    0x00,
+                    optionid,
+                    PCWSTR::null(),
+                    PCWSTR::null(),
+                    &mut scopeinfo,
+                )
+            } {
+                0 | 2 => Ok(()),
+                e => Err(WinDhcpError::new("removing option", e)),
+            };
+        }
+
+        let mut set_values_u16 = set_values.iter()
+            .map(|s| s.encode_utf16().chain([0u16]).collect::<Vec<u16>>())
+            .collect::<Vec<Vec<u16>>>();
+
+        let mut values = set_values_u16.iter_mut().map(|i| match ipv6 {
+            true => DHCP_OPTION_DATA_ELEMENT {
+                OptionType: DhcpIpv6AddressOption,
+                Element: DHCP_OPTION_DATA_ELEMENT_0 { Ipv6AddressDataOption: PWSTR(i.as_mut_ptr()) },
+            },
+            false => DHCP_OPTION_DATA_ELEMENT {
+                OptionType: DhcpStringDataOption,
+                Element: DHCP_OPTION_DATA_ELEMENT_0 { StringDataOption: PWSTR(i.as_mut_ptr()) },
+            },
+        }).collect::<Vec<DHCP_OPTION_DATA_ELEMENT>>();
+
+        let mut optionvalue = DHCP_OPTION_DATA {
+            NumElements: values.len() as u32,
+            Elements: values.as_mut_ptr(),
+        };
+
+        match unsafe {
+            DhcpSetOptionValueV6(
+                &self.serveripaddress,
+                0x00,
+                optionid,
+                PCWSTR::null(),
+                PCWSTR::null(),
+                &mut scopeinfo,
+                &mut optionvalue,
+            )
+        } {
+            0 => Ok(()),
+            e => Err(WinDhcpError::new("setting option", e)),
+        }
+    }
+
+    pub fn get_dns_servers(&self) -> WinDhcpResult<Vec<Ipv6Addr>> {
+        Ok(self.get_option_strings(OPTION_V6_DNS_SERVERS, true)?
+            .iter().filter_map(|s| s.parse().ok()).collect())
+    }
+
+    pub fn set_dns_servers(&self, servers: &[Ipv6Addr]) -> WinDhcpResult<()> {
+        let servers = servers.iter().map(|s| s.to_string()).collect::<Vec<String>>();
+        self.set_option_strings(OPTION_V6_DNS_SERVERS, &servers, true)
+    }
+
+    pub fn get_domain_search(&self) -> WinDhcpResult<Vec<String>> {
+        self.get_option_strings(OPTION_V6_DOMAIN_LIST, false)
+    }
+
+    pub fn set_domain_search(&self, domains: &[String]) -> WinDhcpResult<()> {
+        self.set_option_strings(OPTION_V6_DOMAIN_LIST, domains, false)
+    }
+
+    pub fn get_reservations(&self) -> WinDhcpResult<Vec<ReservationV6Info>> {
+        let mut resumehandle: u32 = 0;
+        let mut elementsread: u32 = 0;
+        let mut elementstotal: u32 = 0;
+
+        let mut enumelementinfo: *mut DHCP_SUBNET_ELEMENT_INFO_ARRAY_V6 = ptr::null_mut();
+
+        match unsafe {
+            DhcpEnumSubnetElementsV6(
+                &self.serveripaddress,
+                self.subnetaddress(),
+                Dhcpv6ReservedIps,
+                &mut resumehandle,
+                0xFFFFFFFF,
+                &mut enumelementinfo,
+                &mut elementsread,
+                &mut elementstotal,
+            )
+        } {
+            0 => (),
+            //ERROR_NO_MORE_ITEMS
+            259 => return Ok(Vec::new()),
+            e => return Err(WinDhcpError::new("getting reservations", e)),
+        }
+
+        let mut reservations = Vec::new();
+
+        for idx in 0usize..unsafe { (*enumelementinfo).NumElements.try_into().unwrap() } {
+            let res = unsafe { *(*(*enumelementinfo).Elements.offset(idx.try_into().unwrap())).Element.ReservedIp };
+            let duid = unsafe {
+                let data = *res.ReservedForClient;
+                std::slice::from_raw_parts(data.Data, data.DataLength as usize).to_vec()
+            };
+            reservations.push(ReservationV6Info {
+                ip_address: from_dhcp_ipv6(&res.ReservedIpAddress),
+                duid,
+                iaid: res.InterfaceId,
+            });
+        }
+
+        #[cfg(feature = "rpc_free")]
+        unsafe {
+            for idx in 0usize..(*enumelementinfo).NumElements.try_into().unwrap() {
+                DhcpRpcFreeMemory((*(*enumelementinfo).Elements.offset(idx.try_into().unwrap())).Element.ReservedIp as *mut c_void);
+            }
+            DhcpRpcFreeMemory((*enumelementinfo).Elements as *mut c_void);
+            DhcpRpcFreeMemory(enumelementinfo as *mut c_void);
+        }
+
+        Ok(reservations)
+    }
+
+    fn reservation_element(&self, address: Ipv6Addr, duid: &[u8], iaid: u32, call: impl FnOnce(&mut DHCP_SUBNET_ELEMENT_DATA_V6) -> u32) -> u32 {
+        let mut duid = duid.to_owned();
+        let mut for_client = DHCP_BINARY_DATA {
+            DataLength: duid.len().try_into().unwrap(),
+            Data: duid[..].as_mut_ptr(),
+        };
+
+        let mut reserved_ip = DHCP_IP_RESERVATION_V6 {
+            ReservedIpAddress: to_dhcp_ipv6(&address),
+            ReservedForClient: &mut for_client,
+            InterfaceId: iaid,
+        };
+
+        let mut elementinfo = DHCP_SUBNET_ELEMENT_DATA_V6 {
+            ElementType: Dhcpv6ReservedIps,
+            Element: DHCP_SUBNET_ELEMENT_DATA_V6_0 { ReservedIp: &mut reserved_ip },
+        };
+
+        call(&mut elementinfo)
+    }
+
+    pub fn add_reservation(&self, address: Ipv6Addr, duid: &[u8], iaid: u32) -> WinDhcpResult<()> {
+        match self.reservation_element(address, duid, iaid, |element| unsafe {
+            DhcpAddSubnetElementV6(&self.serveripaddress, self.subnetaddress(), element)
+        }) {
+            0 => Ok(()),
+            e => Err(WinDhcpError::new("adding reservation", e)),
+        }
+    }
+
+    pub fn remove_reservation(&self, address: Ipv6Addr, duid: &[u8], iaid: u32) -> WinDhcpResult<()> {
+        match self.reservation_element(address, duid, iaid, |element| unsafe {
+            DhcpRemoveSubnetElementV6(&self.serveripaddress, self.subnetaddress(), element, DhcpFullForce)
+        }) {
+            0 => Ok(()),
+            e => Err(WinDhcpError::new("removing reservation", e)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_converts_ipv6_addresses() {
+        let address: Ipv6Addr = "2001:db8::1:2".parse().unwrap();
+        let dhcp = to_dhcp_ipv6(&address);
+        assert_eq!(dhcp.HighOrderBits, 0x2001_0db8_0000_0000);
+        assert_eq!(dhcp.LowOrderBits, 0x0000_0000_0001_0002);
+        assert_eq!(from_dhcp_ipv6(&dhcp), address);
+    }
+}